@@ -0,0 +1,607 @@
+use failure::Error;
+use file_watcher::FileWatcher;
+use mesh::Mesh;
+use palette::Palette;
+use shader::Shader;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use texture::Texture;
+use vox_loader::{VoxLoader, ASSETS_DIRECTORY};
+use vox_scene::VoxSceneNode;
+use voxel::chunk::Chunk;
+use voxel::voxel_mesh_builder::{build_mesh_with_mode, MeshingMode};
+
+/// The key of `Palette::default()` among the palettes, so the materials using it share it.
+const DEFAULT_PALETTE_KEY: &str = "default";
+
+/// A cheap reference to an asset of an `AssetManager`, which components store instead of the
+/// asset itself.
+pub struct Handle<T> {
+    id: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            marker: PhantomData,
+        }
+    }
+}
+
+// Implemented by hand since deriving them would require `T` to implement them as well.
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Handle({})", self.id)
+    }
+}
+
+/// The assets of one type, along with the handles of the ones loaded from a path.
+struct AssetStorage<T> {
+    assets: Vec<Option<T>>,
+    handles_by_path: HashMap<String, Handle<T>>,
+}
+
+impl<T> Default for AssetStorage<T> {
+    fn default() -> Self {
+        Self {
+            assets: Vec::new(),
+            handles_by_path: HashMap::new(),
+        }
+    }
+}
+
+impl<T> AssetStorage<T> {
+    fn insert(&mut self, asset: T) -> Handle<T> {
+        self.assets.push(Some(asset));
+
+        Handle::new(self.assets.len() - 1)
+    }
+
+    /// Return the handle of the asset loaded from `path`, calling `load` the first time only. A
+    /// failed load is not cached, so it is tried again on the next call.
+    fn load<F>(&mut self, path: &str, load: F) -> Result<Handle<T>, Error>
+    where
+        F: FnOnce() -> Result<T, Error>,
+    {
+        if let Some(handle) = self.handles_by_path.get(path) {
+            return Ok(*handle);
+        }
+
+        let handle = self.insert(load()?);
+        self.handles_by_path.insert(path.to_string(), handle);
+
+        Ok(handle)
+    }
+
+    fn replace(&mut self, handle: Handle<T>, asset: T) {
+        if let Some(previous_asset) = self.assets.get_mut(handle.id) {
+            *previous_asset = Some(asset);
+        }
+    }
+
+    fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.assets.get(handle.id).and_then(Option::as_ref)
+    }
+
+    fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.assets.get_mut(handle.id).and_then(Option::as_mut)
+    }
+
+    fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        self.handles_by_path.retain(|_, loaded_handle| *loaded_handle != handle);
+        self.assets.get_mut(handle.id).and_then(Option::take)
+    }
+}
+
+/// The first model of a `.vox` file along with the palette to render it. Models without any solid
+/// voxel have no mesh.
+#[derive(Debug, Clone)]
+pub struct VoxModel {
+    pub mesh: Option<Handle<Mesh>>,
+    pub palette: Handle<Palette>,
+}
+
+/// Every model of a `.vox` file along with the palette to render them and the nodes placing them.
+/// The meshes are in the order of `VoxScene::models`, models without any solid voxel have none.
+#[derive(Debug, Clone)]
+pub struct VoxSceneModels {
+    pub meshes: Vec<Option<Handle<Mesh>>>,
+    pub palette: Handle<Palette>,
+    pub nodes: Vec<VoxSceneNode>,
+}
+
+/// What a shader is built from, to build it again when one of its files changes.
+struct ShaderSources {
+    handle: Handle<Shader>,
+    vertex_path: String,
+    fragment_path: String,
+    defines: Vec<(String, String)>,
+}
+
+/// Load each asset once and hand out handles to it. Assets stay loaded until they are removed. The
+/// `MeshRemoval` system removes the meshes of the removed `MeshRender`s which no other `MeshRender`
+/// uses, which frees their GL objects.
+///
+/// The files of the shaders, along with their includes, and of the `.vox` models and scenes are
+/// watched, see `reload_changed_assets()`.
+#[derive(Default)]
+pub struct AssetManager {
+    meshes: AssetStorage<Mesh>,
+    textures: AssetStorage<Texture>,
+    shaders: AssetStorage<Shader>,
+    vox_models: AssetStorage<VoxModel>,
+    vox_scenes: AssetStorage<VoxSceneModels>,
+    palettes: AssetStorage<Palette>,
+    file_watcher: FileWatcher,
+    shader_sources: Vec<ShaderSources>,
+    vox_sources: Vec<(Handle<VoxModel>, String)>,
+    vox_scene_sources: Vec<(Handle<VoxSceneModels>, String)>,
+    /// The error of each asset which failed to reload, by path.
+    reload_errors: HashMap<String, String>,
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load_texture(&mut self, path: &str) -> Result<Handle<Texture>, Error> {
+        self.textures.load(path, || Texture::new(path))
+    }
+
+    pub fn load_shader(&mut self, vertex_path: &str, fragment_path: &str) -> Result<Handle<Shader>, Error> {
+        self.load_shader_with_defines(vertex_path, fragment_path, &[])
+    }
+
+    /// Load a variant of a shader, see `Shader::with_defines`. Each set of defines is a different
+    /// shader.
+    pub fn load_shader_with_defines(
+        &mut self,
+        vertex_path: &str,
+        fragment_path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Handle<Shader>, Error> {
+        let key = shader_key(vertex_path, fragment_path, defines);
+        let handle = self
+            .shaders
+            .load(&key, || Ok(Shader::with_defines(vertex_path, fragment_path, defines)?))?;
+
+        if !self.shader_sources.iter().any(|sources| sources.handle == handle) {
+            if let Some(shader) = self.shaders.get(handle) {
+                for file in shader.source_files() {
+                    self.file_watcher.watch(file);
+                }
+            }
+
+            self.shader_sources.push(ShaderSources {
+                handle,
+                vertex_path: vertex_path.to_string(),
+                fragment_path: fragment_path.to_string(),
+                defines: defines
+                    .iter()
+                    .map(|&(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            });
+        }
+
+        Ok(handle)
+    }
+
+    /// Load a `.vox` file of the assets directory and build the mesh of its first model with
+    /// greedy meshing.
+    pub fn load_vox(&mut self, asset_name: &str) -> Result<Handle<VoxModel>, Error> {
+        let meshes = &mut self.meshes;
+        let palettes = &mut self.palettes;
+        let handle = self.vox_models.load(asset_name, || {
+            let (chunk, palette) = VoxLoader::load(asset_name)?;
+
+            Ok(VoxModel {
+                mesh: build_vox_mesh(meshes, &chunk),
+                palette: palettes.insert(palette),
+            })
+        })?;
+
+        if !self.vox_sources.iter().any(|&(source_handle, _)| source_handle == handle) {
+            self.file_watcher.watch(&vox_path(asset_name));
+            self.vox_sources.push((handle, asset_name.to_string()));
+        }
+
+        Ok(handle)
+    }
+
+    /// Load every model of a `.vox` file of the assets directory, along with its scene graph, and
+    /// build their meshes with greedy meshing. See `spawn_vox_scene()` to create its entities.
+    pub fn load_vox_scene(&mut self, asset_name: &str) -> Result<Handle<VoxSceneModels>, Error> {
+        let meshes = &mut self.meshes;
+        let palettes = &mut self.palettes;
+        let handle = self.vox_scenes.load(asset_name, || {
+            let scene = VoxLoader::load_scene(asset_name)?;
+
+            Ok(VoxSceneModels {
+                meshes: scene
+                    .models
+                    .iter()
+                    .map(|chunk| build_vox_mesh(meshes, chunk))
+                    .collect(),
+                palette: palettes.insert(scene.palette),
+                nodes: scene.nodes,
+            })
+        })?;
+
+        if !self.vox_scene_sources.iter().any(|&(source_handle, _)| source_handle == handle) {
+            self.file_watcher.watch(&vox_path(asset_name));
+            self.vox_scene_sources.push((handle, asset_name.to_string()));
+        }
+
+        Ok(handle)
+    }
+
+    /// Reload the shaders, `.vox` models and `.vox` scenes whose files changed since the last call.
+    /// A shader which fails to build keeps its previous version, and the error stays in
+    /// `reload_errors()` until it builds again. The meshes and palettes of the models are replaced
+    /// in place, so the entities spawned from them are updated as well. The nodes of a scene are
+    /// only used by the entities spawned afterward.
+    pub fn reload_changed_assets(&mut self) {
+        let changed_files = self.file_watcher.changed_files();
+
+        if changed_files.is_empty() {
+            return;
+        }
+
+        let is_changed = |path: &str| changed_files.iter().any(|changed_file| changed_file == path);
+
+        for sources in &self.shader_sources {
+            let handle = sources.handle;
+            let (vertex_path, fragment_path) = (&sources.vertex_path, &sources.fragment_path);
+            // The included files are watched as well.
+            let is_shader_changed = match self.shaders.get(handle) {
+                Some(shader) => shader.source_files().iter().any(|file| is_changed(file)),
+                None => is_changed(vertex_path) || is_changed(fragment_path),
+            };
+
+            if !is_shader_changed {
+                continue;
+            }
+
+            let defines: Vec<(&str, &str)> = sources
+                .defines
+                .iter()
+                .map(|&(ref name, ref value)| (name.as_str(), value.as_str()))
+                .collect();
+            let key = shader_key(vertex_path, fragment_path, &defines);
+
+            match Shader::with_defines(vertex_path, fragment_path, &defines) {
+                Ok(shader) => {
+                    if let Some(previous_shader) = self.shaders.get(handle) {
+                        unsafe { shader.copy_uniforms_from(previous_shader) };
+                    }
+
+                    for file in shader.source_files() {
+                        self.file_watcher.watch(file);
+                    }
+
+                    self.shaders.replace(handle, shader);
+                    self.reload_errors.remove(&key);
+                    info!("Reloaded the shader of '{}' and '{}'", vertex_path, fragment_path);
+                }
+                Err(error) => {
+                    error!("{}", error);
+                    self.reload_errors.insert(key, error.to_string());
+                }
+            }
+        }
+
+        for &(handle, ref asset_name) in &self.vox_sources {
+            let path = vox_path(asset_name);
+
+            if !is_changed(&path) {
+                continue;
+            }
+
+            let (chunk, palette) = match VoxLoader::load(asset_name) {
+                Ok(model) => model,
+                Err(error) => {
+                    error!("{}", error);
+                    self.reload_errors.insert(path, error.to_string());
+                    continue;
+                }
+            };
+
+            if let Some(vox_model) = self.vox_models.get_mut(handle) {
+                update_vox_mesh(&mut self.meshes, &mut vox_model.mesh, &chunk);
+                self.palettes.replace(vox_model.palette, palette);
+            }
+
+            self.reload_errors.remove(&path);
+            info!("Reloaded '{}'", asset_name);
+        }
+
+        for &(handle, ref asset_name) in &self.vox_scene_sources {
+            let path = vox_path(asset_name);
+
+            if !is_changed(&path) {
+                continue;
+            }
+
+            let scene = match VoxLoader::load_scene(asset_name) {
+                Ok(scene) => scene,
+                Err(error) => {
+                    error!("{}", error);
+                    self.reload_errors.insert(path, error.to_string());
+                    continue;
+                }
+            };
+
+            if let Some(vox_scene) = self.vox_scenes.get_mut(handle) {
+                // The meshes of removed models are kept, the entities spawned before use them.
+                if scene.models.len() > vox_scene.meshes.len() {
+                    vox_scene.meshes.resize(scene.models.len(), None);
+                }
+
+                for (mesh, chunk) in vox_scene.meshes.iter_mut().zip(&scene.models) {
+                    update_vox_mesh(&mut self.meshes, mesh, chunk);
+                }
+
+                self.palettes.replace(vox_scene.palette, scene.palette);
+                vox_scene.nodes = scene.nodes;
+            }
+
+            self.reload_errors.remove(&path);
+            info!("Reloaded '{}'", asset_name);
+        }
+    }
+
+    /// The errors of the assets which failed to reload, to show them on screen.
+    pub fn reload_errors(&self) -> Vec<&str> {
+        self.reload_errors.values().map(String::as_str).collect()
+    }
+
+    /// Add a mesh built at runtime, such as the mesh of a chunk. It is removed by
+    /// `remove_unused_meshes()` once the `MeshRender`s using it are removed.
+    pub fn add_mesh(&mut self, mesh: Mesh) -> Handle<Mesh> {
+        self.meshes.insert(mesh)
+    }
+
+    pub fn remove_mesh(&mut self, handle: Handle<Mesh>) -> Option<Mesh> {
+        self.meshes.remove(handle)
+    }
+
+    /// Remove the meshes of `removed_meshes`, the meshes of the removed `MeshRender`s, which are
+    /// not in `used_meshes` and return how many were removed. The meshes of the `.vox` models and
+    /// scenes stay loaded, as `load_vox()` and `load_vox_scene()` hand them out again.
+    pub fn remove_unused_meshes(
+        &mut self,
+        removed_meshes: &[Handle<Mesh>],
+        used_meshes: &HashSet<Handle<Mesh>>,
+    ) -> usize {
+        let vox_model_meshes = self
+            .vox_models
+            .assets
+            .iter()
+            .filter_map(|vox_model| vox_model.as_ref().and_then(|vox_model| vox_model.mesh));
+        let vox_scene_meshes = self
+            .vox_scenes
+            .assets
+            .iter()
+            .filter_map(Option::as_ref)
+            .flat_map(|vox_scene| vox_scene.meshes.iter().filter_map(|mesh| *mesh));
+        let owned_meshes: HashSet<Handle<Mesh>> = vox_model_meshes.chain(vox_scene_meshes).collect();
+
+        select_unused_meshes(removed_meshes, used_meshes, &owned_meshes)
+            .into_iter()
+            .filter(|handle| self.meshes.remove(*handle).is_some())
+            .count()
+    }
+
+    pub fn mesh(&self, handle: Handle<Mesh>) -> Option<&Mesh> {
+        self.meshes.get(handle)
+    }
+
+    pub fn mesh_mut(&mut self, handle: Handle<Mesh>) -> Option<&mut Mesh> {
+        self.meshes.get_mut(handle)
+    }
+
+    /// Add a palette, such as the one of a `.vox` scene, to share it between materials.
+    pub fn add_palette(&mut self, palette: Palette) -> Handle<Palette> {
+        self.palettes.insert(palette)
+    }
+
+    /// Return the handle of `Palette::default()`, which is only added by the first call.
+    pub fn default_palette(&mut self) -> Handle<Palette> {
+        self.palettes
+            .load(DEFAULT_PALETTE_KEY, || Ok(Palette::default()))
+            .expect("the default palette cannot fail to load")
+    }
+
+    pub fn palette(&self, handle: Handle<Palette>) -> Option<&Palette> {
+        self.palettes.get(handle)
+    }
+
+    pub fn texture(&self, handle: Handle<Texture>) -> Option<&Texture> {
+        self.textures.get(handle)
+    }
+
+    pub fn shader(&self, handle: Handle<Shader>) -> Option<&Shader> {
+        self.shaders.get(handle)
+    }
+
+    pub fn vox_model(&self, handle: Handle<VoxModel>) -> Option<&VoxModel> {
+        self.vox_models.get(handle)
+    }
+
+    pub fn vox_scene(&self, handle: Handle<VoxSceneModels>) -> Option<&VoxSceneModels> {
+        self.vox_scenes.get(handle)
+    }
+}
+
+/// Build the mesh of a `.vox` model with greedy meshing, or return `None` if it has no solid voxel.
+fn build_vox_mesh(meshes: &mut AssetStorage<Mesh>, chunk: &Chunk) -> Option<Handle<Mesh>> {
+    let mesh_data = build_mesh_with_mode(chunk, MeshingMode::Greedy);
+
+    if mesh_data.vertices.is_empty() {
+        None
+    } else {
+        Some(meshes.insert(Mesh::new(mesh_data, Vec::default())))
+    }
+}
+
+/// Rebuild the mesh of a reloaded `.vox` model in place, creating it if the model had no solid
+/// voxel before.
+fn update_vox_mesh(meshes: &mut AssetStorage<Mesh>, mesh: &mut Option<Handle<Mesh>>, chunk: &Chunk) {
+    match *mesh {
+        Some(handle) => {
+            if let Some(mesh) = meshes.get_mut(handle) {
+                mesh.update(&build_mesh_with_mode(chunk, MeshingMode::Greedy));
+            }
+        }
+        None => *mesh = build_vox_mesh(meshes, chunk),
+    }
+}
+
+/// Return the meshes of `removed_meshes` which are neither in `used_meshes` nor in `owned_meshes`,
+/// each one once.
+fn select_unused_meshes(
+    removed_meshes: &[Handle<Mesh>],
+    used_meshes: &HashSet<Handle<Mesh>>,
+    owned_meshes: &HashSet<Handle<Mesh>>,
+) -> Vec<Handle<Mesh>> {
+    let mut selected_meshes = HashSet::new();
+
+    removed_meshes
+        .iter()
+        .cloned()
+        .filter(|handle| !used_meshes.contains(handle) && !owned_meshes.contains(handle))
+        .filter(|handle| selected_meshes.insert(*handle))
+        .collect()
+}
+
+fn shader_key(vertex_path: &str, fragment_path: &str, defines: &[(&str, &str)]) -> String {
+    let mut key = format!("{}:{}", vertex_path, fragment_path);
+
+    for &(name, value) in defines {
+        key.push_str(&format!(":{}={}", name, value));
+    }
+
+    key
+}
+
+fn vox_path(asset_name: &str) -> String {
+    format!("{}/{}", ASSETS_DIRECTORY, asset_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{select_unused_meshes, AssetManager, AssetStorage, Handle};
+    use mesh::Mesh;
+    use palette::Palette;
+    use std::collections::HashSet;
+
+    #[test]
+    fn load_assets_once() {
+        let mut storage: AssetStorage<String> = AssetStorage::default();
+        let mut loads = 0;
+
+        let first = storage
+            .load("a.txt", || {
+                loads += 1;
+                Ok("a".to_string())
+            })
+            .unwrap();
+        let second = storage.load("a.txt", || Ok("other".to_string())).unwrap();
+        let other = storage.load("b.txt", || Ok("b".to_string())).unwrap();
+
+        assert_eq!(loads, 1);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(storage.get(first).map(String::as_str), Some("a"));
+    }
+
+    #[test]
+    fn retry_failed_loads() {
+        let mut storage: AssetStorage<String> = AssetStorage::default();
+
+        assert!(storage.load("a.txt", || Err(format_err!("missing file"))).is_err());
+
+        let handle = storage.load("a.txt", || Ok("a".to_string())).unwrap();
+
+        assert_eq!(storage.get(handle).map(String::as_str), Some("a"));
+    }
+
+    #[test]
+    fn remove_assets() {
+        let mut storage: AssetStorage<String> = AssetStorage::default();
+        let handle = storage.load("a.txt", || Ok("a".to_string())).unwrap();
+
+        assert_eq!(storage.remove(handle), Some("a".to_string()));
+        assert_eq!(storage.get(handle), None);
+
+        let reloaded_handle = storage.load("a.txt", || Ok("a again".to_string())).unwrap();
+
+        assert_ne!(handle, reloaded_handle);
+        assert_eq!(storage.get(reloaded_handle).map(String::as_str), Some("a again"));
+    }
+
+    #[test]
+    fn select_the_unused_meshes_among_the_removed_ones() {
+        let meshes: Vec<Handle<Mesh>> = (0..4).map(Handle::new).collect();
+        let used_meshes: HashSet<Handle<Mesh>> = [meshes[1]].iter().cloned().collect();
+        let owned_meshes: HashSet<Handle<Mesh>> = [meshes[2]].iter().cloned().collect();
+
+        // `meshes[3]` was never used by a removed `MeshRender`, like a mesh not attached yet.
+        let unused_meshes = select_unused_meshes(
+            &[meshes[0], meshes[1], meshes[2], meshes[0]],
+            &used_meshes,
+            &owned_meshes,
+        );
+
+        assert_eq!(unused_meshes, vec![meshes[0]]);
+    }
+
+    #[test]
+    fn share_the_default_palette() {
+        let mut assets = AssetManager::new();
+        let default_palette = assets.default_palette();
+
+        assert_eq!(assets.default_palette(), default_palette, "materials should batch with it");
+        assert_ne!(assets.add_palette(Palette::default()), default_palette);
+    }
+}
@@ -0,0 +1,188 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3, Vector4};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// The smallest box containing the points, `None` when there is no point.
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+    {
+        let mut points = points.into_iter();
+        let first_point = points.next()?;
+
+        Some(points.fold(Self::new(first_point, first_point), |aabb, point| Self {
+            min: Point3::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y), aabb.min.z.min(point.z)),
+            max: Point3::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y), aabb.max.z.max(point.z)),
+        }))
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// The box containing this one once transformed by `matrix`, such as the world matrix of a
+    /// mesh. It is larger than the transformed box when the matrix has a rotation.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let corners = self.corners();
+
+        Self::from_points(corners.iter().map(|corner| matrix.transform_point(*corner)))
+            .expect("a box should have corners")
+    }
+}
+
+/// The six planes of the frustum of a camera, pointing inward.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Each plane is `(normal, distance)`, a point `p` being inside when
+    /// `normal.dot(p) + distance >= 0`.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extract the frustum of a view projection matrix, in world space.
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self {
+        let row = |index: usize| view_projection.row(index);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ];
+
+        Self {
+            planes: [
+                normalize_plane(planes[0]),
+                normalize_plane(planes[1]),
+                normalize_plane(planes[2]),
+                normalize_plane(planes[3]),
+                normalize_plane(planes[4]),
+                normalize_plane(planes[5]),
+            ],
+        }
+    }
+
+    /// Whether a part of the box may be inside the frustum. Boxes near the corners of the frustum
+    /// can be reported inside while they are not, which only costs a draw call.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box the farthest along the normal of the plane.
+            let farthest_corner = Vector3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+
+            plane.truncate().dot(farthest_corner) + plane.w >= 0.0
+        })
+    }
+}
+
+fn normalize_plane(plane: Vector4<f32>) -> Vector4<f32> {
+    let length = plane.truncate().magnitude();
+
+    if length > 0.0 {
+        plane / length
+    } else {
+        plane
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aabb, Frustum};
+    use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+
+    fn unit_box_at(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(Point3::new(x - 0.5, y - 0.5, z - 0.5), Point3::new(x + 0.5, y + 0.5, z + 0.5))
+    }
+
+    /// A camera at the origin looking along -z, seeing from 1 to 100.
+    fn camera_frustum() -> Frustum {
+        let view = Matrix4::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+
+        Frustum::from_matrix(&(perspective(Deg(90.0), 1.0, 1.0, 100.0) * view))
+    }
+
+    #[test]
+    fn bound_points() {
+        let aabb = Aabb::from_points(vec![
+            Point3::new(1.0, -2.0, 3.0),
+            Point3::new(-1.0, 4.0, 0.5),
+            Point3::new(0.0, 0.0, 5.0),
+        ])
+        .unwrap();
+
+        assert_eq!(aabb, Aabb::new(Point3::new(-1.0, -2.0, 0.5), Point3::new(1.0, 4.0, 5.0)));
+        assert_eq!(aabb.center(), Point3::new(0.0, 1.0, 2.75));
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+    }
+
+    #[test]
+    fn transform_boxes() {
+        let aabb = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 1.0, 1.0));
+        let matrix = Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0)) * Matrix4::from_angle_y(Deg(90.0));
+        let transformed = aabb.transform(&matrix);
+
+        let is_close = |point: Point3<f32>, expected: Point3<f32>| {
+            (point.x - expected.x).abs() < 0.001
+                && (point.y - expected.y).abs() < 0.001
+                && (point.z - expected.z).abs() < 0.001
+        };
+        assert!(is_close(transformed.min, Point3::new(10.0, 0.0, -2.0)), "{:?}", transformed);
+        assert!(is_close(transformed.max, Point3::new(11.0, 1.0, 0.0)), "{:?}", transformed);
+    }
+
+    #[test]
+    fn keep_boxes_inside_the_frustum() {
+        let frustum = camera_frustum();
+
+        assert!(frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -10.0)));
+        // Crossing the left plane and the near plane.
+        assert!(frustum.intersects_aabb(&unit_box_at(-10.0, 0.0, -10.0)));
+        assert!(frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -1.0)));
+        // Larger than the frustum.
+        assert!(frustum.intersects_aabb(&Aabb::new(
+            Point3::new(-1000.0, -1000.0, -1000.0),
+            Point3::new(1000.0, 1000.0, 1000.0),
+        )));
+    }
+
+    #[test]
+    fn cull_boxes_outside_of_the_frustum() {
+        let frustum = camera_frustum();
+
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 0.0, 10.0)), "behind the camera");
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -0.2)), "before the near plane");
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 0.0, -102.0)), "after the far plane");
+        assert!(!frustum.intersects_aabb(&unit_box_at(-20.0, 0.0, -10.0)), "on the left");
+        assert!(!frustum.intersects_aabb(&unit_box_at(0.0, 20.0, -10.0)), "above");
+    }
+}
@@ -1,7 +1,38 @@
+use cgmath::{perspective, Deg, Matrix4};
+use config::{SCR_HEIGHT, SCR_WIDTH};
 use specs::{Component, VecStorage};
 
-#[derive(Debug)]
-pub struct Camera;
+/// A perspective camera looking along the forward direction of the entity. The camera of the
+/// `ActiveCamera` resource renders the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            fov: 45.0,
+            aspect_ratio: SCR_WIDTH as f32 / SCR_HEIGHT as f32,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        perspective(Deg(self.fov), self.aspect_ratio, self.near, self.far)
+    }
+}
 
 impl Component for Camera {
     type Storage = VecStorage<Self>;
@@ -0,0 +1,63 @@
+use cgmath::Vector3;
+use specs::{Component, VecStorage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    /// Lights every direction from the position of the entity, fading out until `range`.
+    Point { range: f32 },
+    /// Lights along the forward direction of the entity from infinitely far away, like the sun.
+    Directional,
+    /// Lights a cone along the forward direction of the entity. The light is full inside
+    /// `inner_angle` and fades out until `outer_angle`, both in degrees from the direction.
+    Spot {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+/// A light at the position of the `Transform` of the entity. The `LightGathering` system uploads
+/// the lights to the shaders each frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Only the directional and spot lights cast shadows, see `LightGathering` for their limits.
+    #[serde(default)]
+    pub casts_shadows: bool,
+}
+
+impl Light {
+    pub fn point(color: Vector3<f32>, range: f32) -> Self {
+        Self::new(LightKind::Point { range }, color)
+    }
+
+    pub fn directional(color: Vector3<f32>) -> Self {
+        Self::new(LightKind::Directional, color)
+    }
+
+    pub fn spot(color: Vector3<f32>, range: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        Self::new(
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+        )
+    }
+
+    fn new(kind: LightKind, color: Vector3<f32>) -> Self {
+        Self {
+            kind,
+            color,
+            intensity: 1.0,
+            casts_shadows: false,
+        }
+    }
+}
+
+impl Component for Light {
+    type Storage = VecStorage<Self>;
+}
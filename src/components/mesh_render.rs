@@ -1,13 +1,32 @@
-use specs::{Component, VecStorage};
+use asset_manager::Handle;
+use specs::{Component, FlaggedStorage, VecStorage};
 use material::Material;
 use mesh::Mesh;
 
-#[derive(Debug)]
+/// The mesh is an asset of the `AssetManager`, shared by the clones of the component. The storage
+/// is flagged so the `MeshRemoval` system knows when a mesh may have become unused.
+#[derive(Debug, Clone)]
 pub struct MeshRender {
     pub material: Material,
-    pub mesh: Mesh,
+    pub mesh: Handle<Mesh>,
+    /// Whether the mesh is drawn in the shadow maps of the lights.
+    pub casts_shadows: bool,
+    /// Whether the shadows of the other meshes are drawn on the mesh.
+    pub receives_shadows: bool,
+}
+
+impl MeshRender {
+    /// A mesh casting and receiving shadows.
+    pub fn new(material: Material, mesh: Handle<Mesh>) -> Self {
+        Self {
+            material,
+            mesh,
+            casts_shadows: true,
+            receives_shadows: true,
+        }
+    }
 }
 
 impl Component for MeshRender {
-    type Storage = VecStorage<Self>;
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
 }
@@ -0,0 +1,17 @@
+use specs::{Component, VecStorage};
+
+/// Where the mesh of a `MeshRender` comes from, kept so the scene can be saved back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    /// The first model of a `.vox` file in the assets directory, rendered with its palette.
+    Vox(String),
+    /// A chunk described in the scene file, as a list of `(x, y, z, value)` solid voxels.
+    Chunk {
+        size: (u8, u8, u8),
+        voxels: Vec<(u8, u8, u8, u8)>,
+    },
+}
+
+impl Component for MeshSource {
+    type Storage = VecStorage<Self>;
+}
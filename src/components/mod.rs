@@ -3,3 +3,7 @@ pub mod transform;
 pub mod camera;
 pub mod player;
 pub mod parent;
+pub mod mesh_source;
+pub mod voxel_chunk;
+pub mod world_chunk;
+pub mod light;
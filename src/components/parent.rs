@@ -1,9 +1,9 @@
-use specs::{Component, DenseVecStorage, Entity};
+use specs::{Component, DenseVecStorage, Entity, FlaggedStorage};
 
 pub struct Parent {
     pub entity: Entity,
 }
 
 impl Component for Parent {
-    type Storage = DenseVecStorage<Self>;
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
@@ -1,12 +1,11 @@
 // TODO:
-// - rotate_around_world_space(&mut self, rotation: Vector3<f32>)
 // - set_left/set_up
-// - up
 
 use cgmath::prelude::*;
-use cgmath::{Matrix3, Matrix4, Point3, Quaternion, Vector3};
+use cgmath::Transform as TransformMatrix;
+use cgmath::{Deg, Matrix3, Matrix4, Point3, Quaternion, Vector3, Vector4};
 use math::point_to_vector;
-use specs::{Component, VecStorage};
+use specs::{Component, FlaggedStorage, VecStorage};
 use std::f32;
 use std::f32::consts::PI;
 
@@ -15,47 +14,68 @@ const DEG_TO_RAD: f32 = PI / 180.0 as f32;
 
 #[derive(Debug, Clone)]
 pub struct Transform {
-    pub name: &'static str,
+    pub name: String,
     pub local_position: Point3<f32>,
     pub local_rotation: Quaternion<f32>,
-    // TODO: Add scale.
+    pub local_scale: Vector3<f32>,
     pub local_matrix: Matrix4<f32>,
     pub world_matrix: Matrix4<f32>,
+    /// The world matrix of the parent, or the identity at the root. It is updated along with
+    /// `world_matrix` by the `Transformation` system.
+    pub parent_world_matrix: Matrix4<f32>,
     pub is_dirty: bool,
 }
 
 impl Transform {
-    pub fn new(local_position: Point3<f32>, name: &'static str) -> Self {
+    pub fn new(local_position: Point3<f32>, name: &str) -> Self {
         let up = Vector3::unit_y();
         let forward = Vector3::unit_z();
         let local_rotation = Quaternion::look_at(forward, up);
-        let local_rotation_matrix = Matrix4::from(local_rotation);
 
-        let translation = Matrix4::from_translation(point_to_vector(local_position));
-        let local_matrix: Matrix4<f32> = translation * local_rotation_matrix;
-        // let local_matrix: Matrix4<f32> = local_rotation_matrix * translation;
-
-        Transform {
-            name,
+        let mut transform = Transform {
+            name: name.to_string(),
             local_position,
             local_rotation,
-            local_matrix,
-            world_matrix: local_matrix,
+            local_scale: Vector3::new(1.0, 1.0, 1.0),
+            local_matrix: Matrix4::identity(),
+            world_matrix: Matrix4::identity(),
+            parent_world_matrix: Matrix4::identity(),
             is_dirty: true,
-        }
+        };
+
+        transform.local_matrix = transform.compute_local_matrix();
+        transform.world_matrix = transform.local_matrix;
+        transform
     }
 
-    pub fn get_normal_matrix(&self) -> Matrix3<f32> {
-        let m = self.world_matrix.invert().unwrap().transpose();
-        let row0 = m.row(0);
-        let row1 = m.row(1);
-        let row2 = m.row(2);
+    /// The local matrix scales, then rotates, then translates.
+    pub fn compute_local_matrix(&self) -> Matrix4<f32> {
+        let translation = Matrix4::from_translation(point_to_vector(self.local_position));
+        let rotation = Matrix4::from(self.local_rotation);
+        let scale = Matrix4::from_nonuniform_scale(
+            self.local_scale.x,
+            self.local_scale.y,
+            self.local_scale.z,
+        );
+
+        translation * rotation * scale
+    }
 
-        Matrix3::<f32>::new(
-            row0.x, row1.x, row2.x,
-            row0.y, row1.y, row2.y,
-            row0.z, row1.z, row2.z,
-        )
+    /// The inverse transpose of the world matrix without its translation, which keeps the normals
+    /// perpendicular to the surfaces under non-uniform scale. A transform with a zero scale has no
+    /// surface to light, so the identity is returned in that case.
+    pub fn get_normal_matrix(&self) -> Matrix3<f32> {
+        let m = self.world_matrix;
+        let world_matrix3 = Matrix3::<f32>::new(
+            m.x.x, m.x.y, m.x.z,
+            m.y.x, m.y.y, m.y.z,
+            m.z.x, m.z.y, m.z.z,
+        );
+
+        match world_matrix3.invert() {
+            Some(inverse) => inverse.transpose(),
+            None => Matrix3::identity(),
+        }
     }
 
     pub fn forward(&self) -> Vector3<f32> {
@@ -65,8 +85,7 @@ impl Transform {
     pub fn set_forward(&mut self, new_forward: Vector3<f32>) {
         let up = Vector3::unit_y();
 
-        self.local_rotation = Quaternion::look_at(new_forward, up);
-        //self.is_local_position_changed = true;
+        self.set_local_rotation(rotation_towards(new_forward, up));
     }
 
     pub fn left(&self) -> Vector3<f32> {
@@ -76,6 +95,77 @@ impl Transform {
         self.forward().cross(down).normalize()
     }
 
+    pub fn right(&self) -> Vector3<f32> {
+        self.local_rotation * -Vector3::unit_x()
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.local_rotation * Vector3::unit_y()
+    }
+
+    /// The world-space methods account for the parents through `parent_world_matrix`, so they are
+    /// up to date with the local values but see the parents as of the last `Transformation` run.
+    pub fn world_position(&self) -> Point3<f32> {
+        self.parent_world_matrix.transform_point(self.local_position)
+    }
+
+    /// Move the transform to `position` in world space, by changing its local position.
+    pub fn set_world_position(&mut self, position: Point3<f32>) {
+        match self.parent_world_matrix.invert() {
+            Some(inverse) => self.set_local_position(inverse.transform_point(position)),
+            None => warn!("Cannot set the world position of {} since its parent has a zero scale", self.name),
+        }
+    }
+
+    pub fn world_rotation(&self) -> Quaternion<f32> {
+        rotation_of(&self.parent_world_matrix) * self.local_rotation
+    }
+
+    pub fn set_world_rotation(&mut self, rotation: Quaternion<f32>) {
+        let parent_rotation = rotation_of(&self.parent_world_matrix);
+
+        self.set_local_rotation(parent_rotation.invert() * rotation);
+    }
+
+    pub fn world_forward(&self) -> Vector3<f32> {
+        self.world_rotation() * Vector3::unit_z()
+    }
+
+    pub fn world_up(&self) -> Vector3<f32> {
+        self.world_rotation() * Vector3::unit_y()
+    }
+
+    pub fn world_right(&self) -> Vector3<f32> {
+        self.world_rotation() * -Vector3::unit_x()
+    }
+
+    /// Rotate the transform so its forward points at `target`, both in world space.
+    pub fn look_at(&mut self, target: Point3<f32>, up: Vector3<f32>) {
+        let direction = target - self.world_position();
+
+        if direction.magnitude2() > 0.0 {
+            self.set_world_rotation(rotation_towards(direction, up));
+        }
+    }
+
+    /// Rotate the transform by `angle` around the world-space `axis` going through `point`. Both
+    /// its position and its rotation change, like a planet orbiting around a star.
+    pub fn rotate_around(&mut self, point: Point3<f32>, axis: Vector3<f32>, angle: Deg<f32>) {
+        let rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        let position = point + rotation * (self.world_position() - point);
+        let world_rotation = rotation * self.world_rotation();
+
+        self.set_world_position(position);
+        self.set_world_rotation(world_rotation);
+    }
+
+    /// Rotate the transform by Euler angles in degrees, around the world axes instead of its own.
+    pub fn rotate_around_world_space(&mut self, rotation: Vector3<f32>) {
+        let world_rotation = euler_to_quaternion(rotation.x, rotation.y, rotation.z) * self.world_rotation();
+
+        self.set_world_rotation(world_rotation);
+    }
+
     /// If `set_position()` has been called this frame, the last function calling will set the
     /// positions.
     pub fn set_local_position(&mut self, position: Point3<f32>) {
@@ -134,6 +224,54 @@ impl Transform {
         self.local_rotation = euler_to_quaternion(x, y, z);
         self.is_dirty = true;
     }
+
+    pub fn set_local_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.local_rotation = rotation;
+        self.is_dirty = true;
+    }
+
+    /// The scale can differ on each axis. It is applied before the rotation.
+    pub fn set_local_scale(&mut self, scale: Vector3<f32>) {
+        self.local_scale = scale;
+        self.is_dirty = true;
+    }
+
+    pub fn set_uniform_scale(&mut self, scale: f32) {
+        self.set_local_scale(Vector3::new(scale, scale, scale));
+    }
+}
+
+/// The rotation turning the z axis towards `forward`, with the y axis as close as possible to
+/// `up`.
+fn rotation_towards(forward: Vector3<f32>, up: Vector3<f32>) -> Quaternion<f32> {
+    let forward = forward.normalize();
+    let mut left = up.cross(forward);
+
+    // Any perpendicular will do when looking straight up or down.
+    if left.magnitude2() < 0.000_001 {
+        let axis = if forward.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_z() };
+        left = axis.cross(forward);
+    }
+
+    let left = left.normalize();
+    let up = forward.cross(left);
+
+    Quaternion::from(Matrix3::from_cols(left, up, forward))
+}
+
+/// The rotation of a matrix without shear, ignoring its scale.
+fn rotation_of(matrix: &Matrix4<f32>) -> Quaternion<f32> {
+    let column = |vector: Vector4<f32>| {
+        let vector = vector.truncate();
+
+        if vector.magnitude2() > 0.0 {
+            vector.normalize()
+        } else {
+            vector
+        }
+    };
+
+    Quaternion::from(Matrix3::from_cols(column(matrix.x), column(matrix.y), column(matrix.z))).normalize()
 }
 
 fn normalize_angles_vector(angles: Vector3<f32>) -> Vector3<f32> {
@@ -184,5 +322,217 @@ fn euler_to_quaternion(x: f32, y: f32, z: f32) -> Quaternion<f32> {
 }
 
 impl Component for Transform {
-    type Storage = VecStorage<Self>;
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transform;
+    use cgmath::prelude::*;
+    use cgmath::Transform as TransformMatrix;
+    use cgmath::{Deg, Matrix3, Point3, Quaternion, Vector3};
+    use components::parent::Parent;
+    use specs::{Builder, RunNow, World};
+    use systems::transformation::Transformation;
+
+    fn assert_near(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 0.0001, "{:?} != {:?}", actual, expected);
+    }
+
+    /// A parent moved and turned a quarter to the left, a scaled child and a grandchild, with
+    /// their world matrices computed like the `Transformation` system does.
+    fn nested_transforms() -> (Transform, Transform, Transform) {
+        let mut parent = Transform::new(Point3::new(1.0, 0.0, 0.0), "Parent");
+        parent.set_rotation(0.0, 90.0, 0.0);
+        parent.world_matrix = parent.compute_local_matrix();
+
+        let mut child = Transform::new(Point3::new(0.0, 0.0, 2.0), "Child");
+        child.set_uniform_scale(2.0);
+        child.parent_world_matrix = parent.world_matrix;
+        child.world_matrix = child.parent_world_matrix * child.compute_local_matrix();
+
+        let mut grandchild = Transform::new(Point3::new(0.0, 1.0, 1.0), "Grandchild");
+        grandchild.parent_world_matrix = child.world_matrix;
+        grandchild.world_matrix = grandchild.parent_world_matrix * grandchild.compute_local_matrix();
+
+        (parent, child, grandchild)
+    }
+
+    #[test]
+    fn scale_local_matrix() {
+        let mut transform = Transform::new(Point3::new(1.0, 2.0, 3.0), "Scaled");
+        transform.set_local_scale(Vector3::new(2.0, 3.0, 4.0));
+        transform.set_rotation(0.0, 90.0, 0.0);
+
+        let point = transform.compute_local_matrix().transform_point(Point3::new(1.0, 1.0, 0.0));
+
+        // Scaled to (2, 3, 0) then rotated around y.
+        assert!((point - Point3::new(1.0, 5.0, 1.0)).magnitude() < 0.0001);
+        assert!(transform.is_dirty);
+    }
+
+    #[test]
+    fn keep_normals_perpendicular_under_non_uniform_scale() {
+        let mut transform = Transform::new(Point3::new(0.0, 0.0, 0.0), "Scaled");
+        transform.set_local_scale(Vector3::new(2.0, 1.0, 1.0));
+        transform.world_matrix = transform.compute_local_matrix();
+
+        let tangent = Vector3::new(1.0, -1.0, 0.0);
+        let normal = Vector3::new(1.0, 1.0, 0.0);
+        let world_tangent = transform.world_matrix.transform_vector(tangent);
+        let world_normal = transform.get_normal_matrix() * normal;
+
+        assert!(world_tangent.dot(world_normal).abs() < 0.0001);
+    }
+
+    #[test]
+    fn normal_matrix_of_zero_scale() {
+        let mut transform = Transform::new(Point3::new(0.0, 0.0, 0.0), "Flat");
+        transform.set_uniform_scale(0.0);
+        transform.world_matrix = transform.compute_local_matrix();
+
+        assert_eq!(transform.get_normal_matrix(), Matrix3::identity());
+    }
+
+    #[test]
+    fn world_position_of_nested_transforms() {
+        let (_parent, child, grandchild) = nested_transforms();
+
+        assert_near(child.world_position().to_vec(), Vector3::new(3.0, 0.0, 0.0));
+        assert_near(grandchild.world_position().to_vec(), Vector3::new(5.0, 2.0, 0.0));
+        assert_near(
+            grandchild.world_position().to_vec(),
+            grandchild.world_matrix.transform_point(Point3::origin()).to_vec(),
+        );
+    }
+
+    #[test]
+    fn set_world_position_of_nested_transforms() {
+        let (_parent, _child, mut grandchild) = nested_transforms();
+
+        grandchild.set_world_position(Point3::new(-1.0, 4.0, 2.0));
+
+        assert_near(grandchild.world_position().to_vec(), Vector3::new(-1.0, 4.0, 2.0));
+        assert_near(grandchild.local_position.to_vec(), Vector3::new(-1.0, 2.0, -2.0));
+        assert!(grandchild.is_dirty);
+    }
+
+    #[test]
+    fn world_rotation_of_nested_transforms() {
+        let (_parent, mut child, _grandchild) = nested_transforms();
+
+        assert_near(child.forward(), Vector3::unit_z());
+        assert_near(child.world_forward(), Vector3::unit_x());
+        assert_near(child.world_up(), Vector3::unit_y());
+        assert_near(child.world_right(), Vector3::unit_z());
+
+        child.set_world_rotation(Quaternion::one());
+
+        assert_near(child.world_forward(), Vector3::unit_z());
+        assert_near(child.forward(), -Vector3::unit_x());
+    }
+
+    #[test]
+    fn look_at_from_nested_transform() {
+        let (_parent, _child, mut grandchild) = nested_transforms();
+
+        grandchild.look_at(Point3::new(5.0, 2.0, 4.0), Vector3::unit_y());
+
+        assert_near(grandchild.world_forward(), Vector3::unit_z());
+        assert_near(grandchild.world_up(), Vector3::unit_y());
+
+        grandchild.look_at(Point3::new(5.0, -1.0, 0.0), Vector3::unit_y());
+
+        assert_near(grandchild.world_forward(), -Vector3::unit_y());
+    }
+
+    #[test]
+    fn rotate_around_point() {
+        let (_parent, mut child, _grandchild) = nested_transforms();
+
+        child.rotate_around(Point3::new(1.0, 0.0, 0.0), Vector3::unit_y(), Deg(90.0));
+
+        assert_near(child.world_position().to_vec(), Vector3::new(1.0, 0.0, -2.0));
+        assert_near(child.world_forward(), -Vector3::unit_z());
+    }
+
+    #[test]
+    fn rotate_around_world_axes() {
+        let (_parent, mut child, _grandchild) = nested_transforms();
+
+        child.rotate_around_world_space(Vector3::new(0.0, 90.0, 0.0));
+
+        assert_near(child.world_forward(), -Vector3::unit_z());
+        assert_near(child.world_position().to_vec(), Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(child.parent_world_matrix, nested_transforms().1.parent_world_matrix);
+    }
+
+    #[test]
+    fn right_of_rolled_root_transform() {
+        let mut transform = Transform::new(Point3::new(0.0, 0.0, 0.0), "Rolled");
+        transform.set_rotation(30.0, 0.0, 45.0);
+
+        assert_near(transform.right(), transform.world_right());
+        assert!(transform.right().dot(transform.up()).abs() < 0.0001);
+    }
+
+    #[test]
+    fn world_position_after_transformation() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let (parent_transform, child_transform, grandchild_transform) = nested_transforms();
+        let parent = world.create_entity().with(parent_transform).build();
+        let child = world
+            .create_entity()
+            .with(child_transform)
+            .with(Parent { entity: parent })
+            .build();
+        let grandchild = world
+            .create_entity()
+            .with(grandchild_transform)
+            .with(Parent { entity: child })
+            .build();
+        let sibling = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 1.0), "Sibling"))
+            .with(Parent { entity: parent })
+            .build();
+        let nephew = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 1.0, 0.0), "Nephew"))
+            .with(Parent { entity: sibling })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        world
+            .write_storage::<Transform>()
+            .get_mut(parent)
+            .unwrap()
+            .set_local_position(Point3::new(2.0, 0.0, 0.0));
+        transformation_system.run_now(&world.res);
+
+        let mut transform_storage = world.write_storage::<Transform>();
+        assert_near(
+            transform_storage.get(grandchild).unwrap().world_position().to_vec(),
+            Vector3::new(6.0, 2.0, 0.0),
+        );
+        assert_near(
+            transform_storage.get(sibling).unwrap().world_position().to_vec(),
+            Vector3::new(3.0, 0.0, 0.0),
+        );
+
+        let nephew_transform = transform_storage.get_mut(nephew).unwrap();
+        assert_near(nephew_transform.world_position().to_vec(), Vector3::new(3.0, 1.0, 0.0));
+        assert_near(nephew_transform.world_forward(), Vector3::unit_x());
+
+        nephew_transform.look_at(Point3::new(3.0, 1.0, 5.0), Vector3::unit_y());
+
+        assert_near(nephew_transform.world_forward(), Vector3::unit_z());
+    }
 }
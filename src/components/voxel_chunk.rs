@@ -0,0 +1,35 @@
+use specs::{Component, VecStorage};
+use voxel::chunk::Chunk;
+use voxel::voxel_mesh_builder::MeshingMode;
+
+/// Voxels which can be edited at runtime. The `ChunkMeshing` system rebuilds the mesh of the
+/// `MeshRender` of the entity when the chunk is dirty.
+#[derive(Debug, Clone)]
+pub struct VoxelChunk {
+    pub chunk: Chunk,
+    meshing_mode: MeshingMode,
+}
+
+impl VoxelChunk {
+    pub fn new(chunk: Chunk) -> Self {
+        Self {
+            chunk,
+            meshing_mode: MeshingMode::Greedy,
+        }
+    }
+
+    pub fn meshing_mode(&self) -> MeshingMode {
+        self.meshing_mode
+    }
+
+    /// Change how the mesh is built. The chunk is marked dirty, so its mesh is rebuilt with the new
+    /// mode.
+    pub fn set_meshing_mode(&mut self, meshing_mode: MeshingMode) {
+        self.meshing_mode = meshing_mode;
+        self.chunk.mark_dirty();
+    }
+}
+
+impl Component for VoxelChunk {
+    type Storage = VecStorage<Self>;
+}
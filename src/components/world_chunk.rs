@@ -0,0 +1,21 @@
+use specs::{Component, VecStorage};
+use voxel::position::Position;
+
+/// The chunk of the `VoxelWorld` resource at these chunk coordinates. Its voxels stay in the
+/// `VoxelWorld`, so edits go through `VoxelWorld::set_voxel`, and the `ChunkMeshing` system
+/// rebuilds the mesh of the `MeshRender` of the entity with its neighbouring chunks when it is
+/// dirty.
+#[derive(Debug, Clone)]
+pub struct WorldChunk {
+    pub position: Position,
+}
+
+impl WorldChunk {
+    pub fn new(position: Position) -> Self {
+        Self { position }
+    }
+}
+
+impl Component for WorldChunk {
+    type Storage = VecStorage<Self>;
+}
@@ -0,0 +1,400 @@
+extern crate gl;
+
+use asset_manager::AssetManager;
+use components::camera::Camera;
+use components::light::Light;
+use components::mesh_render::MeshRender;
+use components::mesh_source::MeshSource;
+use components::parent::Parent;
+use components::player::Player;
+use components::transform::Transform;
+use components::voxel_chunk::VoxelChunk;
+use components::world_chunk::WorldChunk;
+use config::{SCR_HEIGHT, SCR_WIDTH};
+use failure::Error;
+use input::Input;
+use lifecycle::{
+    Event, Lifecycle, DEFAULT_FIXED_UPDATE_PER_SECOND, DEFAULT_UPDATE_PER_SECOND,
+};
+use specs::{Builder, Component, DispatcherBuilder, RunNow, World};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+use systems::asset_reloading::AssetReloading;
+use systems::chunk_meshing::ChunkMeshing;
+use systems::gui_rendering::GuiRendering;
+use systems::light_gathering::LightGathering;
+use systems::mesh_removal::MeshRemoval;
+use systems::mouse_control::MouseControl;
+use systems::player_movement::PlayerMovement;
+use systems::render::Render;
+use systems::transformation::Transformation;
+use systems::window_event::WindowEvent;
+use systems::AfterRender;
+use time::{FixedTime, Time};
+use voxel::world::VoxelWorld;
+use window::Window;
+
+/// The built-in stages of a frame, in the order they run. Games insert their systems before or
+/// after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Window events, mouse control and player movement.
+    Input,
+    /// Computes the world matrices from the `Transform` and `Parent` components.
+    Transformation,
+    /// Reloads the edited asset files, removes the meshes no entity uses anymore, rebuilds the
+    /// meshes of the edited chunks and uploads the lights, then draws the shadow maps and the
+    /// meshes seen by the active camera.
+    Render,
+    /// Draws the debug GUI. The buffers are swapped right after.
+    Gui,
+}
+
+const STAGES: [Stage; 4] = [Stage::Input, Stage::Transformation, Stage::Render, Stage::Gui];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Before,
+    After,
+}
+
+type AddSystem = Box<dyn FnOnce(&mut DispatcherBuilder<'static, 'static>)>;
+type Startup = Box<dyn FnOnce(&mut World) -> Result<(), Error>>;
+
+struct StageSystem {
+    stage: Stage,
+    order: Order,
+    add: AddSystem,
+}
+
+/// Create the window, the world and the dispatcher of a game. The built-in components and the
+/// `Time`, `Input`, `AssetManager` and `VoxelWorld` resources are always registered.
+///
+/// ```no_run
+/// # use bigseed::engine::{EngineBuilder, Stage};
+/// # use bigseed::systems::Rotator;
+/// EngineBuilder::new()
+///     .with_system_before(Stage::Transformation, Rotator::new())
+///     .with_startup(|_world| {
+///         // Create the entities.
+///         Ok(())
+///     })
+///     .run()
+///     .unwrap();
+/// ```
+pub struct EngineBuilder {
+    world: World,
+    systems: Vec<StageSystem>,
+    fixed_systems: Vec<AddSystem>,
+    startups: Vec<Startup>,
+    fixed_updates_per_second: u64,
+    updates_per_second: u64,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        let mut world = World::new();
+
+        world.register::<Transform>();
+        world.register::<MeshRender>();
+        world.register::<MeshSource>();
+        world.register::<Camera>();
+        world.register::<Player>();
+        world.register::<Parent>();
+        world.register::<VoxelChunk>();
+        world.register::<WorldChunk>();
+        world.register::<Light>();
+
+        world.add_resource(Time::new());
+        world.add_resource(Input::new());
+        world.add_resource(AssetManager::new());
+        world.add_resource(VoxelWorld::new());
+
+        Self {
+            world,
+            systems: Vec::new(),
+            fixed_systems: Vec::new(),
+            startups: Vec::new(),
+            fixed_updates_per_second: DEFAULT_FIXED_UPDATE_PER_SECOND,
+            updates_per_second: DEFAULT_UPDATE_PER_SECOND,
+        }
+    }
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_component<C>(mut self) -> Self
+    where
+        C: Component,
+        C::Storage: Default,
+    {
+        self.world.register::<C>();
+        self
+    }
+
+    pub fn with_resource<R>(mut self, resource: R) -> Self
+    where
+        R: Any + Send + Sync,
+    {
+        self.world.add_resource(resource);
+        self
+    }
+
+    /// Run `system` on the main thread right before the built-in systems of `stage`. Systems
+    /// added to the same stage and order run in the order they are added.
+    pub fn with_system_before<S>(self, stage: Stage, system: S) -> Self
+    where
+        S: for<'c> RunNow<'c> + 'static,
+    {
+        self.with_system(stage, Order::Before, system)
+    }
+
+    /// Run `system` on the main thread right after the built-in systems of `stage`.
+    pub fn with_system_after<S>(self, stage: Stage, system: S) -> Self
+    where
+        S: for<'c> RunNow<'c> + 'static,
+    {
+        self.with_system(stage, Order::After, system)
+    }
+
+    /// Run `system` on the main thread at each fixed update, with a constant delta time given by
+    /// the `FixedTime` resource. This is where physics and gameplay go. Fixed systems run in the
+    /// order they are added.
+    pub fn with_fixed_system<S>(mut self, system: S) -> Self
+    where
+        S: for<'c> RunNow<'c> + 'static,
+    {
+        self.fixed_systems.push(Box::new(move |dispatcher_builder| {
+            dispatcher_builder.add_thread_local(system);
+        }));
+        self
+    }
+
+    /// Set the number of fixed updates and frames per second. They default to
+    /// `DEFAULT_FIXED_UPDATE_PER_SECOND` and `DEFAULT_UPDATE_PER_SECOND`.
+    pub fn with_rates(mut self, fixed_updates_per_second: u64, updates_per_second: u64) -> Self {
+        self.fixed_updates_per_second = fixed_updates_per_second;
+        self.updates_per_second = updates_per_second;
+        self
+    }
+
+    /// Call `startup` once the window and the OpenGL context exist, before the first frame. This
+    /// is where the entities of the game are created. Startup closures are called in the order
+    /// they are added.
+    pub fn with_startup<F>(mut self, startup: F) -> Self
+    where
+        F: FnOnce(&mut World) -> Result<(), Error> + 'static,
+    {
+        self.startups.push(Box::new(startup));
+        self
+    }
+
+    /// Open the window and run the game until the window is closed.
+    pub fn run(self) -> Result<(), Error> {
+        let EngineBuilder {
+            mut world,
+            systems,
+            fixed_systems,
+            startups,
+            fixed_updates_per_second,
+            updates_per_second,
+        } = self;
+        let mut event_loop = Lifecycle::with_rates(fixed_updates_per_second, updates_per_second);
+
+        world.add_resource(FixedTime::new(event_loop.get_fixed_delta_time()));
+
+        let window = Rc::new(RefCell::new(Window::new(SCR_WIDTH, SCR_HEIGHT)));
+
+        unsafe {
+            // configure global opengl state
+            // -----------------------------
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        let scene_root_entity = world.create_entity().build();
+
+        for startup in startups {
+            startup(&mut world)?;
+        }
+
+        let mut dispatcher_builder = DispatcherBuilder::new();
+
+        add_systems(&mut dispatcher_builder, systems, &mut |dispatcher_builder, stage| {
+            match stage {
+                Stage::Input => {
+                    dispatcher_builder.add_thread_local(WindowEvent::new(Rc::clone(&window)));
+                    dispatcher_builder.add_thread_local(MouseControl::new(Rc::clone(&window)));
+                    dispatcher_builder.add_thread_local(PlayerMovement::new());
+                }
+                Stage::Transformation => {
+                    dispatcher_builder.add_thread_local(Transformation::new(scene_root_entity));
+                }
+                Stage::Render => {
+                    dispatcher_builder.add_thread_local(AssetReloading::new());
+                    dispatcher_builder.add_thread_local(MeshRemoval::new());
+                    dispatcher_builder.add_thread_local(ChunkMeshing::new());
+                    dispatcher_builder.add_thread_local(LightGathering::new());
+                    dispatcher_builder.add_thread_local(Render::new());
+                }
+                Stage::Gui => {
+                    dispatcher_builder.add_thread_local(GuiRendering::new(Rc::clone(&window)));
+                }
+            }
+        });
+
+        dispatcher_builder.add_thread_local(AfterRender::new(Rc::clone(&window)));
+
+        let mut dispatcher = dispatcher_builder.build();
+        let mut fixed_dispatcher_builder = DispatcherBuilder::new();
+
+        for add_fixed_system in fixed_systems {
+            add_fixed_system(&mut fixed_dispatcher_builder);
+        }
+
+        let mut fixed_dispatcher = fixed_dispatcher_builder.build();
+
+        dispatcher.setup(&mut world.res);
+        fixed_dispatcher.setup(&mut world.res);
+
+        // TODO: Simplify event loop.
+        while let Some(event) = event_loop.next() {
+            match event {
+                Event::FixedUpdate => {
+                    fixed_dispatcher.dispatch(&world.res);
+                    world.maintain();
+                }
+                Event::OnInput => {}
+                Event::Update => {
+                    {
+                        let mut time = world.write_resource::<Time>();
+                        (*time).update();
+                    }
+                    {
+                        let mut fixed_time = world.write_resource::<FixedTime>();
+                        fixed_time.set_alpha(event_loop.get_alpha());
+                    }
+
+                    dispatcher.dispatch(&world.res);
+                    world.maintain();
+
+                    if !window.borrow().running {
+                        return Ok(());
+                    }
+                }
+                Event::Render => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn with_system<S>(mut self, stage: Stage, order: Order, system: S) -> Self
+    where
+        S: for<'c> RunNow<'c> + 'static,
+    {
+        self.systems.push(StageSystem {
+            stage,
+            order,
+            add: Box::new(move |dispatcher_builder| {
+                dispatcher_builder.add_thread_local(system);
+            }),
+        });
+        self
+    }
+}
+
+/// Add the systems of every stage in order, `add_built_in_systems` adds the built-in systems of a
+/// stage between the ones added before and after it.
+fn add_systems(
+    dispatcher_builder: &mut DispatcherBuilder<'static, 'static>,
+    systems: Vec<StageSystem>,
+    add_built_in_systems: &mut dyn FnMut(&mut DispatcherBuilder<'static, 'static>, Stage),
+) {
+    let mut systems: Vec<Option<StageSystem>> = systems.into_iter().map(Some).collect();
+
+    for stage in &STAGES {
+        add_stage_systems(dispatcher_builder, &mut systems, *stage, Order::Before);
+        add_built_in_systems(dispatcher_builder, *stage);
+        add_stage_systems(dispatcher_builder, &mut systems, *stage, Order::After);
+    }
+}
+
+fn add_stage_systems(
+    dispatcher_builder: &mut DispatcherBuilder<'static, 'static>,
+    systems: &mut [Option<StageSystem>],
+    stage: Stage,
+    order: Order,
+) {
+    for system in systems.iter_mut() {
+        let is_in_stage = system
+            .as_ref()
+            .map_or(false, |system| system.stage == stage && system.order == order);
+
+        if is_in_stage {
+            if let Some(system) = system.take() {
+                (system.add)(dispatcher_builder);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_systems, EngineBuilder, Stage};
+    use specs::{DispatcherBuilder, System, Write};
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    struct Logger(String);
+
+    impl<'a> System<'a> for Logger {
+        type SystemData = Write<'a, Log>;
+
+        fn run(&mut self, mut log: Self::SystemData) {
+            log.0.push(self.0.clone());
+        }
+    }
+
+    fn logger(name: &str) -> Logger {
+        Logger(name.to_string())
+    }
+
+    #[test]
+    fn run_systems_around_stages() {
+        let EngineBuilder {
+            world, systems, ..
+        } = EngineBuilder::new()
+            .with_resource(Log::default())
+            .with_system_after(Stage::Render, logger("after render"))
+            .with_system_before(Stage::Transformation, logger("before transformation"))
+            .with_system_before(Stage::Input, logger("before input"))
+            .with_system_before(Stage::Transformation, logger("before transformation 2"))
+            .with_system_after(Stage::Gui, logger("after gui"));
+
+        let mut dispatcher_builder = DispatcherBuilder::new();
+        add_systems(&mut dispatcher_builder, systems, &mut |dispatcher_builder, stage| {
+            dispatcher_builder.add_thread_local(logger(&format!("{:?}", stage)));
+        });
+        dispatcher_builder.build().dispatch(&world.res);
+
+        assert_eq!(
+            world.write_resource::<Log>().0,
+            vec![
+                "before input",
+                "Input",
+                "before transformation",
+                "before transformation 2",
+                "Transformation",
+                "Render",
+                "after render",
+                "Gui",
+                "after gui",
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+/// Detect file changes by polling their modification times, which is enough to reload assets
+/// while editing them.
+#[derive(Debug, Default)]
+pub struct FileWatcher {
+    /// `None` for files which could not be read, so they are reported once they exist again.
+    modification_times: HashMap<String, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn watch(&mut self, path: &str) {
+        if !self.modification_times.contains_key(path) {
+            self.modification_times.insert(path.to_string(), modification_time(path));
+        }
+    }
+
+    /// Return the watched files modified, created or removed since the last call.
+    pub fn changed_files(&mut self) -> Vec<String> {
+        let mut changed_files = Vec::new();
+
+        for (path, last_modification_time) in &mut self.modification_times {
+            let current_modification_time = modification_time(path);
+
+            if current_modification_time != *last_modification_time {
+                *last_modification_time = current_modification_time;
+                changed_files.push(path.clone());
+            }
+        }
+
+        changed_files
+    }
+}
+
+fn modification_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileWatcher;
+    use std::fs;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use temporary_file::TemporaryFile;
+
+    #[test]
+    fn report_changed_files() {
+        let file = TemporaryFile::new("file_watcher_test.txt");
+        let path = file.path();
+        fs::write(path, "first").unwrap();
+
+        let mut file_watcher = FileWatcher::new();
+        file_watcher.watch(path);
+
+        assert!(file_watcher.changed_files().is_empty());
+
+        // Some file systems only store the modification time in seconds.
+        sleep(Duration::from_millis(1100));
+        fs::write(path, "second").unwrap();
+
+        assert_eq!(file_watcher.changed_files(), vec![path.to_string()]);
+        assert!(file_watcher.changed_files().is_empty());
+
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(file_watcher.changed_files(), vec![path.to_string()]);
+    }
+}
@@ -0,0 +1,160 @@
+use gl;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The kinds of OpenGL objects owned by a `GlHandle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlObjectKind {
+    VertexArray,
+    Buffer,
+    Texture,
+    Program,
+    Framebuffer,
+}
+
+static LIVE_VERTEX_ARRAYS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_TEXTURES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_FRAMEBUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The objects whose last handle has been dropped, waiting to be deleted on the thread owning
+    /// the OpenGL context.
+    static ref DELETION_QUEUE: Mutex<Vec<(GlObjectKind, u32)>> = Mutex::new(Vec::new());
+}
+
+fn live_counter(kind: GlObjectKind) -> &'static AtomicUsize {
+    match kind {
+        GlObjectKind::VertexArray => &LIVE_VERTEX_ARRAYS,
+        GlObjectKind::Buffer => &LIVE_BUFFERS,
+        GlObjectKind::Texture => &LIVE_TEXTURES,
+        GlObjectKind::Program => &LIVE_PROGRAMS,
+        GlObjectKind::Framebuffer => &LIVE_FRAMEBUFFERS,
+    }
+}
+
+/// The number of OpenGL objects created through a `GlHandle` and not deleted yet. A count which
+/// keeps growing while the scene does not is a leak.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlObjectCounts {
+    pub vertex_arrays: usize,
+    pub buffers: usize,
+    pub textures: usize,
+    pub programs: usize,
+    pub framebuffers: usize,
+}
+
+impl GlObjectCounts {
+    pub fn live() -> Self {
+        Self {
+            vertex_arrays: LIVE_VERTEX_ARRAYS.load(Ordering::SeqCst),
+            buffers: LIVE_BUFFERS.load(Ordering::SeqCst),
+            textures: LIVE_TEXTURES.load(Ordering::SeqCst),
+            programs: LIVE_PROGRAMS.load(Ordering::SeqCst),
+            framebuffers: LIVE_FRAMEBUFFERS.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GlObject {
+    kind: GlObjectKind,
+    id: u32,
+}
+
+impl Drop for GlObject {
+    /// Components can be dropped outside of the OpenGL thread, so the deletion is deferred to
+    /// `delete_dropped_gl_objects()`.
+    fn drop(&mut self) {
+        DELETION_QUEUE
+            .lock()
+            .expect("the deletion queue should not be poisoned")
+            .push((self.kind, self.id));
+    }
+}
+
+/// A reference counted OpenGL object, deleted once its last handle is dropped. Clones share the
+/// same object.
+#[derive(Debug, Clone)]
+pub struct GlHandle(Arc<GlObject>);
+
+impl GlHandle {
+    /// Take the ownership of an object created with `gl::Gen*` or `gl::Create*`.
+    pub fn new(kind: GlObjectKind, id: u32) -> Self {
+        live_counter(kind).fetch_add(1, Ordering::SeqCst);
+
+        GlHandle(Arc::new(GlObject { kind, id }))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    pub fn kind(&self) -> GlObjectKind {
+        self.0.kind
+    }
+}
+
+/// Delete the objects whose last handle has been dropped. It must be called on the thread owning
+/// the OpenGL context, which the `AfterRender` system does after each frame.
+pub fn delete_dropped_gl_objects() {
+    let dropped_objects = {
+        let mut deletion_queue = DELETION_QUEUE
+            .lock()
+            .expect("the deletion queue should not be poisoned");
+
+        mem::replace(&mut *deletion_queue, Vec::new())
+    };
+
+    for (kind, id) in dropped_objects {
+        unsafe {
+            match kind {
+                GlObjectKind::VertexArray => gl::DeleteVertexArrays(1, &id),
+                GlObjectKind::Buffer => gl::DeleteBuffers(1, &id),
+                GlObjectKind::Texture => gl::DeleteTextures(1, &id),
+                GlObjectKind::Program => gl::DeleteProgram(id),
+                GlObjectKind::Framebuffer => gl::DeleteFramebuffers(1, &id),
+            }
+        }
+
+        live_counter(kind).fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{live_counter, GlHandle, GlObjectCounts, GlObjectKind, DELETION_QUEUE};
+    use std::sync::atomic::Ordering;
+
+    /// Remove the object from the queue, so no test tries to delete it without an OpenGL context.
+    /// It is no longer counted as live, as if it had been deleted.
+    fn take_from_deletion_queue(id: u32) -> bool {
+        let mut deletion_queue = DELETION_QUEUE.lock().unwrap();
+        let length = deletion_queue.len();
+
+        deletion_queue.retain(|&(kind, queued_id)| kind != GlObjectKind::Texture || queued_id != id);
+        let removed_count = length - deletion_queue.len();
+        live_counter(GlObjectKind::Texture).fetch_sub(removed_count, Ordering::SeqCst);
+
+        removed_count > 0
+    }
+
+    #[test]
+    fn queue_objects_when_their_last_handle_is_dropped() {
+        let id = 4_000_000_000;
+        let handle = GlHandle::new(GlObjectKind::Texture, id);
+        let clone = handle.clone();
+
+        assert!(GlObjectCounts::live().textures >= 1);
+
+        drop(handle);
+
+        assert!(!take_from_deletion_queue(id), "a clone still uses the texture");
+
+        drop(clone);
+
+        assert!(take_from_deletion_queue(id), "the texture should be deleted");
+    }
+}
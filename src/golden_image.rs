@@ -0,0 +1,203 @@
+use failure::Error;
+use image;
+use image::RgbaImage;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Set this environment variable to `1` to overwrite the golden images with the current rendering.
+pub const UPDATE_GOLDEN_IMAGES_ENV: &str = "UPDATE_GOLDEN_IMAGES";
+
+/// Return the mean absolute difference between the channels of two images, between 0 (same
+/// images) and 1, or `None` if their sizes differ.
+pub fn image_difference(image: &RgbaImage, other: &RgbaImage) -> Option<f32> {
+    if image.dimensions() != other.dimensions() {
+        return None;
+    }
+
+    let channel_count = image.len();
+
+    if channel_count == 0 {
+        return Some(0.0);
+    }
+
+    let total_difference: u64 = image
+        .iter()
+        .zip(other.iter())
+        .map(|(a, b)| u64::from(if a > b { a - b } else { b - a }))
+        .sum();
+
+    Some(total_difference as f32 / (channel_count as f32 * 255.0))
+}
+
+/// Compare an image to the golden image stored at `golden_path`. `tolerance` is the maximum
+/// `image_difference` accepted. When `UPDATE_GOLDEN_IMAGES=1`, the image is saved as the new golden
+/// image instead, which is also the only way to create a missing one.
+pub fn compare_to_golden_image(image: &RgbaImage, golden_path: &str, tolerance: f32) -> Result<(), Error> {
+    let update_golden_images = env::var(UPDATE_GOLDEN_IMAGES_ENV).map(|value| value == "1").unwrap_or(false);
+
+    if update_golden_images {
+        if let Some(directory) = Path::new(golden_path).parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        return image
+            .save(golden_path)
+            .map_err(|error| format_err!("Cannot save the golden image at '{}': {}", golden_path, error));
+    }
+
+    if !Path::new(golden_path).exists() {
+        return Err(format_err!(
+            "The golden image at '{}' does not exist, render it with {}=1, check it and commit it.",
+            golden_path,
+            UPDATE_GOLDEN_IMAGES_ENV,
+        ));
+    }
+
+    let golden_image = image::open(golden_path)
+        .map_err(|error| format_err!("Cannot open the golden image at '{}': {}", golden_path, error))?
+        .to_rgba();
+
+    let difference = image_difference(image, &golden_image).ok_or_else(|| {
+        format_err!(
+            "The image is {:?} while the golden image at '{}' is {:?}.",
+            image.dimensions(),
+            golden_path,
+            golden_image.dimensions(),
+        )
+    })?;
+
+    if difference > tolerance {
+        let actual_path = env::temp_dir().join(
+            Path::new(golden_path)
+                .file_name()
+                .map_or_else(|| "golden.png".into(), |name| name.to_owned()),
+        );
+        let _ = image.save(&actual_path);
+
+        return Err(format_err!(
+            "The image differs from the golden image at '{}' by {} (tolerance {}), the rendering has been saved at '{}'.",
+            golden_path,
+            difference,
+            tolerance,
+            actual_path.display(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_to_golden_image, image_difference};
+    use cgmath::{Point3, Vector3};
+    use asset_manager::AssetManager;
+    use components::camera::Camera;
+    use components::light::Light;
+    use components::mesh_render::MeshRender;
+    use components::parent::Parent;
+    use components::transform::Transform;
+    use gl;
+    use headless::HeadlessContext;
+    use image::{Rgba, RgbaImage};
+    use material::Material;
+    use mesh::Mesh;
+    use resources::active_camera::ActiveCamera;
+    use specs::{Builder, RunNow, World};
+    use std::env;
+    use std::path::Path;
+    use systems::light_gathering::LightGathering;
+    use systems::render::Render;
+    use systems::transformation::Transformation;
+    use vox_loader::VoxLoader;
+    use voxel::voxel_mesh_builder::build_mesh;
+
+    const GOLDEN_IMAGES_DIRECTORY: &str = "./resources/tests";
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    #[test]
+    fn difference_between_images() {
+        let black = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let mut one_white_pixel = black.clone();
+        one_white_pixel.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+
+        assert_eq!(image_difference(&black, &black), Some(0.0));
+        assert_eq!(image_difference(&black, &one_white_pixel), Some(3.0 / 16.0));
+        assert_eq!(image_difference(&black, &RgbaImage::new(1, 1)), None);
+    }
+
+    #[test]
+    fn fail_on_missing_golden_images() {
+        let golden_path = env::temp_dir().join("missing_golden_image.png");
+        let golden_path = golden_path.to_str().unwrap();
+
+        assert!(compare_to_golden_image(&RgbaImage::new(1, 1), golden_path, 0.0).is_err());
+        assert!(!Path::new(golden_path).exists());
+    }
+
+    /// Needs an OpenGL driver, see `HeadlessContext`.
+    #[test]
+    #[ignore]
+    fn render_chr_old() {
+        let context = HeadlessContext::new(WIDTH, HEIGHT).unwrap();
+        let (chunk, palette) = VoxLoader::load("chr_old.vox").unwrap();
+        let mut assets = AssetManager::new();
+        let mut material = Material::new(&mut assets).unwrap();
+        let palette = assets.add_palette(palette);
+        material.set_palette(palette);
+        let mesh = assets.add_mesh(Mesh::new(build_mesh(&chunk), Vec::default()));
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<MeshRender>();
+        world.register::<Camera>();
+        world.register::<Parent>();
+        world.register::<Light>();
+        world.add_resource(assets);
+
+        let scene_root_entity = world.create_entity().build();
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        let mut light_gathering_system = LightGathering::new();
+        let mut render_system = Render::new();
+
+        // Look down at the model from its front.
+        let mut camera_transform = Transform::new(Point3::new(9.5, 18.0, 45.0), "Camera");
+        camera_transform.set_rotation(15.0, 180.0, 0.0);
+
+        let camera = Camera {
+            aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+            ..Camera::default()
+        };
+        let camera_entity = world.create_entity().with(camera_transform).with(camera).build();
+        world.add_resource(ActiveCamera(camera_entity));
+
+        world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "chr_old"))
+            .with(MeshRender::new(material, mesh))
+            .build();
+        // The range is large enough for the light to be barely attenuated at the model.
+        world
+            .create_entity()
+            .with(Transform::new(Point3::new(30.0, 30.0, 30.0), "Light"))
+            .with(Light::point(Vector3::new(1.0, 1.0, 1.0), 100_000.0))
+            .build();
+
+        transformation_system.setup(&mut world.res);
+        light_gathering_system.setup(&mut world.res);
+        render_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+        light_gathering_system.run_now(&world.res);
+        render_system.run_now(&world.res);
+
+        let image = context.read_pixels();
+        let golden_path = format!("{}/chr_old.png", GOLDEN_IMAGES_DIRECTORY);
+
+        compare_to_golden_image(&image, &golden_path, 0.01).unwrap();
+    }
+}
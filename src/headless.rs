@@ -0,0 +1,153 @@
+extern crate gl;
+extern crate glutin;
+
+use self::glutin::{Api, GlContext, GlProfile, GlRequest, HeadlessRendererBuilder};
+use failure::Error;
+use image::RgbaImage;
+use std::os::raw::c_void;
+use std::sync::{Mutex, MutexGuard};
+
+lazy_static! {
+    /// Held by the living headless context, as the OpenGL functions are loaded globally and the
+    /// tests compare the global `GlObjectCounts`.
+    static ref CONTEXT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An OpenGL context without any visible window, rendering into an offscreen framebuffer which
+/// can be read back as an image.
+///
+/// On Linux, glutin creates headless contexts with OSMesa, Mesa's offscreen software rasterizer,
+/// so neither a GPU nor a display is needed, only `libOSMesa.so` (the `libosmesa6-dev` package on
+/// Debian).
+///
+/// Only one headless context exists at a time, `new` waits until the previous one is dropped.
+pub struct HeadlessContext {
+    context: glutin::HeadlessContext,
+    framebuffer: u32,
+    color_renderbuffer: u32,
+    depth_renderbuffer: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Declared last so it is released once the context is destroyed.
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl HeadlessContext {
+    /// Create the context, make it current, load the OpenGL functions and bind a `width` x
+    /// `height` framebuffer so everything drawn afterward ends up in it.
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        // A test which panicked while holding the lock left no context behind.
+        let lock = CONTEXT_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+
+        // The shaders target OpenGL 3.3 core.
+        let context = HeadlessRendererBuilder::new(width, height)
+            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+            .with_gl_profile(GlProfile::Core)
+            .build()
+            .map_err(|error| format_err!("Cannot create the headless OpenGL context: {}", error))?;
+
+        unsafe {
+            context
+                .make_current()
+                .map_err(|error| format_err!("Cannot make the headless OpenGL context current: {:?}", error))?;
+        }
+
+        gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+
+        let (framebuffer, color_renderbuffer, depth_renderbuffer) = unsafe {
+            let mut framebuffer = 0;
+            let mut color_renderbuffer = 0;
+            let mut depth_renderbuffer = 0;
+
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+
+            gl::GenRenderbuffers(1, &mut color_renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, color_renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width as i32, height as i32);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                color_renderbuffer,
+            );
+
+            gl::GenRenderbuffers(1, &mut depth_renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_renderbuffer,
+            );
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format_err!("The headless framebuffer is not complete"));
+            }
+
+            gl::Viewport(0, 0, width as i32, height as i32);
+
+            (framebuffer, color_renderbuffer, depth_renderbuffer)
+        };
+
+        Ok(Self {
+            context,
+            framebuffer,
+            color_renderbuffer,
+            depth_renderbuffer,
+            width,
+            height,
+            _lock: lock,
+        })
+    }
+
+    /// Read the content of the framebuffer. OpenGL stores the rows from the bottom to the top, so
+    /// they are flipped to get the image the right way up.
+    pub fn read_pixels(&self) -> RgbaImage {
+        let row_size = self.width as usize * 4;
+        let mut pixels: Vec<u8> = vec![0; row_size * self.height as usize];
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+
+        let flipped_pixels: Vec<u8> = pixels
+            .chunks(row_size)
+            .rev()
+            .flat_map(|row| row.iter().cloned())
+            .collect();
+
+        RgbaImage::from_raw(self.width, self.height, flipped_pixels)
+            .expect("the pixels should fit the image size")
+    }
+
+    pub fn context(&self) -> &glutin::HeadlessContext {
+        &self.context
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteRenderbuffers(1, &self.color_renderbuffer);
+            gl::DeleteRenderbuffers(1, &self.depth_renderbuffer);
+        }
+    }
+}
@@ -5,18 +5,39 @@ extern crate floating_duration;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate log;
 extern crate specs;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate ron;
+#[macro_use] extern crate lazy_static;
 
 pub mod errors;
 pub mod window;
+pub mod engine;
+pub mod headless;
+pub mod golden_image;
 pub mod shader;
+pub mod shader_preprocessor;
+pub mod shadows;
+pub mod shadow_map;
+pub mod bounds;
+pub mod uniform_buffer;
+pub mod render_queue;
 pub mod macros;
 pub mod input;
 pub mod vox_loader;
+pub mod vox_scene;
+pub mod vox_writer;
+pub mod scene;
+pub mod scene_loader;
+pub mod scene_writer;
 pub mod voxel;
 pub mod vertex;
 pub mod mesh;
 pub mod mesh_data;
 pub mod texture;
+pub mod gl_object;
+pub mod asset_manager;
+pub mod file_watcher;
 pub mod material;
 pub mod components;
 pub mod resources;
@@ -28,5 +49,8 @@ pub mod time;
 pub mod scene_tree;
 pub mod palette;
 
+#[cfg(test)]
+mod temporary_file;
+
 mod game;
 pub use self::game::game;
@@ -3,8 +3,11 @@ use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 static BILLION: u64 = 1_000_000_000;
-const DEFAULT_FIXED_UPDATE_PER_SECOND: u64 = 120;
-const DEFAULT_UPDATE_PER_SECOND: u64 = 60;
+pub const DEFAULT_FIXED_UPDATE_PER_SECOND: u64 = 120;
+pub const DEFAULT_UPDATE_PER_SECOND: u64 = 60;
+/// When the fixed updates are late by more than this number of steps, for example after a long
+/// frame, only this number of steps is run to catch up and the older ones are dropped.
+const MAX_FIXED_UPDATES_BEHIND: u32 = 8;
 
 fn ns_to_duration(ns: u64) -> Duration {
     let secs = ns / BILLION;
@@ -12,6 +15,29 @@ fn ns_to_duration(ns: u64) -> Duration {
     Duration::new(secs, nanos)
 }
 
+fn duration_to_seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / BILLION as f32
+}
+
+/// The source of time of `Lifecycle`, so tests can control it.
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&mut self, duration: Duration);
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        sleep(duration);
+    }
+}
+
 #[derive(Debug)]
 pub enum Event {
     FixedUpdate,
@@ -28,58 +54,102 @@ enum State {
     Render,
 }
 
-pub struct Lifecycle {
+pub struct Lifecycle<C: Clock = SystemClock> {
+    clock: C,
     state: State,
     last_fixed_update_time: Instant,
     last_update_time: Instant,
     fixed_delta_time: Duration,
     delta_time: Duration,
+    alpha: f32,
 }
 
 impl Default for Lifecycle {
     fn default() -> Self {
-        let start = Instant::now();
+        Self::with_rates(DEFAULT_FIXED_UPDATE_PER_SECOND, DEFAULT_UPDATE_PER_SECOND)
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Lifecycle {
+        Default::default()
+    }
+
+    pub fn with_rates(fixed_updates_per_second: u64, updates_per_second: u64) -> Lifecycle {
+        Self::with_clock(SystemClock, fixed_updates_per_second, updates_per_second)
+    }
+}
+
+impl<C: Clock> Lifecycle<C> {
+    pub fn with_clock(clock: C, fixed_updates_per_second: u64, updates_per_second: u64) -> Self {
+        assert!(
+            fixed_updates_per_second > 0 && updates_per_second > 0,
+            "the rates should be greater than 0"
+        );
+
+        let start = clock.now();
 
         Lifecycle {
-            state: State::FixedUpdate,
+            clock,
+            state: State::Schedule,
             last_fixed_update_time: start,
             last_update_time: start,
-            fixed_delta_time: ns_to_duration(BILLION / DEFAULT_FIXED_UPDATE_PER_SECOND),
-            delta_time: ns_to_duration(BILLION / DEFAULT_UPDATE_PER_SECOND),
+            fixed_delta_time: ns_to_duration(BILLION / fixed_updates_per_second),
+            delta_time: ns_to_duration(BILLION / updates_per_second),
+            alpha: 0.0,
         }
     }
-}
 
-// TODO:
-//   - pass return real delta time
-impl Lifecycle {
-    pub fn new() -> Lifecycle {
-        Default::default()
+    pub fn get_fixed_delta_time(&self) -> Duration {
+        self.fixed_delta_time
+    }
+
+    /// How far the last `Update` is between the last fixed update and the next one, from 0 to 1.
+    /// Rendering can interpolate the fixed update states with it.
+    pub fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
     }
 
+    /// Return the next event, sleeping until it is due. The fixed updates are run at a constant
+    /// rate and before any late frame.
     pub fn next(&mut self) -> Option<Event> {
         loop {
             self.state = match self.state {
                 State::Schedule => {
-                    let current_time = Instant::now();
+                    let current_time = self.clock.now();
                     let next_frame = self.last_update_time + self.delta_time;
-                    let next_update = self.last_fixed_update_time + self.fixed_delta_time;
-                    let next_event = cmp::min(next_frame, next_update);
+                    let next_fixed_update = self.last_fixed_update_time + self.fixed_delta_time;
 
-                    if next_event > current_time {
-                        sleep(next_event - current_time);
-                        State::Schedule
-                    } else if next_event == next_frame {
+                    if next_fixed_update <= current_time {
+                        State::FixedUpdate
+                    } else if next_frame <= current_time {
                         State::OnInput
                     } else {
-                        State::FixedUpdate
+                        let next_event = cmp::min(next_frame, next_fixed_update);
+
+                        self.clock.sleep(next_event - current_time);
+                        State::Schedule
                     }
                 }
                 State::FixedUpdate => {
-                    let current_time = Instant::now();
-                    // let delta_time = current_time - self.last_fixed_update_time;
+                    let current_time = self.clock.now();
+
+                    // Only the last `MAX_FIXED_UPDATES_BEHIND` late steps are kept.
+                    if current_time - self.last_fixed_update_time
+                        > self.fixed_delta_time * MAX_FIXED_UPDATES_BEHIND
+                    {
+                        self.last_fixed_update_time =
+                            current_time - self.fixed_delta_time * MAX_FIXED_UPDATES_BEHIND;
+                    }
+
+                    // Fixed updates are stepped from the previous one, not from the current time,
+                    // so they do not drift.
+                    self.last_fixed_update_time += self.fixed_delta_time;
 
-                    self.last_fixed_update_time = current_time;
                     self.state = State::Schedule;
                     return Some(Event::FixedUpdate);
                 }
@@ -88,9 +158,12 @@ impl Lifecycle {
                     return Some(Event::OnInput);
                 }
                 State::Update => {
-                    let current_time = Instant::now();
-                    // let delta_time = current_time - self.last_update_time;
+                    let current_time = self.clock.now();
+                    let since_fixed_update = current_time - self.last_fixed_update_time;
 
+                    self.alpha = (duration_to_seconds(since_fixed_update)
+                        / duration_to_seconds(self.fixed_delta_time))
+                    .min(1.0);
                     self.last_update_time = current_time;
                     self.state = State::Render;
                     return Some(Event::Update);
@@ -103,3 +176,120 @@ impl Lifecycle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Event, Lifecycle, MAX_FIXED_UPDATES_BEHIND};
+    use std::time::{Duration, Instant};
+
+    /// A clock which only moves forward when sleeping or when told to.
+    struct MockClock {
+        now: Instant,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self { now: Instant::now() }
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration;
+        }
+    }
+
+    #[test]
+    fn run_events_at_their_rates() {
+        let mut lifecycle = Lifecycle::with_clock(MockClock::new(), 100, 50);
+        let start = lifecycle.clock().now();
+        let mut fixed_updates = 0;
+        let mut updates = 0;
+
+        while updates < 50 {
+            match lifecycle.next().unwrap() {
+                Event::FixedUpdate => fixed_updates += 1,
+                Event::Update => updates += 1,
+                _ => {}
+            }
+        }
+
+        assert_eq!(lifecycle.clock().now() - start, Duration::from_secs(1));
+        assert_eq!(fixed_updates, 100);
+        assert_eq!(lifecycle.get_fixed_delta_time(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn order_fixed_updates_before_frames() {
+        let mut lifecycle = Lifecycle::with_clock(MockClock::new(), 100, 100);
+        let events: Vec<String> = (0..8)
+            .map(|_| format!("{:?}", lifecycle.next().unwrap()))
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                "FixedUpdate", "OnInput", "Update", "Render",
+                "FixedUpdate", "OnInput", "Update", "Render",
+            ]
+        );
+    }
+
+    #[test]
+    fn compute_alpha() {
+        let mut lifecycle = Lifecycle::with_clock(MockClock::new(), 10, 1000);
+
+        // First fixed update at 100ms, then frames every millisecond.
+        while let Some(event) = lifecycle.next() {
+            if let Event::FixedUpdate = event {
+                break;
+            }
+        }
+
+        lifecycle.clock.sleep(Duration::from_millis(25));
+
+        while let Some(event) = lifecycle.next() {
+            if let Event::Update = event {
+                break;
+            }
+        }
+
+        assert!((lifecycle.get_alpha() - 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn catch_up_late_fixed_updates() {
+        let mut lifecycle = Lifecycle::with_clock(MockClock::new(), 100, 100);
+
+        lifecycle.clock.sleep(Duration::from_millis(50));
+
+        let mut fixed_updates = 0;
+        while let Some(Event::FixedUpdate) = lifecycle.next() {
+            fixed_updates += 1;
+        }
+
+        assert_eq!(fixed_updates, 5, "it should run the late fixed updates before the frame");
+
+        // Update and Render.
+        lifecycle.next();
+        lifecycle.next();
+
+        // A long frame should not run every late fixed update.
+        lifecycle.clock.sleep(Duration::from_secs(10));
+
+        let mut fixed_updates = 0;
+        loop {
+            match lifecycle.next().unwrap() {
+                Event::FixedUpdate => fixed_updates += 1,
+                Event::Update => break,
+                _ => {}
+            }
+        }
+
+        assert_eq!(fixed_updates, MAX_FIXED_UPDATES_BEHIND);
+    }
+}
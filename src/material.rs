@@ -1,49 +1,111 @@
+use asset_manager::{AssetManager, Handle};
 use cgmath::{Matrix3, Matrix4, Vector3, Vector4};
+use failure::Error;
+use palette::Palette;
 use shader::Shader;
+use shadows::{CASCADE_COUNT, SHADOW_MAP_LAYERS};
 use std::ffi::{CStr, CString};
+use systems::light_gathering::MAX_LIGHTS;
 use texture::Texture;
 
+const VERTEX_SHADER_PATH: &str = "src/shaders/chunk.vs";
+const FRAGMENT_SHADER_PATH: &str = "src/shaders/chunk.fs";
+const TEXTURE_PATH: &str = "resources/textures/container.jpg";
+
+/// What the materials drawn one after another must share to be bound only once. The keys are
+/// ordered by the cost of a change: the shader first, then the texture, then the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MaterialKey {
+    shader: Handle<Shader>,
+    texture: Handle<Texture>,
+    palette: Handle<Palette>,
+}
+
+/// The shader, texture and palette of a material are assets of the `AssetManager`, so materials
+/// are cheap to clone.
 #[derive(Debug, Clone)]
 pub struct Material {
-    shader: Shader,
-    texture: Texture,
+    shader: Handle<Shader>,
+    texture: Handle<Texture>,
+    palette: Handle<Palette>,
 }
 
-impl Default for Material {
-    fn default() -> Self {
-        // build and compile our shader program
-        // ------------------------------------
-        let shader = Shader::new("src/shaders/chunk.vs", "src/shaders/chunk.fs");
-
-        // load and create a texture
-        // -------------------------
-        // texture 1
-        // ---------
-        let texture = Texture::new("resources/textures/container.jpg");
+impl Material {
+    /// The material of the voxel meshes, with the default palette. Its shader and texture are only
+    /// loaded by the first call.
+    pub fn new(assets: &mut AssetManager) -> Result<Self, Error> {
+        Ok(Self {
+            shader: assets.load_shader_with_defines(
+                VERTEX_SHADER_PATH,
+                FRAGMENT_SHADER_PATH,
+                &[
+                    ("MAX_LIGHTS", &MAX_LIGHTS.to_string()),
+                    ("CASCADE_COUNT", &CASCADE_COUNT.to_string()),
+                    ("SHADOW_MAP_LAYERS", &SHADOW_MAP_LAYERS.to_string()),
+                ],
+            )?,
+            texture: assets.load_texture(TEXTURE_PATH)?,
+            palette: assets.default_palette(),
+        })
+    }
 
-        // tell opengl for each sampler to which texture unit it belongs to (only has to be done once)
-        // -------------------------------------------------------------------------------------------
-        unsafe {
-            shader.useProgram();
-            shader.setInt(c_str!("texture1"), texture.get_id() as i32);
+    /// Return a copy of the material using another palette. The shader and texture are shared.
+    pub fn with_palette(&self, palette: Handle<Palette>) -> Self {
+        Self {
+            shader: self.shader,
+            texture: self.texture,
+            palette,
         }
+    }
 
-        Self { shader, texture }
+    pub fn set_palette(&mut self, palette: Handle<Palette>) {
+        self.palette = palette;
     }
-}
 
-impl Material {
-    pub fn new() -> Self {
-        Default::default()
+    pub fn get_palette(&self) -> Handle<Palette> {
+        self.palette
     }
 
-    pub fn bind(&self) {
+    pub fn key(&self) -> MaterialKey {
+        MaterialKey {
+            shader: self.shader,
+            texture: self.texture,
+            palette: self.palette,
+        }
+    }
+
+    /// Use the shader of the material and return it to set the uniforms. The palette is uploaded
+    /// on each bind since materials sharing the same shader may use different palettes.
+    pub fn bind<'a>(&self, assets: &'a AssetManager) -> BoundMaterial<'a> {
+        let shader = assets
+            .shader(self.shader)
+            .expect("the shader of the material should be loaded");
+        let texture = assets
+            .texture(self.texture)
+            .expect("the texture of the material should be loaded");
+        let palette = assets
+            .palette(self.palette)
+            .expect("the palette of the material should be loaded");
+
         unsafe {
-            self.shader.useProgram();
-            self.texture.bind();
+            shader.useProgram();
+            texture.bind();
+            // tell opengl to which texture unit the sampler belongs to
+            shader.setInt(c_str!("texture1"), texture.get_id() as i32);
         }
+
+        let bound_material = BoundMaterial { shader };
+        bound_material.set_vector4_array("palette", palette.as_vector4_array());
+        bound_material
     }
+}
+
+/// A material whose shader is in use, see `Material::bind`.
+pub struct BoundMaterial<'a> {
+    shader: &'a Shader,
+}
 
+impl<'a> BoundMaterial<'a> {
     pub fn set_matrix3(&self, name: &str, matrix: &Matrix3<f32>) {
         let c_string = CString::new(name).unwrap();
         let c_str = c_string.as_c_str();
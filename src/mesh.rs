@@ -1,12 +1,16 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
+use std::cmp;
 use std::mem::size_of;
 use std::os::raw::c_void;
 use std::ptr;
 
 use gl;
 
+use bounds::Aabb;
+use gl_object::{GlHandle, GlObjectKind};
+
 use vertex::Vertex;
 use mesh_data::MeshData;
 
@@ -17,37 +21,82 @@ pub struct Texture {
     pub path: String,
 }
 
-#[derive(Debug, Clone)]
+/// Meshes are not `Clone`, their GPU buffers would be shared by copies whose data could diverge.
+/// Share a mesh through its `AssetManager` handle instead.
+#[derive(Debug)]
 pub struct Mesh {
     /*  Mesh Data  */
     pub vertices: Vec<Vertex>,
     pub indices: Vec<i32>,
     pub textures: Vec<Texture>,
-    pub VAO: u32,
+    /// `None` when the mesh has no vertex.
+    bounds: Option<Aabb>,
 
     /*  Render data  */
-    VBO: u32,
-    EBO: u32,
+    VAO: GlHandle,
+    VBO: GlHandle,
+    EBO: GlHandle,
+    /// The sizes of the buffers in bytes, which can be larger than their data after an update.
+    vertex_buffer_size: isize,
+    index_buffer_size: isize,
 }
 
 impl Mesh {
     pub fn new(mesh_data: MeshData, textures: Vec<Texture>) -> Mesh {
+        // create buffers/arrays
+        let (VAO, VBO, EBO) = unsafe {
+            let (mut VAO, mut VBO, mut EBO) = (0, 0, 0);
+
+            gl::GenVertexArrays(1, &mut VAO);
+            gl::GenBuffers(1, &mut VBO);
+            gl::GenBuffers(1, &mut EBO);
+
+            (
+                GlHandle::new(GlObjectKind::VertexArray, VAO),
+                GlHandle::new(GlObjectKind::Buffer, VBO),
+                GlHandle::new(GlObjectKind::Buffer, EBO),
+            )
+        };
+
         let mut mesh = Mesh {
+            bounds: mesh_data.bounds(),
             vertices: mesh_data.vertices,
             indices: mesh_data.indices,
             textures,
-            VAO: 0,
-            VBO: 0,
-            EBO: 0,
+            VAO,
+            VBO,
+            EBO,
+            vertex_buffer_size: 0,
+            index_buffer_size: 0,
         };
 
         unsafe { mesh.setupMesh() }
         mesh
     }
 
+    /// Replace the geometry of the mesh, reusing its buffers. They are reallocated with
+    /// `gl::DYNAMIC_DRAW` when the new data does not fit in them.
+    pub fn update(&mut self, mesh_data: &MeshData) {
+        self.vertices.clone_from(&mesh_data.vertices);
+        self.indices.clone_from(&mesh_data.indices);
+        self.bounds = mesh_data.bounds();
+
+        unsafe {
+            gl::BindVertexArray(self.VAO.id());
+            update_buffer(gl::ARRAY_BUFFER, self.VBO.id(), &self.vertices, &mut self.vertex_buffer_size);
+            update_buffer(gl::ELEMENT_ARRAY_BUFFER, self.EBO.id(), &self.indices, &mut self.index_buffer_size);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    /// The bounds of the vertices, in the space of the mesh, to cull it when it is out of view.
+    pub fn bounds(&self) -> Option<&Aabb> {
+        self.bounds.as_ref()
+    }
+
     pub unsafe fn Draw(&self) {
         // draw mesh
-        gl::BindVertexArray(self.VAO);
+        gl::BindVertexArray(self.VAO.id());
         gl::DrawElements(gl::TRIANGLES, self.indices.len() as i32, gl::UNSIGNED_INT, ptr::null());
         gl::BindVertexArray(0);
 
@@ -56,25 +105,22 @@ impl Mesh {
     }
 
     unsafe fn setupMesh(&mut self) {
-        // create buffers/arrays
-        gl::GenVertexArrays(1, &mut self.VAO);
-        gl::GenBuffers(1, &mut self.VBO);
-        gl::GenBuffers(1, &mut self.EBO);
-
-        gl::BindVertexArray(self.VAO);
+        gl::BindVertexArray(self.VAO.id());
         // load data into vertex buffers
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO.id());
         // A great thing about structs with repr(C) is that their memory layout is sequential for all its items.
         // The effect is that we can simply pass a pointer to the struct and it translates perfectly to a glm::vec3/2 array which
         // again translates to 3/2 floats which translates to a byte array.
         let size = (self.vertices.len() * size_of::<Vertex>()) as isize;
-        let data = &self.vertices[0] as *const Vertex as *const c_void;
+        let data = self.vertices.as_ptr() as *const c_void;
         gl::BufferData(gl::ARRAY_BUFFER, size, data, gl::STATIC_DRAW);
+        self.vertex_buffer_size = size;
 
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.EBO);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.EBO.id());
         let size = (self.indices.len() * size_of::<i32>()) as isize;
-        let data = &self.indices[0] as *const i32 as *const c_void;
+        let data = self.indices.as_ptr() as *const c_void;
         gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, size, data, gl::STATIC_DRAW);
+        self.index_buffer_size = size;
 
         // set the vertex attribute pointers
         let size = size_of::<Vertex>() as i32;
@@ -87,7 +133,29 @@ impl Mesh {
         // vertex normals
         gl::EnableVertexAttribArray(2);
         gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, size, offset_of!(Vertex, normal) as *const c_void);
+        // vertex ambient occlusion, from 0.0 to 3.0
+        gl::EnableVertexAttribArray(3);
+        gl::VertexAttribPointer(3, 1, gl::UNSIGNED_BYTE, gl::FALSE, size, offset_of!(Vertex, ambient_occlusion) as *const c_void);
 
         gl::BindVertexArray(0);
     }
 }
+
+/// Write `data` at the start of the buffer, growing it first if it is too small. The buffer grows
+/// by at least half its size, so a chunk edited voxel by voxel is not reallocated every time.
+unsafe fn update_buffer<T>(target: u32, buffer: u32, data: &[T], buffer_size: &mut isize) {
+    let size = (data.len() * size_of::<T>()) as isize;
+
+    gl::BindBuffer(target, buffer);
+
+    if size > *buffer_size {
+        let new_size = cmp::max(size, *buffer_size + *buffer_size / 2);
+
+        gl::BufferData(target, new_size, ptr::null(), gl::DYNAMIC_DRAW);
+        *buffer_size = new_size;
+    }
+
+    if size > 0 {
+        gl::BufferSubData(target, 0, size, data.as_ptr() as *const c_void);
+    }
+}
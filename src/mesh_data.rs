@@ -1,3 +1,5 @@
+use bounds::Aabb;
+use cgmath::Point3;
 use vertex::Vertex;
 
 #[derive(Debug, Clone)]
@@ -11,6 +13,15 @@ impl MeshData {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// The bounds of the vertices, in the space of the mesh. `None` when there is no vertex.
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(
+            self.vertices
+                .iter()
+                .map(|vertex| Point3::new(vertex.position.x, vertex.position.y, vertex.position.z)),
+        )
+    }
 }
 
 impl Default for MeshData {
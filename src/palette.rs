@@ -1,6 +1,23 @@
 use cgmath::Vector4;
 
-pub struct Palette;
+/// The number of colors of a palette, as in MagicaVoxel files.
+pub const PALETTE_SIZE: usize = 256;
+
+#[derive(Fail, Debug)]
+#[fail(display = "A palette must have {} colors, got {}", expected, actual)]
+pub struct PaletteSizeError {
+    expected: usize,
+    actual: usize,
+}
+
+/// The 256 colors a voxel color index refers to. Colors are coded into an integer, with red in the
+/// lowest byte and alpha in the highest one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: Vec<u32>,
+    // The palette must be in a contiguous memory to be sent to the gpu, so we keep a flat copy.
+    colors_flat: Vec<f32>,
+}
 
 fn to_vector4_f32(rgba_color: u32) -> Vector4<f32> {
     let r = (rgba_color) & 0xFF;
@@ -17,26 +34,58 @@ fn to_vector4_f32(rgba_color: u32) -> Vector4<f32> {
 }
 
 impl Palette {
-    pub fn get() -> Vec<f32> {
+    pub fn new(colors: [u32; PALETTE_SIZE]) -> Self {
+        Self::from_colors(colors.to_vec())
+    }
+
+    pub fn from_slice(colors: &[u32]) -> Result<Self, PaletteSizeError> {
+        if colors.len() != PALETTE_SIZE {
+            return Err(PaletteSizeError {
+                expected: PALETTE_SIZE,
+                actual: colors.len(),
+            });
+        }
+
+        Ok(Self::from_colors(colors.to_vec()))
+    }
+
+    fn from_colors(colors: Vec<u32>) -> Self {
         // Convert color coded into an integer to 4 floats.
-        let palette_f32: Vec<Vector4<f32>> = DEFAULT_PALETTE
-            .iter()
-            .map(|color| to_vector4_f32(*color))
-            .collect();
-
-        // The palette must be in a contiguous memory to be sent to the gpu, so we flatten is.
-        let palette_flat: Vec<f32> =
-            palette_f32
-                .iter()
-                .fold(Vec::with_capacity(palette_f32.len() * 3), |mut acc, v| {
-                    acc.push(v.x);
-                    acc.push(v.y);
-                    acc.push(v.z);
-                    acc.push(v.w);
-                    acc
-                });
-
-        palette_flat
+        let colors_flat: Vec<f32> = colors.iter().fold(
+            Vec::with_capacity(colors.len() * 4),
+            |mut acc, color| {
+                let v = to_vector4_f32(*color);
+                acc.push(v.x);
+                acc.push(v.y);
+                acc.push(v.z);
+                acc.push(v.w);
+                acc
+            },
+        );
+
+        Self {
+            colors,
+            colors_flat,
+        }
+    }
+
+    pub fn colors(&self) -> &[u32] {
+        &self.colors
+    }
+
+    pub fn get_color(&self, index: u8) -> Vector4<f32> {
+        to_vector4_f32(self.colors[index as usize])
+    }
+
+    /// The colors as 4 floats each, ready to be sent to a `vec4` array uniform.
+    pub fn as_vector4_array(&self) -> &[f32] {
+        &self.colors_flat
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new(DEFAULT_PALETTE)
     }
 }
 
@@ -75,3 +124,21 @@ const DEFAULT_PALETTE: [u32; 256] = [
     4285988864, 4283760640, 4282646528, 4280418304, 4279304192, 4293848814, 4292730333, 4290493371,
     4289374890, 4287137928, 4286019447, 4283782485, 4282664004, 4280427042, 4279308561, 0,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::{Palette, PALETTE_SIZE};
+    use cgmath::Vector4;
+
+    #[test]
+    fn from_slice() {
+        let mut colors = vec![0; PALETTE_SIZE];
+        colors[1] = 0xFF00_00FF;
+
+        let palette = Palette::from_slice(&colors).unwrap();
+
+        assert_eq!(palette.get_color(1), Vector4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(&palette.as_vector4_array()[4..8], &[1.0, 0.0, 0.0, 1.0]);
+        assert!(Palette::from_slice(&colors[1..]).is_err(), "it should reject incomplete palettes");
+    }
+}
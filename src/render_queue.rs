@@ -0,0 +1,136 @@
+use std::cmp::Ordering;
+
+/// An item to draw, along with the render state it needs, such as a `MaterialKey`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawItem<S, T> {
+    pub state: S,
+    /// The distance from the camera along its forward direction.
+    pub depth: f32,
+    pub item: T,
+}
+
+/// The items to draw in a frame. Once sorted, the items needing the same render state follow each
+/// other, so the state is only set once per batch, and the items of a batch go from front to back
+/// so the depth test discards the hidden fragments early.
+#[derive(Debug)]
+pub struct RenderQueue<S, T> {
+    items: Vec<DrawItem<S, T>>,
+}
+
+impl<S: Ord, T> Default for RenderQueue<S, T> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<S: Ord, T> RenderQueue<S, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, state: S, depth: f32, item: T) {
+        self.items.push(DrawItem { state, depth, item });
+    }
+
+    pub fn sort(&mut self) {
+        self.items.sort_by(|item, other_item| {
+            item.state.cmp(&other_item.state).then_with(|| {
+                item.depth
+                    .partial_cmp(&other_item.depth)
+                    .unwrap_or(Ordering::Equal)
+            })
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The consecutive items needing the same render state, in the order they were pushed or
+    /// sorted.
+    pub fn batches(&self) -> Batches<'_, S, T> {
+        Batches { items: &self.items }
+    }
+}
+
+/// An iterator over the batches of a `RenderQueue`, see `RenderQueue::batches`.
+#[derive(Debug)]
+pub struct Batches<'a, S: 'a, T: 'a> {
+    items: &'a [DrawItem<S, T>],
+}
+
+impl<'a, S: Ord, T> Iterator for Batches<'a, S, T> {
+    type Item = &'a [DrawItem<S, T>];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first_item = self.items.first()?;
+        let batch_length = self
+            .items
+            .iter()
+            .position(|item| item.state != first_item.state)
+            .unwrap_or(self.items.len());
+        let (batch, items) = self.items.split_at(batch_length);
+
+        self.items = items;
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderQueue;
+
+    /// The names of the items of each batch.
+    fn batch_names(render_queue: &RenderQueue<(u32, u32), &'static str>) -> Vec<Vec<&'static str>> {
+        render_queue
+            .batches()
+            .map(|batch| batch.iter().map(|draw_item| draw_item.item).collect())
+            .collect()
+    }
+
+    #[test]
+    fn batch_items_sharing_a_state() {
+        let mut render_queue = RenderQueue::new();
+        // The states are (shader, palette).
+        render_queue.push((1, 0), 5.0, "far tree");
+        render_queue.push((0, 0), 1.0, "ground");
+        render_queue.push((1, 0), 2.0, "close tree");
+        render_queue.push((1, 1), 3.0, "red tree");
+        render_queue.push((0, 0), 9.0, "far ground");
+
+        render_queue.sort();
+
+        assert_eq!(
+            batch_names(&render_queue),
+            vec![
+                vec!["ground", "far ground"],
+                vec!["close tree", "far tree"],
+                vec!["red tree"],
+            ]
+        );
+        assert_eq!(render_queue.len(), 5);
+    }
+
+    #[test]
+    fn keep_the_order_of_unsorted_items() {
+        let mut render_queue = RenderQueue::new();
+        render_queue.push((0, 0), 1.0, "a");
+        render_queue.push((1, 0), 1.0, "b");
+        render_queue.push((0, 0), 1.0, "c");
+
+        assert_eq!(batch_names(&render_queue), vec![vec!["a"], vec!["b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn empty_queue_has_no_batch() {
+        let mut render_queue: RenderQueue<(u32, u32), &'static str> = RenderQueue::new();
+        render_queue.sort();
+
+        assert!(render_queue.is_empty());
+        assert_eq!(render_queue.batches().count(), 0);
+    }
+}
@@ -0,0 +1,17 @@
+use cgmath::Vector3;
+
+/// The light settings which are not attached to an entity, uploaded along with the `Light`
+/// components by the `LightGathering` system.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    pub ambient_color: Vector3<f32>,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            ambient_color: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod active_camera;
-pub mod rotating_entity;
\ No newline at end of file
+pub mod rotating_entity;
+pub mod lighting;
+pub mod shadow_casters;
+pub mod render_stats;
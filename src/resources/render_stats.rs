@@ -0,0 +1,9 @@
+/// The meshes of the last frame seen by the active camera, counted by the `Render` system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub drawn_meshes: usize,
+    /// The groups of drawn meshes sharing a material, each binding it once.
+    pub batches: usize,
+    /// The meshes out of the view of the camera, which were not drawn.
+    pub culled_meshes: usize,
+}
@@ -0,0 +1,6 @@
+use shadows::ShadowCaster;
+
+/// The lights casting shadows in the frame, found by the `LightGathering` system for the `Render`
+/// system.
+#[derive(Debug, Default)]
+pub struct ShadowCasters(pub Vec<ShadowCaster>);
@@ -0,0 +1,669 @@
+use asset_manager::AssetManager;
+use cgmath::{Point3, Vector3};
+use components::camera::Camera;
+use components::light::Light;
+use components::mesh_render::MeshRender;
+use components::mesh_source::MeshSource;
+use components::parent::Parent;
+use components::player::Player;
+use components::transform::Transform;
+use components::voxel_chunk::VoxelChunk;
+use components::world_chunk::WorldChunk;
+use failure::Error;
+use material::Material;
+use mesh::Mesh;
+use mesh_data::MeshData;
+use resources::active_camera::ActiveCamera;
+use resources::lighting::Lighting;
+use ron;
+use ron::ser::PrettyConfig;
+use specs::{Builder, Entity, Join, World};
+use std::collections::HashMap;
+use voxel::chunk::Chunk;
+use voxel::is_solid;
+use voxel::world::VoxelWorld;
+
+/// An entity of a scene file. Only the name and the position are mandatory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntity {
+    pub name: String,
+    pub position: Point3<f32>,
+    /// Euler angles in degrees, as taken by `Transform::set_rotation`.
+    #[serde(default = "no_rotation")]
+    pub rotation: Vector3<f32>,
+    #[serde(default = "unit_scale")]
+    pub scale: Vector3<f32>,
+    /// Index of the parent entity in `Scene::entities`.
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub mesh: Option<MeshSource>,
+    /// The first camera of the scene becomes the active camera. Without one, nothing is rendered.
+    #[serde(default)]
+    pub camera: bool,
+    #[serde(default)]
+    pub player: bool,
+    /// Lights along the forward direction of the entity for the directional and spot lights.
+    #[serde(default)]
+    pub light: Option<Light>,
+    /// The shadow flags of the mesh, see `MeshRender`.
+    #[serde(default = "enabled")]
+    pub casts_shadows: bool,
+    #[serde(default = "enabled")]
+    pub receives_shadows: bool,
+}
+
+/// A declarative description of the entities of a world, stored as RON in the scene files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    /// Parent entities appear before their children.
+    pub entities: Vec<SceneEntity>,
+    /// The ambient light, the other lights are entities.
+    #[serde(default)]
+    pub lighting: Lighting,
+}
+
+impl Scene {
+    pub fn from_ron(text: &str) -> Result<Self, Error> {
+        let scene: Self = ron::de::from_str(text).map_err(|error| format_err!("{}", error))?;
+        scene.validate()?;
+
+        Ok(scene)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Error> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .map_err(|error| format_err!("{}", error))
+    }
+
+    /// Return the index of the first entity named `name`, which is also the index of its entity in
+    /// the vector returned by `spawn`.
+    pub fn entity_index(&self, name: &str) -> Option<usize> {
+        self.entities.iter().position(|entity| entity.name == name)
+    }
+
+    /// Create the entities of the scene and add the `ActiveCamera` and `Lighting` resources. The
+    /// meshes are built with greedy meshing and rendered with `material`, using the palette of
+    /// their file for the `.vox` ones. Chunk entities get a `VoxelChunk`, so their voxels can be
+    /// edited at runtime. Return the entities in the order of `Scene::entities`.
+    ///
+    /// The components used by scenes and the `AssetManager` resource must be added to `world`
+    /// beforehand. A `.vox` file is only loaded once by the asset manager, even if several
+    /// entities use it.
+    pub fn spawn(&self, world: &mut World, material: &Material) -> Result<Vec<Entity>, Error> {
+        self.validate()?;
+
+        let mut entities: Vec<Entity> = Vec::with_capacity(self.entities.len());
+        let mut active_camera = None;
+
+        for scene_entity in &self.entities {
+            let (mesh_render, voxel_chunk) = {
+                let mut assets = world.write_resource::<AssetManager>();
+
+                match scene_entity.mesh {
+                    Some(MeshSource::Vox(ref asset_name)) => {
+                        let vox_model_handle = assets.load_vox(asset_name)?;
+                        let vox_model = assets
+                            .vox_model(vox_model_handle)
+                            .expect("the model should be loaded");
+
+                        let mesh_render = vox_model.mesh.map(|mesh| {
+                            MeshRender::new(material.with_palette(vox_model.palette), mesh)
+                        });
+
+                        (mesh_render, None)
+                    }
+                    Some(MeshSource::Chunk { size, ref voxels }) => {
+                        let mut voxel_chunk = VoxelChunk::new(chunk_from_voxels(size, voxels)?);
+                        let mesh_render =
+                            build_mesh_render(&mut assets, &mut voxel_chunk, material.clone());
+
+                        (Some(mesh_render), Some(voxel_chunk))
+                    }
+                    None => (None, None),
+                }
+            };
+
+            let rotation = scene_entity.rotation;
+            let mut transform = Transform::new(scene_entity.position, &scene_entity.name);
+            transform.set_rotation(rotation.x, rotation.y, rotation.z);
+            transform.set_local_scale(scene_entity.scale);
+
+            let mut entity_builder = world.create_entity().with(transform);
+
+            if let Some(parent) = scene_entity.parent {
+                entity_builder = entity_builder.with(Parent {
+                    entity: entities[parent],
+                });
+            }
+
+            if let Some(ref mesh_source) = scene_entity.mesh {
+                entity_builder = entity_builder.with(mesh_source.clone());
+            }
+
+            if let Some(mut mesh_render) = mesh_render {
+                mesh_render.casts_shadows = scene_entity.casts_shadows;
+                mesh_render.receives_shadows = scene_entity.receives_shadows;
+                entity_builder = entity_builder.with(mesh_render);
+            }
+
+            if let Some(voxel_chunk) = voxel_chunk {
+                entity_builder = entity_builder.with(voxel_chunk);
+            }
+
+            if scene_entity.camera {
+                entity_builder = entity_builder.with(Camera::new());
+            }
+
+            if scene_entity.player {
+                entity_builder = entity_builder.with(Player);
+            }
+
+            if let Some(ref light) = scene_entity.light {
+                entity_builder = entity_builder.with(light.clone());
+            }
+
+            let entity = entity_builder.build();
+
+            if scene_entity.camera && active_camera.is_none() {
+                active_camera = Some(entity);
+            }
+
+            entities.push(entity);
+        }
+
+        if let Some(camera) = active_camera {
+            world.add_resource(ActiveCamera(camera));
+        }
+
+        world.add_resource(self.lighting.clone());
+
+        Ok(entities)
+    }
+
+    /// Describe every entity of the world having a `Transform`. Meshes are only saved for the
+    /// entities having a `MeshSource`, which is the case of the ones created by `spawn`, a
+    /// `VoxelChunk` or a `WorldChunk`, whose current voxels are saved so the edits made at runtime
+    /// are kept. A chunk of the `VoxelWorld` is saved alone, it is loaded back as a `VoxelChunk`.
+    ///
+    /// As for `spawn`, the components used by scenes must be added to `world` beforehand.
+    pub fn from_world(world: &World) -> Self {
+        let entities = world.entities();
+        let transform_storage = world.read_storage::<Transform>();
+        let parent_storage = world.read_storage::<Parent>();
+        let mesh_source_storage = world.read_storage::<MeshSource>();
+        let voxel_chunk_storage = world.read_storage::<VoxelChunk>();
+        let world_chunk_storage = world.read_storage::<WorldChunk>();
+        let voxel_world = world.res.try_fetch::<VoxelWorld>();
+        let mesh_render_storage = world.read_storage::<MeshRender>();
+        let camera_storage = world.read_storage::<Camera>();
+        let player_storage = world.read_storage::<Player>();
+        let light_storage = world.read_storage::<Light>();
+
+        let mut roots: Vec<Entity> = Vec::new();
+        let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+
+        for (entity, _) in (&*entities, &transform_storage).join() {
+            match parent_storage.get(entity) {
+                Some(parent) if transform_storage.get(parent.entity).is_some() => children
+                    .entry(parent.entity)
+                    .or_insert_with(Vec::new)
+                    .push(entity),
+                _ => roots.push(entity),
+            }
+        }
+
+        // Depth first so parents are written before their children.
+        let mut ordered_entities: Vec<Entity> = Vec::new();
+        let mut stack: Vec<Entity> = roots.into_iter().rev().collect();
+
+        while let Some(entity) = stack.pop() {
+            ordered_entities.push(entity);
+
+            if let Some(entity_children) = children.get(&entity) {
+                stack.extend(entity_children.iter().rev());
+            }
+        }
+
+        let indices: HashMap<Entity, usize> = ordered_entities
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| (*entity, index))
+            .collect();
+        let mut mesh_without_source_count = 0;
+
+        let scene_entities = ordered_entities
+            .iter()
+            .map(|entity| {
+                let transform = transform_storage.get(*entity).unwrap();
+                let chunk = voxel_chunk_storage
+                    .get(*entity)
+                    .map(|voxel_chunk| &voxel_chunk.chunk)
+                    .or_else(|| {
+                        let world_chunk = world_chunk_storage.get(*entity)?;
+
+                        voxel_world.as_ref()?.get_chunk(&world_chunk.position)
+                    });
+                let mesh = match chunk {
+                    Some(chunk) => Some(mesh_source_from_chunk(chunk)),
+                    None => mesh_source_storage.get(*entity).cloned(),
+                };
+                let mesh_render = mesh_render_storage.get(*entity);
+
+                if mesh.is_none() && mesh_render.is_some() {
+                    mesh_without_source_count += 1;
+                }
+
+                SceneEntity {
+                    name: transform.name.clone(),
+                    position: transform.local_position,
+                    rotation: transform.to_euler_angles(),
+                    scale: transform.local_scale,
+                    parent: parent_storage
+                        .get(*entity)
+                        .and_then(|parent| indices.get(&parent.entity).cloned()),
+                    mesh,
+                    camera: camera_storage.get(*entity).is_some(),
+                    player: player_storage.get(*entity).is_some(),
+                    light: light_storage.get(*entity).cloned(),
+                    casts_shadows: mesh_render
+                        .map_or(true, |mesh_render| mesh_render.casts_shadows),
+                    receives_shadows: mesh_render
+                        .map_or(true, |mesh_render| mesh_render.receives_shadows),
+                }
+            })
+            .collect();
+
+        if mesh_without_source_count > 0 {
+            warn!(
+                "{} entities have a mesh without `MeshSource`, their mesh is not part of the scene.",
+                mesh_without_source_count
+            );
+        }
+
+        let lighting = world
+            .res
+            .try_fetch::<Lighting>()
+            .map(|lighting| lighting.clone())
+            .unwrap_or_default();
+
+        Scene {
+            entities: scene_entities,
+            lighting,
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        for (index, entity) in self.entities.iter().enumerate() {
+            if let Some(parent) = entity.parent {
+                if parent >= index {
+                    return Err(format_err!(
+                        "the parent of the entity '{}' is at the index {}, it must appear before its child at the index {}",
+                        entity.name,
+                        parent,
+                        index,
+                    ));
+                }
+            }
+
+            if let Some(MeshSource::Chunk { size, ref voxels }) = entity.mesh {
+                chunk_from_voxels(size, voxels).map_err(|error| {
+                    format_err!(
+                        "the chunk of the entity '{}' is invalid: {}",
+                        entity.name,
+                        error
+                    )
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn no_rotation() -> Vector3<f32> {
+    Vector3::new(0.0, 0.0, 0.0)
+}
+
+fn unit_scale() -> Vector3<f32> {
+    Vector3::new(1.0, 1.0, 1.0)
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn chunk_from_voxels(size: (u8, u8, u8), voxels: &[(u8, u8, u8, u8)]) -> Result<Chunk, Error> {
+    let mut chunk = Chunk::new(size.0, size.1, size.2);
+
+    for &(x, y, z, value) in voxels {
+        chunk
+            .set_voxel(i64::from(x), i64::from(y), i64::from(z), value)
+            .map_err(|_| {
+                format_err!(
+                    "the voxel ({}, {}, {}) is out of the chunk of size {:?}",
+                    x,
+                    y,
+                    z,
+                    size
+                )
+            })?;
+    }
+
+    Ok(chunk)
+}
+
+fn mesh_source_from_chunk(chunk: &Chunk) -> MeshSource {
+    let mut voxels = Vec::new();
+
+    for x in 0..chunk.size_x {
+        for y in 0..chunk.size_y {
+            for z in 0..chunk.size_z {
+                let value = chunk
+                    .get_voxel(i64::from(x), i64::from(y), i64::from(z))
+                    .expect("the voxel should be in the chunk");
+
+                if is_solid(value) {
+                    voxels.push((x, y, z, value));
+                }
+            }
+        }
+    }
+
+    MeshSource::Chunk {
+        size: (chunk.size_x, chunk.size_y, chunk.size_z),
+        voxels,
+    }
+}
+
+/// The mesh of a chunk is created even when it has no voxel, so `ChunkMeshing` can fill it once
+/// voxels are added.
+fn build_mesh_render(
+    assets: &mut AssetManager,
+    voxel_chunk: &mut VoxelChunk,
+    material: Material,
+) -> MeshRender {
+    let meshing_mode = voxel_chunk.meshing_mode();
+    let mesh_data = voxel_chunk
+        .chunk
+        .update_mesh_data(meshing_mode)
+        .cloned()
+        .unwrap_or_else(MeshData::new);
+
+    MeshRender::new(material, assets.add_mesh(Mesh::new(mesh_data, Vec::default())))
+}
+
+/// Create a world with the components used by scenes, for the tests which spawn or save scenes.
+#[cfg(test)]
+pub fn scene_world() -> World {
+    let mut world = World::new();
+    world.register::<Transform>();
+    world.register::<MeshRender>();
+    world.register::<MeshSource>();
+    world.register::<VoxelChunk>();
+    world.register::<WorldChunk>();
+    world.register::<Camera>();
+    world.register::<Player>();
+    world.register::<Parent>();
+    world.register::<Light>();
+
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_from_voxels, scene_world, Scene};
+    use asset_manager::AssetManager;
+    use cgmath::{Point3, Vector3};
+    use components::camera::Camera;
+    use components::light::{Light, LightKind};
+    use components::mesh_source::MeshSource;
+    use components::parent::Parent;
+    use components::transform::Transform;
+    use components::voxel_chunk::VoxelChunk;
+    use components::world_chunk::WorldChunk;
+    use headless::HeadlessContext;
+    use material::Material;
+    use resources::lighting::Lighting;
+    use specs::Builder;
+    use voxel::chunk::Chunk;
+    use voxel::position::Position;
+    use voxel::world::{VoxelWorld, CHUNK_SIZE};
+
+    #[test]
+    fn parse_scene() {
+        let scene = Scene::from_ron(
+            r#"(
+                entities: [
+                    (name: "Camera", position: (x: 0.0, y: 1.0, z: 0.0), camera: true, player: true),
+                    (
+                        name: "Chunk",
+                        position: (x: 0.0, y: 0.0, z: -1.0),
+                        rotation: (x: 0.0, y: 45.0, z: 0.0),
+                        parent: Some(0),
+                        mesh: Some(Chunk(size: (2, 1, 1), voxels: [(1, 0, 0, 2)])),
+                        receives_shadows: false,
+                    ),
+                    (
+                        name: "Lamp",
+                        position: (x: 0.0, y: 2.0, z: 0.0),
+                        light: Some((kind: Point(range: 10.0), color: (x: 1.0, y: 0.5, z: 0.0), intensity: 2.0)),
+                    ),
+                ],
+            )"#,
+        ).unwrap();
+
+        assert_eq!(scene.entities.len(), 3);
+        assert_eq!(scene.entities[0].rotation, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(scene.entities[0].scale, Vector3::new(1.0, 1.0, 1.0));
+        assert!(scene.entities[0].camera && scene.entities[0].player);
+        assert_eq!(scene.entities[1].parent, Some(0));
+        assert_eq!(
+            scene.entities[1].mesh,
+            Some(MeshSource::Chunk {
+                size: (2, 1, 1),
+                voxels: vec![(1, 0, 0, 2)],
+            })
+        );
+        assert!(scene.entities[1].casts_shadows && !scene.entities[1].receives_shadows);
+        assert!(scene.entities[0].casts_shadows && scene.entities[0].receives_shadows);
+        assert_eq!(scene.entities[1].light, None);
+        assert_eq!(
+            scene.entities[2].light,
+            Some(Light {
+                kind: LightKind::Point { range: 10.0 },
+                color: Vector3::new(1.0, 0.5, 0.0),
+                intensity: 2.0,
+                casts_shadows: false,
+            })
+        );
+        assert_eq!(scene.lighting, Lighting::default(), "it should use the default lighting");
+        assert_eq!(scene.entity_index("Chunk"), Some(1));
+        assert_eq!(Scene::from_ron(&scene.to_ron().unwrap()).unwrap(), scene);
+    }
+
+    #[test]
+    fn reject_invalid_scenes() {
+        let parent_after_child = r#"(entities: [
+            (name: "Child", position: (x: 0.0, y: 0.0, z: 0.0), parent: Some(1)),
+            (name: "Parent", position: (x: 0.0, y: 0.0, z: 0.0)),
+        ])"#;
+        let voxel_out_of_chunk = r#"(entities: [
+            (
+                name: "Chunk",
+                position: (x: 0.0, y: 0.0, z: 0.0),
+                mesh: Some(Chunk(size: (1, 1, 1), voxels: [(1, 0, 0, 2)])),
+            ),
+        ])"#;
+
+        assert!(Scene::from_ron(parent_after_child).is_err());
+        assert!(Scene::from_ron(voxel_out_of_chunk).is_err());
+        assert!(Scene::from_ron("(entities: [(name: \"Nameless\")])").is_err());
+    }
+
+    #[test]
+    fn save_world() {
+        let mut world = scene_world();
+
+        // Without `Transform`, like the scene root.
+        world.create_entity().build();
+        // Created before its parent.
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, -2.0), "Child"))
+            .build();
+        let mut parent_transform = Transform::new(Point3::new(1.0, 2.0, 3.0), "Parent");
+        parent_transform.set_rotation(0.0, 45.0, 0.0);
+        parent_transform.set_local_scale(Vector3::new(1.0, 2.0, 0.5));
+        let parent = world
+            .create_entity()
+            .with(parent_transform)
+            .with(MeshSource::Vox("chr_old.vox".to_string()))
+            .with(Camera::new())
+            .build();
+        world
+            .write_storage::<Parent>()
+            .insert(child, Parent { entity: parent })
+            .unwrap();
+        world
+            .write_storage::<Light>()
+            .insert(child, Light::spot(Vector3::new(1.0, 1.0, 1.0), 5.0, 20.0, 30.0))
+            .unwrap();
+        world.add_resource(Lighting {
+            ambient_color: Vector3::new(0.5, 0.5, 1.0),
+        });
+
+        let scene = Scene::from_world(&world);
+
+        assert_eq!(
+            scene.entities.len(),
+            2,
+            "it should only save entities with a transform"
+        );
+        assert_eq!(scene.entities[0].name, "Parent");
+        assert_eq!(scene.entities[0].position, Point3::new(1.0, 2.0, 3.0));
+        assert!((scene.entities[0].rotation.y - 45.0).abs() < 0.001);
+        assert_eq!(scene.entities[0].scale, Vector3::new(1.0, 2.0, 0.5));
+        assert_eq!(
+            scene.entities[0].mesh,
+            Some(MeshSource::Vox("chr_old.vox".to_string()))
+        );
+        assert!(scene.entities[0].camera && !scene.entities[0].player);
+        assert_eq!(scene.entities[1].name, "Child");
+        assert_eq!(scene.entities[1].parent, Some(0), "it should write parents before their children");
+        assert_eq!(scene.entities[0].light, None);
+        assert_eq!(
+            scene.entities[1].light,
+            Some(Light::spot(Vector3::new(1.0, 1.0, 1.0), 5.0, 20.0, 30.0))
+        );
+        assert_eq!(scene.lighting.ambient_color, Vector3::new(0.5, 0.5, 1.0));
+        assert_eq!(Scene::from_ron(&scene.to_ron().unwrap()).unwrap(), scene);
+    }
+
+    #[test]
+    fn save_edited_chunks() {
+        let mut world = scene_world();
+
+        let spawned_source = MeshSource::Chunk {
+            size: (2, 2, 1),
+            voxels: vec![(0, 0, 0, 1)],
+        };
+        let mut chunk = Chunk::new(2, 2, 1);
+        chunk.set_voxel(0, 0, 0, 1).unwrap();
+        let entity = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Chunk"))
+            .with(spawned_source.clone())
+            .with(VoxelChunk::new(chunk))
+            .build();
+
+        {
+            let mut voxel_chunk_storage = world.write_storage::<VoxelChunk>();
+            let voxel_chunk = voxel_chunk_storage.get_mut(entity).unwrap();
+            voxel_chunk.chunk.set_voxel(0, 0, 0, 0).unwrap();
+            voxel_chunk.chunk.set_voxel(1, 1, 0, 7).unwrap();
+        }
+
+        let scene = Scene::from_ron(&Scene::from_world(&world).to_ron().unwrap()).unwrap();
+
+        assert_ne!(scene.entities[0].mesh, Some(spawned_source));
+
+        match scene.entities[0].mesh {
+            Some(MeshSource::Chunk { size, ref voxels }) => {
+                let chunk = chunk_from_voxels(size, voxels).unwrap();
+
+                assert_eq!(size, (2, 2, 1));
+                assert_eq!(chunk.get_voxel(0, 0, 0).unwrap(), 0);
+                assert_eq!(chunk.get_voxel(1, 1, 0).unwrap(), 7);
+            }
+            ref mesh => panic!("the chunk should be saved, not {:?}", mesh),
+        }
+    }
+
+    #[test]
+    fn save_world_chunks() {
+        let mut world = scene_world();
+
+        let mut voxel_world = VoxelWorld::new();
+        voxel_world.set_voxel(&Position::new(1, 2, 3), 5);
+        world.add_resource(voxel_world);
+
+        world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "WorldChunk"))
+            .with(WorldChunk::new(Position::new(0, 0, 0)))
+            .build();
+
+        let scene = Scene::from_world(&world);
+
+        assert_eq!(
+            scene.entities[0].mesh,
+            Some(MeshSource::Chunk {
+                size: (CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE),
+                voxels: vec![(1, 2, 3, 5)],
+            })
+        );
+    }
+
+    /// Needs an OpenGL driver, see `HeadlessContext`.
+    #[test]
+    #[ignore]
+    fn spawn_saved_chunks() {
+        let _context = HeadlessContext::new(32, 32).unwrap();
+        let mut world = scene_world();
+
+        let mut assets = AssetManager::new();
+        let material = Material::new(&mut assets).unwrap();
+        world.add_resource(assets);
+
+        let scene = Scene::from_ron(
+            r#"(entities: [
+                (
+                    name: "Chunk",
+                    position: (x: 0.0, y: 0.0, z: 0.0),
+                    mesh: Some(Chunk(size: (2, 2, 2), voxels: [(0, 0, 0, 1)])),
+                ),
+            ])"#,
+        ).unwrap();
+        let entities = scene.spawn(&mut world, &material).unwrap();
+
+        world
+            .write_storage::<VoxelChunk>()
+            .get_mut(entities[0])
+            .unwrap()
+            .chunk
+            .set_voxel(1, 1, 1, 3)
+            .unwrap();
+
+        let saved_scene = Scene::from_world(&world);
+        let mut new_world = scene_world();
+        new_world.add_resource(AssetManager::new());
+
+        let new_entities = saved_scene.spawn(&mut new_world, &material).unwrap();
+        let voxel_chunk_storage = new_world.read_storage::<VoxelChunk>();
+        let chunk = &voxel_chunk_storage.get(new_entities[0]).unwrap().chunk;
+
+        assert_eq!(chunk.get_voxel(0, 0, 0).unwrap(), 1);
+        assert_eq!(chunk.get_voxel(1, 1, 1).unwrap(), 3);
+    }
+}
@@ -0,0 +1,34 @@
+use failure::Error;
+use scene::Scene;
+use std::fs::File;
+use std::io::Read;
+
+pub const SCENES_DIRECTORY: &str = "./resources/scenes";
+
+#[derive(Default)]
+pub struct SceneLoader {}
+
+impl SceneLoader {
+    /// Load a scene file from the scenes directory.
+    pub fn load(asset_name: &str) -> Result<Scene, Error> {
+        Self::load_file(&format!("{}/{}", SCENES_DIRECTORY, asset_name))
+    }
+
+    /// Same as `load` but with a path which is not relative to the scenes directory.
+    pub fn load_file(filepath: &str) -> Result<Scene, Error> {
+        let mut text = String::new();
+
+        File::open(filepath)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|error| {
+                format_err!(
+                    "Tryed to open the scene file at '{}' and got the following error: {}.",
+                    filepath,
+                    error,
+                )
+            })?;
+
+        Scene::from_ron(&text)
+            .map_err(|error| format_err!("Cannot load the scene file at '{}': {}", filepath, error))
+    }
+}
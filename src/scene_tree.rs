@@ -6,11 +6,27 @@ use self::vec_tree::{
 use specs::Entity;
 use std::collections::HashMap;
 
+#[derive(Fail, Debug)]
+pub enum SceneTreeError {
+    #[fail(display = "The entity {:?} is not in the scene tree, it may have no transform", entity)]
+    MissingEntity { entity: Entity },
+    #[fail(
+        display = "The entity {:?} cannot be the parent of {:?} since it is one of its descendants",
+        parent,
+        entity
+    )]
+    Cycle { entity: Entity, parent: Entity },
+}
+
+/// The hierarchy of the entities having a transform. The `Transformation` system keeps it in sync
+/// with the `Transform` and `Parent` components and shares it as a resource.
 pub struct SceneTree {
     pub tree: VecTree<Entity>,
     root_node: Index,
     scene_root_entity: Entity,
     entity_node_map: HashMap<Entity, Index>,
+    /// Deleted entities are only known by their id in the component events.
+    entities_by_id: HashMap<u32, Entity>,
 }
 
 impl SceneTree {
@@ -24,12 +40,14 @@ impl SceneTree {
             root_node,
             scene_root_entity,
             entity_node_map: HashMap::new(),
+            entities_by_id: HashMap::new(),
         }
     }
 
     pub fn reset(&mut self) {
         self.tree.clear();
         self.entity_node_map.clear();
+        self.entities_by_id.clear();
 
         self.root_node = self.tree.insert_root(self.scene_root_entity);
     }
@@ -38,17 +56,94 @@ impl SceneTree {
         let scene_node = self.tree.insert(entity, self.root_node);
 
         self.entity_node_map.insert(entity, scene_node);
+        self.entities_by_id.insert(entity.id(), entity);
+    }
+
+    /// Add the entity at the root of the scene, unless it is already in the tree.
+    pub fn insert_entity(&mut self, entity: Entity) {
+        if !self.contains_entity(&entity) {
+            self.add_entity_node(entity);
+        }
+    }
+
+    /// Remove the entity and its descendants from the tree. Return the descendants, so they can be
+    /// deleted as well.
+    pub fn remove_entity(&mut self, entity: &Entity) -> Vec<Entity> {
+        if !self.contains_entity(entity) {
+            return Vec::new();
+        }
+
+        let mut removed_entities: Vec<Entity> = self.descendant_entities(entity).collect();
+
+        // Leaves first, so nodes have no children left when they are removed.
+        for removed_entity in removed_entities.iter().rev() {
+            if let Some(node) = self.entity_node_map.remove(removed_entity) {
+                self.tree.remove(node);
+            }
+
+            if self.entities_by_id.get(&removed_entity.id()) == Some(removed_entity) {
+                self.entities_by_id.remove(&removed_entity.id());
+            }
+        }
+
+        removed_entities.remove(0);
+        removed_entities
+    }
+
+    /// Return the entity of the tree having this id, even if it has been deleted since.
+    pub fn entity_with_id(&self, id: u32) -> Option<Entity> {
+        self.entities_by_id.get(&id).cloned()
     }
 
-    pub fn get_entity_node(&self, entity: &Entity) -> Index {
-        self.entity_node_map[entity]
+    /// Move the entity, along with its descendants, under `parent` or at the root of the scene.
+    /// The tree is left untouched when the parent is the entity itself or one of its
+    /// descendants.
+    pub fn set_parent(&mut self, entity: &Entity, parent: Option<&Entity>) -> Result<(), SceneTreeError> {
+        let node = *self
+            .entity_node_map
+            .get(entity)
+            .ok_or(SceneTreeError::MissingEntity { entity: *entity })?;
+
+        let parent_node = match parent {
+            Some(parent) => {
+                let parent_node = *self
+                    .entity_node_map
+                    .get(parent)
+                    .ok_or(SceneTreeError::MissingEntity { entity: *parent })?;
+
+                if self.is_ancestor_of(entity, parent) {
+                    return Err(SceneTreeError::Cycle {
+                        entity: *entity,
+                        parent: *parent,
+                    });
+                }
+
+                parent_node
+            }
+            None => self.root_node,
+        };
+
+        self.set_node_child(parent_node, node);
+        Ok(())
     }
 
-    pub fn set_entity_child(&mut self, entity: &Entity, child: &Entity) {
-        let node = self.get_entity_node(entity);
-        let child_node = self.get_entity_node(child);
+    /// Return `None` for the entities which are not in the tree yet, such as the ones created
+    /// during this frame. The queries of the tree see them as having no parent, children,
+    /// ancestors or descendants.
+    pub fn get_entity_node(&self, entity: &Entity) -> Option<Index> {
+        self.entity_node_map.get(entity).cloned()
+    }
+
+    pub fn set_entity_child(&mut self, entity: &Entity, child: &Entity) -> Result<(), SceneTreeError> {
+        let node = self
+            .get_entity_node(entity)
+            .ok_or(SceneTreeError::MissingEntity { entity: *entity })?;
+        let child_node = self
+            .get_entity_node(child)
+            .ok_or(SceneTreeError::MissingEntity { entity: *child })?;
 
         self.set_node_child(node, child_node);
+        Ok(())
     }
 
     fn set_node_child(&mut self, node: Index, child: Index) {
@@ -56,7 +151,7 @@ impl SceneTree {
     }
 
     pub fn get_parent_entity(&self, entity: &Entity) -> Option<&Entity> {
-        let node = self.get_entity_node(entity);
+        let node = self.get_entity_node(entity)?;
 
         match self.tree.parent(node) {
             Some(node_id) => self.tree.get(node_id),
@@ -64,6 +159,20 @@ impl SceneTree {
         }
     }
 
+    pub fn contains_entity(&self, entity: &Entity) -> bool {
+        self.entity_node_map.contains_key(entity)
+    }
+
+    pub fn has_children(&self, entity: &Entity) -> bool {
+        self.get_entity_node(entity)
+            .map_or(false, |node| self.tree.children(node).next().is_some())
+    }
+
+    /// Return true if `ancestor` is `entity` or one of its ancestors.
+    pub fn is_ancestor_of(&self, ancestor: &Entity, entity: &Entity) -> bool {
+        self.ancestor_entities(entity).any(|ancestor_entity| ancestor_entity == *ancestor)
+    }
+
     // pub fn get_entity_root_parent(&self, entity: &Entity) -> &Entity {
     //     let mut node_id: Index = self.get_entity_node(entity);
 
@@ -77,43 +186,43 @@ impl SceneTree {
     // }
 
     /// Parent nodes appear before the descendants.
-    /// Call `.next().unwrap()` once on the iterator to skip the node itself.
+    /// Call `.next()` once on the iterator to skip the node itself.
     pub fn descendant_entities(&self, entity: &Entity) -> DescendantEntities {
-        let node_id = self.get_entity_node(entity);
-
         DescendantEntities {
-            descendants: self.tree.descendants(node_id),
+            descendants: self
+                .get_entity_node(entity)
+                .map(|node_id| self.tree.descendants(node_id)),
             tree: &self.tree,
         }
     }
 
     /// Parent nodes appear before the descendants.
-    /// Call `.next().unwrap()` once on the iterator to skip the node itself.
+    /// Call `.next()` once on the iterator to skip the node itself.
     pub fn descendant_entities_with_depth(&self, entity: &Entity) -> DescendantEntitiesWithDepth {
-        let node_id = self.get_entity_node(entity);
-
         DescendantEntitiesWithDepth {
-            descendants_with_depth: self.tree.descendants_with_depth(node_id),
+            descendants_with_depth: self
+                .get_entity_node(entity)
+                .map(|node_id| self.tree.descendants_with_depth(node_id)),
             tree: &self.tree,
         }
     }
 
-    /// Call `.next().unwrap()` once on the iterator to skip the node itself.
+    /// Call `.next()` once on the iterator to skip the node itself.
     pub fn following_entities(&self, entity: &Entity) -> FollowingEntities {
-        let node_id = self.get_entity_node(entity);
-
         FollowingEntities {
-            following_siblings: self.tree.following_siblings(node_id),
+            following_siblings: self
+                .get_entity_node(entity)
+                .map(|node_id| self.tree.following_siblings(node_id)),
             tree: &self.tree,
         }
     }
 
-    /// Call `.next().unwrap()` once on the iterator to skip the node itself.
+    /// Call `.next()` once on the iterator to skip the node itself.
     pub fn ancestor_entities(&self, entity: &Entity) -> AncestorEntities {
-        let node_id = self.get_entity_node(entity);
-
         AncestorEntities {
-            ancestors: self.tree.ancestors(node_id),
+            ancestors: self
+                .get_entity_node(entity)
+                .map(|node_id| self.tree.ancestors(node_id)),
             tree: &self.tree,
         }
     }
@@ -121,21 +230,16 @@ impl SceneTree {
     /// This is the root nodes on the scene tree perspective. On the vec tree perspective, they are
     /// the children of the root node.
     pub fn root_entities(&self) -> Option<ChildrenEntities> {
-        let root_node_id_opt = self.tree.get_root_index();
-
-        match root_node_id_opt {
-            Some(root_node_id) => Some(ChildrenEntities {
-                children: self.tree.children(root_node_id),
-                tree: &self.tree,
-            }),
-            _ => None,
-        }
+        self.tree.get_root_index().map(|root_node_id| ChildrenEntities {
+            children: self.tree.children(root_node_id),
+            tree: &self.tree,
+        })
     }
 }
 
 // TODO: DescendantEntitiesIter.
 pub struct DescendantEntities<'a> {
-    descendants: DescendantsIter<'a, Entity>,
+    descendants: Option<DescendantsIter<'a, Entity>>,
     tree: &'a VecTree<Entity>,
 }
 
@@ -143,7 +247,7 @@ impl<'a> Iterator for DescendantEntities<'a> {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Entity> {
-        let descendants = &mut self.descendants;
+        let descendants = self.descendants.as_mut()?;
 
         match descendants.next() {
             Some(node_id) => Some(self.tree[node_id]),
@@ -153,7 +257,7 @@ impl<'a> Iterator for DescendantEntities<'a> {
 }
 
 pub struct DescendantEntitiesWithDepth<'a> {
-    descendants_with_depth: DescendantsWithDepthIter<'a, Entity>,
+    descendants_with_depth: Option<DescendantsWithDepthIter<'a, Entity>>,
     tree: &'a VecTree<Entity>,
 }
 
@@ -161,7 +265,7 @@ impl<'a> Iterator for DescendantEntitiesWithDepth<'a> {
     type Item = (Entity, u32);
 
     fn next(&mut self) -> Option<(Entity, u32)> {
-        let descendants_with_depth = &mut self.descendants_with_depth;
+        let descendants_with_depth = self.descendants_with_depth.as_mut()?;
 
         match descendants_with_depth.next() {
             Some((node_id, depth)) => Some((self.tree[node_id], depth)),
@@ -171,7 +275,7 @@ impl<'a> Iterator for DescendantEntitiesWithDepth<'a> {
 }
 
 pub struct FollowingEntities<'a> {
-    following_siblings: FollowingSiblingsIter<'a, Entity>,
+    following_siblings: Option<FollowingSiblingsIter<'a, Entity>>,
     tree: &'a VecTree<Entity>,
 }
 
@@ -179,7 +283,7 @@ impl<'a> Iterator for FollowingEntities<'a> {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Entity> {
-        let following_siblings = &mut self.following_siblings;
+        let following_siblings = self.following_siblings.as_mut()?;
 
         match following_siblings.next() {
             Some(node_id) => Some(self.tree[node_id]),
@@ -189,7 +293,7 @@ impl<'a> Iterator for FollowingEntities<'a> {
 }
 
 pub struct AncestorEntities<'a> {
-    ancestors: AncestorsIter<'a, Entity>,
+    ancestors: Option<AncestorsIter<'a, Entity>>,
     tree: &'a VecTree<Entity>,
 }
 
@@ -197,7 +301,7 @@ impl<'a> Iterator for AncestorEntities<'a> {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Entity> {
-        let ancestors = &mut self.ancestors;
+        let ancestors = self.ancestors.as_mut()?;
 
         match ancestors.next() {
             Some(node_id) => Some(self.tree[node_id]),
@@ -223,3 +327,101 @@ impl<'a> Iterator for ChildrenEntities<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SceneTree, SceneTreeError};
+    use specs::{Builder, Entity, World};
+
+    fn create_entities(count: usize) -> (Entity, Vec<Entity>) {
+        let mut world = World::new();
+        let scene_root_entity = world.create_entity().build();
+        let entities = (0..count).map(|_| world.create_entity().build()).collect();
+
+        (scene_root_entity, entities)
+    }
+
+    #[test]
+    fn reparent_entities() {
+        let (scene_root_entity, entities) = create_entities(3);
+        let mut scene_tree = SceneTree::new(scene_root_entity);
+
+        for entity in &entities {
+            scene_tree.insert_entity(*entity);
+        }
+
+        scene_tree.set_parent(&entities[1], Some(&entities[0])).unwrap();
+        scene_tree.set_parent(&entities[2], Some(&entities[1])).unwrap();
+
+        assert_eq!(scene_tree.get_parent_entity(&entities[2]), Some(&entities[1]));
+        assert_eq!(scene_tree.root_entities().unwrap().collect::<Vec<Entity>>(), vec![entities[0]]);
+
+        scene_tree.set_parent(&entities[1], None).unwrap();
+
+        assert_eq!(scene_tree.get_parent_entity(&entities[1]), Some(&scene_root_entity));
+        assert_eq!(scene_tree.get_parent_entity(&entities[2]), Some(&entities[1]));
+        assert!(!scene_tree.has_children(&entities[0]));
+    }
+
+    #[test]
+    fn reject_cycles() {
+        let (scene_root_entity, entities) = create_entities(3);
+        let mut scene_tree = SceneTree::new(scene_root_entity);
+
+        for entity in &entities {
+            scene_tree.insert_entity(*entity);
+        }
+
+        scene_tree.set_parent(&entities[1], Some(&entities[0])).unwrap();
+        scene_tree.set_parent(&entities[2], Some(&entities[1])).unwrap();
+
+        match scene_tree.set_parent(&entities[0], Some(&entities[2])) {
+            Err(SceneTreeError::Cycle { entity, parent }) => {
+                assert_eq!((entity, parent), (entities[0], entities[2]));
+            }
+            _ => panic!("it should reject the cycle"),
+        }
+
+        assert!(scene_tree.set_parent(&entities[0], Some(&entities[0])).is_err());
+        assert_eq!(scene_tree.get_parent_entity(&entities[0]), Some(&scene_root_entity));
+    }
+
+    #[test]
+    fn query_entities_not_in_the_tree() {
+        let (scene_root_entity, entities) = create_entities(2);
+        let mut scene_tree = SceneTree::new(scene_root_entity);
+        scene_tree.insert_entity(entities[0]);
+
+        let unknown_entity = entities[1];
+
+        assert_eq!(scene_tree.get_parent_entity(&unknown_entity), None);
+        assert!(!scene_tree.has_children(&unknown_entity));
+        assert!(!scene_tree.is_ancestor_of(&entities[0], &unknown_entity));
+        assert_eq!(scene_tree.descendant_entities(&unknown_entity).count(), 0);
+        assert_eq!(scene_tree.descendant_entities_with_depth(&unknown_entity).count(), 0);
+        assert_eq!(scene_tree.following_entities(&unknown_entity).count(), 0);
+        assert_eq!(scene_tree.ancestor_entities(&unknown_entity).count(), 0);
+        assert!(scene_tree.set_entity_child(&entities[0], &unknown_entity).is_err());
+    }
+
+    #[test]
+    fn remove_entities_with_their_descendants() {
+        let (scene_root_entity, entities) = create_entities(4);
+        let mut scene_tree = SceneTree::new(scene_root_entity);
+
+        for entity in &entities {
+            scene_tree.insert_entity(*entity);
+        }
+
+        scene_tree.set_parent(&entities[1], Some(&entities[0])).unwrap();
+        scene_tree.set_parent(&entities[2], Some(&entities[1])).unwrap();
+
+        let removed_entities = scene_tree.remove_entity(&entities[0]);
+
+        assert_eq!(removed_entities, vec![entities[1], entities[2]]);
+        assert!(!scene_tree.contains_entity(&entities[0]));
+        assert!(!scene_tree.contains_entity(&entities[2]));
+        assert_eq!(scene_tree.entity_with_id(entities[0].id()), None);
+        assert_eq!(scene_tree.root_entities().unwrap().collect::<Vec<Entity>>(), vec![entities[3]]);
+    }
+}
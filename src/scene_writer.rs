@@ -0,0 +1,36 @@
+use failure::Error;
+use scene::Scene;
+use scene_loader::SCENES_DIRECTORY;
+use specs::World;
+use std::fs::File;
+use std::io::Write;
+
+#[derive(Default)]
+pub struct SceneWriter {}
+
+impl SceneWriter {
+    /// Save the current state of the world as a scene file in the scenes directory, see
+    /// `Scene::from_world`.
+    pub fn save(asset_name: &str, world: &World) -> Result<(), Error> {
+        Self::save_file(&format!("{}/{}", SCENES_DIRECTORY, asset_name), world)
+    }
+
+    /// Same as `save` but with a path which is not relative to the scenes directory.
+    pub fn save_file(filepath: &str, world: &World) -> Result<(), Error> {
+        let text = Scene::from_world(world)
+            .to_ron()
+            .map_err(|error| format_err!("Cannot save the scene file at '{}': {}", filepath, error))?;
+
+        File::create(filepath)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|error| {
+                format_err!(
+                    "Tryed to write the scene file at '{}' and got the following error: {}.",
+                    filepath,
+                    error,
+                )
+            })?;
+
+        Ok(())
+    }
+}
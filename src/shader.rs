@@ -1,204 +1,549 @@
 #![allow(non_snake_case)]
 use std::ffi::{CString, CStr};
-use std::fs::File;
-use std::io::Read;
+use std::fmt;
 use std::ptr;
-use std::str;
 
 use gl;
 use gl::types::*;
 
+use gl_object::{GlHandle, GlObjectKind};
+use shader_preprocessor::{preprocess, PreprocessError, PreprocessedSource};
+
 use cgmath::{Matrix, Matrix3, Matrix4, Vector3, Vector4};
 use cgmath::prelude::*;
 
+/// The binding point of the `Lights` uniform block, filled by the `LightGathering` system.
+pub const LIGHTS_BLOCK_BINDING: u32 = 0;
+/// The binding point of the `Shadows` uniform block, filled by the `Render` system.
+pub const SHADOWS_BLOCK_BINDING: u32 = 1;
+/// The binding point of the `Camera` uniform block, filled by the `Render` system.
+pub const CAMERA_BLOCK_BINDING: u32 = 2;
+
+/// The uniform blocks shared by the shaders. When a shader declares one of them, it is bound to its
+/// binding point once the shader is built.
+const UNIFORM_BLOCK_BINDINGS: [(&str, u32); 3] = [
+    ("Lights", LIGHTS_BLOCK_BINDING),
+    ("Shadows", SHADOWS_BLOCK_BINDING),
+    ("Camera", CAMERA_BLOCK_BINDING),
+];
+
+/// The stages of a shader program, to tell which source an error comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+}
+
+impl ShaderStage {
+    fn gl_type(self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Geometry => "geometry",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A message of an OpenGL info log, with the line of the source it refers to when the driver
+/// gives one. The file is only known once the line is mapped back to the shader files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderLogEntry {
+    pub path: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// The info log of a shader or a program, split into messages.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShaderLog {
+    pub entries: Vec<ShaderLogEntry>,
+}
+
+impl ShaderLog {
+    /// Parse the info log of the driver. The locations are written `0(12)` by NVIDIA, `0:12(5)` by
+    /// Mesa and `ERROR: 0:12:` by AMD and Apple, where the first number is the index of the source
+    /// string and the second one the line. Messages without a location are kept as they are.
+    pub fn parse(info_log: &str) -> Self {
+        let entries = info_log
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(parse_log_entry)
+            .collect();
+
+        ShaderLog { entries }
+    }
+
+    /// Replace the lines of the preprocessed source by the file and line they come from.
+    pub fn map_lines(mut self, source: &PreprocessedSource) -> Self {
+        for entry in &mut self.entries {
+            if let Some(location) = entry.line.and_then(|line| source.location(line)) {
+                entry.path = Some(location.path.clone());
+                entry.line = Some(location.line);
+            }
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for ShaderLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            match (&entry.path, entry.line) {
+                (&Some(ref path), Some(line)) => write!(f, "{}:{}: {}", path, line, entry.message)?,
+                (_, Some(line)) => write!(f, "line {}: {}", line, entry.message)?,
+                _ => write!(f, "{}", entry.message)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum ShaderError {
+    #[fail(display = "Cannot preprocess the {} shader: {}", stage, error)]
+    Preprocess {
+        stage: ShaderStage,
+        #[cause]
+        error: PreprocessError,
+    },
+    #[fail(display = "The {} shader at '{}' contains a nul byte", stage, path)]
+    NulByte { stage: ShaderStage, path: String },
+    #[fail(display = "Cannot compile the {} shader at '{}':\n{}", stage, path, log)]
+    Compile {
+        stage: ShaderStage,
+        path: String,
+        log: ShaderLog,
+    },
+    #[fail(display = "Cannot link the shader of '{}' and '{}':\n{}", vertex_path, fragment_path, log)]
+    Link {
+        vertex_path: String,
+        fragment_path: String,
+        log: ShaderLog,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub struct Shader {
-    pub ID: u32,
+    /// Deletes the program once the last clone of the shader is dropped.
+    program: GlHandle,
+    /// The files of every stage, including the included ones.
+    source_files: Vec<String>,
 }
 
 /// NOTE: mixture of `shader_s.h` and `shader_m.h` (the latter just contains
 /// a few more setters for uniforms)
 #[allow(dead_code)]
 impl Shader {
-    pub fn new(vertexPath: &str, fragmentPath: &str) -> Shader {
-        let mut shader = Shader { ID: 0 };
-        // 1. retrieve the vertex/fragment source code from filesystem
-        let mut vShaderFile = File::open(vertexPath).unwrap_or_else(|_| panic!("Failed to open {}", vertexPath));
-        let mut fShaderFile = File::open(fragmentPath).unwrap_or_else(|_| panic!("Failed to open {}", fragmentPath));
-        let mut vertexCode = String::new();
-        let mut fragmentCode = String::new();
-        vShaderFile
-            .read_to_string(&mut vertexCode)
-            .expect("Failed to read vertex shader");
-        fShaderFile
-            .read_to_string(&mut fragmentCode)
-            .expect("Failed to read fragment shader");
-
-        let vShaderCode = CString::new(vertexCode.as_bytes()).unwrap();
-        let fShaderCode = CString::new(fragmentCode.as_bytes()).unwrap();
-
-        // 2. compile shaders
-        unsafe {
-            // vertex shader
-            let vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vertex, 1, &vShaderCode.as_ptr(), ptr::null());
-            gl::CompileShader(vertex);
-            shader.checkCompileErrors(vertex, "VERTEX");
-            // fragment Shader
-            let fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fragment, 1, &fShaderCode.as_ptr(), ptr::null());
-            gl::CompileShader(fragment);
-            shader.checkCompileErrors(fragment, "FRAGMENT");
-            // shader Program
-            let ID = gl::CreateProgram();
-            gl::AttachShader(ID, vertex);
-            gl::AttachShader(ID, fragment);
-            gl::LinkProgram(ID);
-            shader.checkCompileErrors(ID, "PROGRAM");
-            // delete the shaders as they're linked into our program now and no longer necessary
-            gl::DeleteShader(vertex);
-            gl::DeleteShader(fragment);
-            shader.ID = ID;
-        }
+    pub fn new(vertexPath: &str, fragmentPath: &str) -> Result<Shader, ShaderError> {
+        Shader::build(vertexPath, fragmentPath, None, &[])
+    }
+
+    /// Build a variant of a shader, with a `#define` added to each stage for each of `defines`,
+    /// such as `("MAX_LIGHTS", "4")`. See `shader_preprocessor::preprocess`.
+    pub fn with_defines(vertexPath: &str, fragmentPath: &str, defines: &[(&str, &str)]) -> Result<Shader, ShaderError> {
+        Shader::build(vertexPath, fragmentPath, None, defines)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.program.id()
+    }
 
-        shader
+    /// The files the shader is built from, to reload it when one of them changes.
+    pub fn source_files(&self) -> &[String] {
+        &self.source_files
     }
 
     /// activate the shader
     /// ------------------------------------------------------------------------
     pub unsafe fn useProgram(&self) {
-        gl::UseProgram(self.ID)
+        gl::UseProgram(self.program.id())
     }
 
     /// utility uniform functions
     /// ------------------------------------------------------------------------
     pub unsafe fn setBool(&self, name: &CStr, value: bool) {
-        gl::Uniform1i(gl::GetUniformLocation(self.ID, name.as_ptr()), value as i32);
+        gl::Uniform1i(gl::GetUniformLocation(self.program.id(), name.as_ptr()), value as i32);
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setInt(&self, name: &CStr, value: i32) {
-        gl::Uniform1i(gl::GetUniformLocation(self.ID, name.as_ptr()), value);
+        gl::Uniform1i(gl::GetUniformLocation(self.program.id(), name.as_ptr()), value);
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setFloat(&self, name: &CStr, value: f32) {
-        gl::Uniform1f(gl::GetUniformLocation(self.ID, name.as_ptr()), value);
+        gl::Uniform1f(gl::GetUniformLocation(self.program.id(), name.as_ptr()), value);
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setVector3(&self, name: &CStr, value: &Vector3<f32>) {
-        gl::Uniform3fv(gl::GetUniformLocation(self.ID, name.as_ptr()), 1, value.as_ptr());
+        gl::Uniform3fv(gl::GetUniformLocation(self.program.id(), name.as_ptr()), 1, value.as_ptr());
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setVec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
-        gl::Uniform3f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y, z);
+        gl::Uniform3f(gl::GetUniformLocation(self.program.id(), name.as_ptr()), x, y, z);
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setVector4(&self, name: &CStr, value: &Vector4<f32>) {
-        gl::Uniform4fv(gl::GetUniformLocation(self.ID, name.as_ptr()), 1, value.as_ptr());
+        gl::Uniform4fv(gl::GetUniformLocation(self.program.id(), name.as_ptr()), 1, value.as_ptr());
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setVec4(&self, name: &CStr, x: f32, y: f32, z: f32, w: f32) {
-        gl::Uniform4f(gl::GetUniformLocation(self.ID, name.as_ptr()), x, y, z, w);
+        gl::Uniform4f(gl::GetUniformLocation(self.program.id(), name.as_ptr()), x, y, z, w);
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setVector4Array(&self, name: &CStr, value: &[f32]) {
         gl::Uniform4fv(
-            gl::GetUniformLocation(self.ID, name.as_ptr()), value.len() as i32, value.as_ptr(),
+            gl::GetUniformLocation(self.program.id(), name.as_ptr()), value.len() as i32, value.as_ptr(),
         );
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setMat3(&self, name: &CStr, mat: &Matrix3<f32>) {
-        gl::UniformMatrix3fv(gl::GetUniformLocation(self.ID, name.as_ptr()), 1, gl::FALSE, mat.as_ptr());
+        gl::UniformMatrix3fv(gl::GetUniformLocation(self.program.id(), name.as_ptr()), 1, gl::FALSE, mat.as_ptr());
     }
     /// ------------------------------------------------------------------------
     pub unsafe fn setMat4(&self, name: &CStr, mat: &Matrix4<f32>) {
-        gl::UniformMatrix4fv(gl::GetUniformLocation(self.ID, name.as_ptr()), 1, gl::FALSE, mat.as_ptr());
+        gl::UniformMatrix4fv(gl::GetUniformLocation(self.program.id(), name.as_ptr()), 1, gl::FALSE, mat.as_ptr());
     }
 
-    /// utility function for checking shader compilation/linking errors.
-    /// ------------------------------------------------------------------------
-    unsafe fn checkCompileErrors(&self, shader: u32, type_: &str) {
-        let mut success = gl::FALSE as GLint;
-        let mut infoLog = Vec::with_capacity(1024);
-        infoLog.set_len(1024 - 1); // subtract 1 to skip the trailing null character
-        if type_ != "PROGRAM" {
-            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(shader, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("ERROR::SHADER_COMPILATION_ERROR of type: {}\n{}\n \
-                          -- --------------------------------------------------- -- ",
-                         type_,
-                         str::from_utf8(&infoLog).unwrap());
+    /// Set the uniforms of this program to the values they have in `other`, so a reloaded shader
+    /// keeps the values which are only set once, like the projection. Arrays are not copied.
+    pub unsafe fn copy_uniforms_from(&self, other: &Shader) {
+        let mut count = 0;
+        gl::GetProgramiv(other.id(), gl::ACTIVE_UNIFORMS, &mut count);
+        self.useProgram();
+
+        for index in 0..count as u32 {
+            let mut name: Vec<u8> = vec![0; 256];
+            let (mut length, mut size, mut type_) = (0, 0, 0);
+            gl::GetActiveUniform(other.id(), index, 256, &mut length, &mut size, &mut type_, name.as_mut_ptr() as *mut GLchar);
+            name.truncate(length as usize);
+
+            let name = match CString::new(name) {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let location = gl::GetUniformLocation(other.id(), name.as_ptr());
+            let new_location = gl::GetUniformLocation(self.id(), name.as_ptr());
+
+            if size != 1 || location < 0 || new_location < 0 {
+                continue;
             }
 
-        } else {
-            gl::GetProgramiv(shader, gl::LINK_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetProgramInfoLog(shader, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("ERROR::PROGRAM_LINKING_ERROR of type: {}\n{}\n \
-                          -- --------------------------------------------------- -- ",
-                         type_,
-                         str::from_utf8(&infoLog).unwrap());
+            let mut values = [0.0_f32; 16];
+            match type_ {
+                gl::INT | gl::BOOL | gl::SAMPLER_2D => {
+                    let mut value = 0;
+                    gl::GetUniformiv(other.id(), location, &mut value);
+                    gl::Uniform1i(new_location, value);
+                }
+                gl::FLOAT => {
+                    gl::GetUniformfv(other.id(), location, values.as_mut_ptr());
+                    gl::Uniform1fv(new_location, 1, values.as_ptr());
+                }
+                gl::FLOAT_VEC3 => {
+                    gl::GetUniformfv(other.id(), location, values.as_mut_ptr());
+                    gl::Uniform3fv(new_location, 1, values.as_ptr());
+                }
+                gl::FLOAT_VEC4 => {
+                    gl::GetUniformfv(other.id(), location, values.as_mut_ptr());
+                    gl::Uniform4fv(new_location, 1, values.as_ptr());
+                }
+                gl::FLOAT_MAT3 => {
+                    gl::GetUniformfv(other.id(), location, values.as_mut_ptr());
+                    gl::UniformMatrix3fv(new_location, 1, gl::FALSE, values.as_ptr());
+                }
+                gl::FLOAT_MAT4 => {
+                    gl::GetUniformfv(other.id(), location, values.as_mut_ptr());
+                    gl::UniformMatrix4fv(new_location, 1, gl::FALSE, values.as_ptr());
+                }
+                _ => {}
             }
         }
-
     }
 
     /// Only used in 4.9 Geometry shaders - ignore until then (shader.h in original C++)
-    pub fn with_geometry_shader(vertexPath: &str, fragmentPath: &str, geometryPath: &str) -> Self {
-        let mut shader = Shader { ID: 0 };
-        // 1. retrieve the vertex/fragment source code from filesystem
-        let mut vShaderFile = File::open(vertexPath).unwrap_or_else(|_| panic!("Failed to open {}", vertexPath));
-        let mut fShaderFile = File::open(fragmentPath).unwrap_or_else(|_| panic!("Failed to open {}", fragmentPath));
-        let mut gShaderFile = File::open(geometryPath).unwrap_or_else(|_| panic!("Failed to open {}", geometryPath));
-        let mut vertexCode = String::new();
-        let mut fragmentCode = String::new();
-        let mut geometryCode = String::new();
-        vShaderFile
-            .read_to_string(&mut vertexCode)
-            .expect("Failed to read vertex shader");
-        fShaderFile
-            .read_to_string(&mut fragmentCode)
-            .expect("Failed to read fragment shader");
-        gShaderFile
-            .read_to_string(&mut geometryCode)
-            .expect("Failed to read geometry shader");
-
-        let vShaderCode = CString::new(vertexCode.as_bytes()).unwrap();
-        let fShaderCode = CString::new(fragmentCode.as_bytes()).unwrap();
-        let gShaderCode = CString::new(geometryCode.as_bytes()).unwrap();
-
-        // 2. compile shaders
+    pub fn with_geometry_shader(vertexPath: &str, fragmentPath: &str, geometryPath: &str) -> Result<Self, ShaderError> {
+        Shader::build(vertexPath, fragmentPath, Some(geometryPath), &[])
+    }
+
+    fn build(
+        vertex_path: &str,
+        fragment_path: &str,
+        geometry_path: Option<&str>,
+        defines: &[(&str, &str)],
+    ) -> Result<Self, ShaderError> {
+        let mut stages = vec![(ShaderStage::Vertex, vertex_path), (ShaderStage::Fragment, fragment_path)];
+        if let Some(geometry_path) = geometry_path {
+            stages.push((ShaderStage::Geometry, geometry_path));
+        }
+
+        // 1. retrieve the source code from filesystem
+        let sources = stages
+            .iter()
+            .map(|&(stage, path)| {
+                preprocess(path, defines).map_err(|error| ShaderError::Preprocess { stage, error })
+            })
+            .collect::<Result<Vec<PreprocessedSource>, ShaderError>>()?;
+        let mut source_files: Vec<String> = Vec::new();
+        for source in &sources {
+            for file in &source.files {
+                if !source_files.contains(file) {
+                    source_files.push(file.clone());
+                }
+            }
+        }
+
         unsafe {
-            // vertex shader
-            let vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vertex, 1, &vShaderCode.as_ptr(), ptr::null());
-            gl::CompileShader(vertex);
-            shader.checkCompileErrors(vertex, "VERTEX");
-            // fragment Shader
-            let fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fragment, 1, &fShaderCode.as_ptr(), ptr::null());
-            gl::CompileShader(fragment);
-            shader.checkCompileErrors(fragment, "FRAGMENT");
-            // geometry shader
-            let geometry = gl::CreateShader(gl::GEOMETRY_SHADER);
-            gl::ShaderSource(geometry, 1, &gShaderCode.as_ptr(), ptr::null());
-            gl::CompileShader(geometry);
-            shader.checkCompileErrors(geometry, "GEOMETRY");
+            // 2. compile shaders
+            let mut shaders = Vec::new();
+            for (&(stage, path), source) in stages.iter().zip(&sources) {
+                match compile(stage, path, source) {
+                    Ok(shader) => shaders.push(shader),
+                    Err(error) => {
+                        for shader in shaders {
+                            gl::DeleteShader(shader);
+                        }
+                        return Err(error);
+                    }
+                }
+            }
 
             // shader Program
             let ID = gl::CreateProgram();
-            gl::AttachShader(ID, vertex);
-            gl::AttachShader(ID, fragment);
-            gl::AttachShader(ID, geometry);
+            for &shader in &shaders {
+                gl::AttachShader(ID, shader);
+            }
             gl::LinkProgram(ID);
-            shader.checkCompileErrors(ID, "PROGRAM");
+            // The program is deleted along with the shader when there is an error.
+            let shader = Shader {
+                program: GlHandle::new(GlObjectKind::Program, ID),
+                source_files,
+            };
             // delete the shaders as they're linked into our program now and no longer necessary
-            gl::DeleteShader(vertex);
-            gl::DeleteShader(fragment);
-            gl::DeleteShader(geometry);
-            shader.ID = ID;
+            for shader in shaders {
+                gl::DeleteShader(shader);
+            }
+
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(ID, gl::LINK_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                return Err(ShaderError::Link {
+                    vertex_path: vertex_path.to_string(),
+                    fragment_path: fragment_path.to_string(),
+                    log: ShaderLog::parse(&program_info_log(ID)),
+                });
+            }
+
+            for &(block_name, binding) in &UNIFORM_BLOCK_BINDINGS {
+                let block_name = CString::new(block_name).unwrap();
+                let block_index = gl::GetUniformBlockIndex(ID, block_name.as_ptr());
+
+                if block_index != gl::INVALID_INDEX {
+                    gl::UniformBlockBinding(ID, block_index, binding);
+                }
+            }
+
+            Ok(shader)
         }
+    }
+}
+
+/// Compile the source of a stage, the shader is deleted when it does not compile.
+unsafe fn compile(stage: ShaderStage, path: &str, source: &PreprocessedSource) -> Result<GLuint, ShaderError> {
+    let code = CString::new(source.code.as_bytes()).map_err(|_| ShaderError::NulByte {
+        stage,
+        path: path.to_string(),
+    })?;
+    let shader = gl::CreateShader(stage.gl_type());
+    gl::ShaderSource(shader, 1, &code.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut success = gl::FALSE as GLint;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    if success != gl::TRUE as GLint {
+        let log = ShaderLog::parse(&shader_info_log(shader)).map_lines(source);
+        gl::DeleteShader(shader);
+
+        return Err(ShaderError::Compile {
+            stage,
+            path: path.to_string(),
+            log,
+        });
+    }
+
+    Ok(shader)
+}
+
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut capacity = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut capacity);
+
+    let mut info_log: Vec<u8> = vec![0; capacity.max(1) as usize];
+    let mut length = 0;
+    gl::GetShaderInfoLog(shader, info_log.len() as GLsizei, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+    info_log.truncate(length as usize);
+
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+unsafe fn program_info_log(program: GLuint) -> String {
+    let mut capacity = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut capacity);
+
+    let mut info_log: Vec<u8> = vec![0; capacity.max(1) as usize];
+    let mut length = 0;
+    gl::GetProgramInfoLog(program, info_log.len() as GLsizei, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+    info_log.truncate(length as usize);
+
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+fn parse_log_entry(line: &str) -> ShaderLogEntry {
+    let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR:") {
+        (Some("error"), rest.trim_start())
+    } else if let Some(rest) = line.strip_prefix("WARNING:") {
+        (Some("warning"), rest.trim_start())
+    } else {
+        (None, line)
+    };
+
+    let (line_number, message) = match parse_location(rest) {
+        Some((line_number, message)) => (Some(line_number), message),
+        None => (None, rest),
+    };
+    let message = match severity {
+        Some(severity) if line_number.is_some() => format!("{}: {}", severity, message),
+        _ if line_number.is_some() => message.to_string(),
+        _ => line.to_string(),
+    };
+
+    ShaderLogEntry {
+        path: None,
+        line: line_number,
+        message,
+    }
+}
 
-        shader
+/// Parse `0(12)`, `0:12(5)` or `0:12` at the start of a log line, and return the line number along
+/// with the rest of the message.
+fn parse_location(text: &str) -> Option<(u32, &str)> {
+    let (_source_index, rest) = parse_number(text)?;
+
+    let (line_number, rest) = if let Some(rest) = rest.strip_prefix('(') {
+        let (line_number, rest) = parse_number(rest)?;
+        (line_number, rest.strip_prefix(')')?)
+    } else if let Some(rest) = rest.strip_prefix(':') {
+        let (line_number, rest) = parse_number(rest)?;
+        // Mesa adds the column after the line.
+        let rest = match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (_column, rest) = parse_number(rest)?;
+                rest.strip_prefix(')')?
+            }
+            None => rest,
+        };
+        (line_number, rest)
+    } else {
+        return None;
+    };
+
+    let message = rest.trim_start().strip_prefix(':')?;
+
+    Some((line_number, message.trim()))
+}
+
+fn parse_number(text: &str) -> Option<(u32, &str)> {
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+
+    text[..digits].parse().ok().map(|number| (number, &text[digits..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShaderLog, ShaderLogEntry};
+    use shader_preprocessor::preprocess_with;
+
+    fn entry(line: Option<u32>, message: &str) -> ShaderLogEntry {
+        ShaderLogEntry {
+            path: None,
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_nvidia_info_log() {
+        let log = ShaderLog::parse("0(12) : error C0000: syntax error, unexpected '}'\n0(14) : warning C7050: \"color\" might be used before being initialized\n");
+
+        assert_eq!(
+            log.entries,
+            vec![
+                entry(Some(12), "error C0000: syntax error, unexpected '}'"),
+                entry(Some(14), "warning C7050: \"color\" might be used before being initialized"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_mesa_info_log() {
+        let log = ShaderLog::parse("0:7(15): error: `colour' undeclared\n");
+
+        assert_eq!(log.entries, vec![entry(Some(7), "error: `colour' undeclared")]);
+    }
+
+    #[test]
+    fn parse_amd_info_log() {
+        let log = ShaderLog::parse("ERROR: 0:3: 'vec5' : no matching overloaded function found\nERROR: 1 compilation errors.  No code generated.");
+
+        assert_eq!(
+            log.entries,
+            vec![
+                entry(Some(3), "error: 'vec5' : no matching overloaded function found"),
+                entry(None, "ERROR: 1 compilation errors.  No code generated."),
+            ]
+        );
+    }
+
+    #[test]
+    fn display_log_with_line_numbers() {
+        let log = ShaderLog::parse("0:7(15): error: `colour' undeclared\nlinking failed");
+
+        assert_eq!(log.to_string(), "line 7: error: `colour' undeclared\nlinking failed");
+    }
+
+    #[test]
+    fn map_log_lines_to_the_shader_files() {
+        let source = preprocess_with("shaders/main.fs", &[("MAX_LIGHTS", "4")], |path| {
+            Ok(match path {
+                "shaders/main.fs" => "#version 330 core\n#include \"light.glsl\"\nvoid main() {}",
+                _ => "vec3 light() {\n    return colour;\n}",
+            }
+            .to_string())
+        })
+        .unwrap();
+        let log = ShaderLog::parse("0:4(12): error: `colour' undeclared\n0(42) : error C0000: unknown").map_lines(&source);
+
+        assert_eq!(
+            log.to_string(),
+            "shaders/light.glsl:2: error: `colour' undeclared\nline 42: error C0000: unknown"
+        );
     }
 }
@@ -0,0 +1,279 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const INCLUDE_DIRECTIVE: &str = "#include";
+const VERSION_DIRECTIVE: &str = "#version";
+
+#[derive(Fail, Debug)]
+pub enum PreprocessError {
+    #[fail(display = "Cannot read the shader source at '{}': {}", path, error)]
+    Read {
+        path: String,
+        #[cause]
+        error: io::Error,
+    },
+    #[fail(display = "Invalid include at '{}' line {}, expected `#include \"file\"`", path, line)]
+    InvalidInclude { path: String, line: u32 },
+}
+
+/// The file and line a line of a preprocessed source comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub path: String,
+    pub line: u32,
+}
+
+/// The code given to OpenGL, along with where each of its lines comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessedSource {
+    pub code: String,
+    /// The files read, the shader first, then its includes.
+    pub files: Vec<String>,
+    /// The location of each line of `code`, `None` for the injected defines.
+    locations: Vec<Option<SourceLocation>>,
+}
+
+impl PreprocessedSource {
+    /// Return where the line of the preprocessed code comes from. Lines start at 1, as in the info
+    /// logs of OpenGL.
+    pub fn location(&self, line: u32) -> Option<&SourceLocation> {
+        if line == 0 {
+            return None;
+        }
+
+        self.locations
+            .get(line as usize - 1)
+            .and_then(Option::as_ref)
+    }
+}
+
+/// Read the shader at `path`, replace its `#include "file"` directives by the content of the file,
+/// relative to the directory of the shader, even in the included files, and add a `#define` for
+/// each of `defines` right after the `#version` directive. A file is only included once, so
+/// includes may include each other.
+pub fn preprocess(path: &str, defines: &[(&str, &str)]) -> Result<PreprocessedSource, PreprocessError> {
+    preprocess_with(path, defines, |path| fs::read_to_string(path))
+}
+
+/// Same as `preprocess` but the files are read by `read_file`.
+pub fn preprocess_with<F>(
+    path: &str,
+    defines: &[(&str, &str)],
+    mut read_file: F,
+) -> Result<PreprocessedSource, PreprocessError>
+where
+    F: FnMut(&str) -> io::Result<String>,
+{
+    let mut source = PreprocessedSource {
+        code: String::new(),
+        files: Vec::new(),
+        locations: Vec::new(),
+    };
+    let define_lines: Vec<String> = defines
+        .iter()
+        .map(|&(name, value)| format!("#define {} {}", name, value).trim_end().to_string())
+        .collect();
+
+    include_file(path, &define_lines, &mut read_file, &mut source)?;
+
+    Ok(source)
+}
+
+/// Append the lines of the file at `path` to `source`. The defines are only added to the shader
+/// itself, which is the first file.
+fn include_file<F>(
+    path: &str,
+    define_lines: &[String],
+    read_file: &mut F,
+    source: &mut PreprocessedSource,
+) -> Result<(), PreprocessError>
+where
+    F: FnMut(&str) -> io::Result<String>,
+{
+    let is_shader = source.files.is_empty();
+    let text = read_file(path).map_err(|error| PreprocessError::Read {
+        path: path.to_string(),
+        error,
+    })?;
+    source.files.push(path.to_string());
+
+    // The version must come first, so the defines are added after it when there is one.
+    let has_version = text.lines().any(|line| line.trim_start().starts_with(VERSION_DIRECTIVE));
+    if is_shader && !has_version {
+        push_defines(define_lines, source);
+    }
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index as u32 + 1;
+        let trimmed_line = line.trim();
+
+        if let Some(include) = trimmed_line.strip_prefix(INCLUDE_DIRECTIVE) {
+            // The shader is the first file.
+            let include_path = parse_include(include)
+                .map(|name| include_path(&source.files[0], name))
+                .ok_or_else(|| PreprocessError::InvalidInclude {
+                    path: path.to_string(),
+                    line: line_number,
+                })?;
+
+            if !source.files.contains(&include_path) {
+                include_file(&include_path, define_lines, read_file, source)?;
+            }
+            continue;
+        }
+
+        push_line(line, Some(path), line_number, source);
+
+        if is_shader && trimmed_line.starts_with(VERSION_DIRECTIVE) {
+            push_defines(define_lines, source);
+        }
+    }
+
+    Ok(())
+}
+
+fn push_defines(define_lines: &[String], source: &mut PreprocessedSource) {
+    for define_line in define_lines {
+        push_line(define_line, None, 0, source);
+    }
+}
+
+fn push_line(line: &str, path: Option<&str>, line_number: u32, source: &mut PreprocessedSource) {
+    source.code.push_str(line);
+    source.code.push('\n');
+    source.locations.push(path.map(|path| SourceLocation {
+        path: path.to_string(),
+        line: line_number,
+    }));
+}
+
+/// Parse the `"file"` part of an include.
+fn parse_include(text: &str) -> Option<&str> {
+    text.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+fn include_path(shader_path: &str, name: &str) -> String {
+    match Path::new(shader_path).parent() {
+        Some(directory) => directory.join(name).to_string_lossy().into_owned(),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preprocess_with, PreprocessError, PreprocessedSource, SourceLocation};
+    use std::collections::HashMap;
+    use std::io;
+
+    fn preprocess_files(
+        files: &[(&str, &str)],
+        defines: &[(&str, &str)],
+    ) -> Result<PreprocessedSource, PreprocessError> {
+        let files: HashMap<&str, &str> = files.iter().cloned().collect();
+
+        preprocess_with("shaders/main.fs", defines, |path| {
+            files
+                .get(path)
+                .map(|text| text.to_string())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+        })
+    }
+
+    fn location(path: &str, line: u32) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            path: path.to_string(),
+            line,
+        })
+    }
+
+    #[test]
+    fn include_files_relative_to_the_shader_directory() {
+        let source = preprocess_files(
+            &[
+                ("shaders/main.fs", "#version 330 core\n#include \"lib/light.glsl\"\nvoid main() {}"),
+                ("shaders/lib/light.glsl", "#include \"lib/common.glsl\"\nvec3 light() { return ONE; }"),
+                ("shaders/lib/common.glsl", "#define ONE vec3(1.0)"),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            source.code,
+            "#version 330 core\n#define ONE vec3(1.0)\nvec3 light() { return ONE; }\nvoid main() {}\n"
+        );
+        assert_eq!(
+            source.files,
+            vec!["shaders/main.fs", "shaders/lib/light.glsl", "shaders/lib/common.glsl"]
+        );
+    }
+
+    #[test]
+    fn include_files_once() {
+        let source = preprocess_files(
+            &[
+                ("shaders/main.fs", "#include \"a.glsl\"\n#include \"b.glsl\""),
+                ("shaders/a.glsl", "#include \"b.glsl\"\na"),
+                ("shaders/b.glsl", "#include \"a.glsl\"\nb"),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(source.code, "b\na\n");
+    }
+
+    #[test]
+    fn add_defines_after_the_version() {
+        let source = preprocess_files(
+            &[("shaders/main.fs", "// Comment\n#version 330 core\nvoid main() {}")],
+            &[("USE_TEXTURE", ""), ("MAX_LIGHTS", "4")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            source.code,
+            "// Comment\n#version 330 core\n#define USE_TEXTURE\n#define MAX_LIGHTS 4\nvoid main() {}\n"
+        );
+
+        let source = preprocess_files(&[("shaders/main.fs", "void main() {}")], &[("MAX_LIGHTS", "4")])
+            .unwrap();
+
+        assert_eq!(source.code, "#define MAX_LIGHTS 4\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn map_lines_to_the_original_files() {
+        let source = preprocess_files(
+            &[
+                ("shaders/main.fs", "#version 330 core\n#include \"light.glsl\"\nvoid main() {}"),
+                ("shaders/light.glsl", "uniform vec3 a;\nuniform vec3 b;"),
+            ],
+            &[("MAX_LIGHTS", "4")],
+        )
+        .unwrap();
+
+        assert_eq!(source.location(1).cloned(), location("shaders/main.fs", 1));
+        assert_eq!(source.location(2), None);
+        assert_eq!(source.location(4).cloned(), location("shaders/light.glsl", 2));
+        assert_eq!(source.location(5).cloned(), location("shaders/main.fs", 3));
+        assert_eq!(source.location(6), None);
+    }
+
+    #[test]
+    fn report_invalid_and_missing_includes() {
+        match preprocess_files(&[("shaders/main.fs", "\n#include <light.glsl>")], &[]) {
+            Err(PreprocessError::InvalidInclude { path, line }) => {
+                assert_eq!(path, "shaders/main.fs");
+                assert_eq!(line, 2);
+            }
+            result => panic!("expected an invalid include, got {:?}", result),
+        }
+
+        match preprocess_files(&[("shaders/main.fs", "#include \"light.glsl\"")], &[]) {
+            Err(PreprocessError::Read { path, .. }) => assert_eq!(path, "shaders/light.glsl"),
+            result => panic!("expected a read error, got {:?}", result),
+        }
+    }
+}
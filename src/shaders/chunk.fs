@@ -3,25 +3,17 @@
 in vec3 FragPos;
 in vec3 Color;
 in vec3 Normal;
+in float AmbientOcclusion;
 
 out vec4 FragColor;
 
-uniform vec3 ambientLightColor;
-uniform vec3 lightPosition;
-uniform vec3 lightColor;
+#include "lighting.glsl"
 
-void main() {
-    float ambientStrength = 0.1;
-
-    // Ambient light calculation
-    vec3 ambient = ambientStrength * ambientLightColor;
+// The light left on the most occluded vertices.
+const float MIN_AMBIENT_OCCLUSION_LIGHT = 0.4;
 
-    // Diffuse light calculation
-    vec3 norm = normalize(Normal);
-    vec3 lightDir = normalize(lightPosition - FragPos);
-    float diff = max(dot(norm, lightDir), 0.0);
-    vec3 diffuse = diff * lightColor;
-
-    vec3 result = (ambient + diffuse) * Color;
+void main() {
+    float occlusion = mix(MIN_AMBIENT_OCCLUSION_LIGHT, 1.0, AmbientOcclusion);
+    vec3 result = computeLighting(FragPos, Normal) * Color * occlusion;
     FragColor = vec4(result, 1.0);
 }
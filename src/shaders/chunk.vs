@@ -2,15 +2,23 @@
 layout(location = 0) in vec3 aPosition;
 layout(location = 1) in uint aColorIndex;
 layout(location = 2) in vec3 aNormal;
+// From 0 when the vertex is fully occluded to 3
+layout(location = 3) in float aAmbientOcclusion;
 
 out vec3 FragPos;
 out vec3 Color;
 out vec3 Normal;
+out float AmbientOcclusion;
 
 uniform mat4 model;
 uniform mat3 normalMatrix;
-uniform mat4 view;
-uniform mat4 projection;
+
+// Uploaded once per frame by the `Render` system.
+layout(std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+};
+
 // TODO: A vec3 could be used
 uniform vec4 palette[256];
 
@@ -19,4 +27,5 @@ void main() {
     FragPos = vec3(model * vec4(aPosition, 1.0));
     Color = palette[aColorIndex].xyz;
     Normal = normalMatrix * aNormal;
+    AmbientOcclusion = aAmbientOcclusion / 3.0;
 }
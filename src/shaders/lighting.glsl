@@ -0,0 +1,142 @@
+// Lighting shared by the shaders, include it with `#include "lighting.glsl"`. The lights are
+// uploaded by the `LightGathering` system, which also gives `MAX_LIGHTS`, and the shadows by the
+// `Render` system.
+
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS 16
+#endif
+#ifndef CASCADE_COUNT
+#define CASCADE_COUNT 3
+#endif
+#ifndef SHADOW_MAP_LAYERS
+#define SHADOW_MAP_LAYERS 7
+#endif
+
+#define POINT_LIGHT 0
+#define DIRECTIONAL_LIGHT 1
+#define SPOT_LIGHT 2
+
+struct Light {
+    // xyz: world position, w: type
+    vec4 positionAndType;
+    // xyz: world direction, w: range
+    vec4 directionAndRange;
+    // rgb: color times intensity
+    vec4 color;
+    // x: cosine of the inner angle, y: cosine of the outer angle, z: shadow map layer or -1
+    vec4 spotCosines;
+};
+
+layout(std140) uniform Lights {
+    // rgb: ambient color, a: number of lights
+    vec4 ambientColorAndCount;
+    Light lights[MAX_LIGHTS];
+};
+
+layout(std140) uniform Shadows {
+    // World space to the texture coordinates and depth of each shadow map layer
+    mat4 shadowMatrices[SHADOW_MAP_LAYERS];
+    // The distance from the camera where each cascade ends
+    vec4 cascadeFarDistances;
+    vec4 cameraPosition;
+    vec4 cameraForward;
+    // x: size of a texel, y: depth bias
+    vec4 shadowSettings;
+};
+
+uniform sampler2DArrayShadow shadowMap;
+uniform bool receiveShadows;
+
+// Average 3x3 depth comparisons around the fragment (percentage closer filtering), so the edges of
+// the shadows are soft.
+float sampleShadowMap(int layer, vec3 fragPos, float bias) {
+    vec4 shadowPosition = shadowMatrices[layer] * vec4(fragPos, 1.0);
+    vec3 coordinates = shadowPosition.xyz / shadowPosition.w;
+
+    // Beyond the far plane of the light.
+    if (coordinates.z > 1.0) {
+        return 1.0;
+    }
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 offset = vec2(x, y) * shadowSettings.x;
+            lit += texture(shadowMap, vec4(coordinates.xy + offset, float(layer), coordinates.z - bias));
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// How much of the light reaches the fragment, from 0 in the shadow to 1.
+float shadowFactor(int lightType, float shadowLayer, vec3 fragPos, vec3 normal, vec3 lightDir) {
+    if (!receiveShadows || shadowLayer < 0.0) {
+        return 1.0;
+    }
+
+    // The surfaces facing away from the light need a larger bias.
+    float bias = shadowSettings.y * (1.0 + 4.0 * (1.0 - max(dot(normal, lightDir), 0.0)));
+    int layer = int(shadowLayer + 0.5);
+
+    if (lightType == DIRECTIONAL_LIGHT) {
+        float viewDepth = dot(fragPos - cameraPosition.xyz, cameraForward.xyz);
+
+        for (int cascade = 0; cascade < CASCADE_COUNT; cascade++) {
+            if (viewDepth <= cascadeFarDistances[cascade]) {
+                // The farther cascades cover more space with each texel.
+                return sampleShadowMap(layer + cascade, fragPos, bias * float(cascade + 1));
+            }
+        }
+
+        return 1.0;
+    }
+
+    return sampleShadowMap(layer, fragPos, bias);
+}
+
+// Fade out smoothly until the range, where the light has no effect.
+float rangeAttenuation(float distance, float range) {
+    float falloff = clamp(1.0 - distance / range, 0.0, 1.0);
+    return falloff * falloff;
+}
+
+vec3 computeLighting(vec3 fragPos, vec3 normal) {
+    float ambientStrength = 0.1;
+
+    // Ambient light calculation
+    vec3 result = ambientStrength * ambientColorAndCount.rgb;
+
+    vec3 norm = normalize(normal);
+    int lightCount = min(int(ambientColorAndCount.a), MAX_LIGHTS);
+
+    for (int i = 0; i < lightCount; i++) {
+        int lightType = int(lights[i].positionAndType.w);
+        vec3 lightDirection = normalize(lights[i].directionAndRange.xyz);
+        vec3 lightDir;
+        float attenuation = 1.0;
+
+        if (lightType == DIRECTIONAL_LIGHT) {
+            lightDir = -lightDirection;
+        } else {
+            vec3 toLight = lights[i].positionAndType.xyz - fragPos;
+            float distance = length(toLight);
+            lightDir = toLight / max(distance, 0.0001);
+            attenuation = rangeAttenuation(distance, lights[i].directionAndRange.w);
+
+            if (lightType == SPOT_LIGHT) {
+                float cosInner = lights[i].spotCosines.x;
+                float cosOuter = lights[i].spotCosines.y;
+                float theta = dot(-lightDir, lightDirection);
+                attenuation *= clamp((theta - cosOuter) / max(cosInner - cosOuter, 0.0001), 0.0, 1.0);
+            }
+        }
+
+        // Diffuse light calculation
+        float diff = max(dot(norm, lightDir), 0.0);
+        float shadow = shadowFactor(lightType, lights[i].spotCosines.z, fragPos, norm, lightDir);
+        result += diff * attenuation * shadow * lights[i].color.rgb;
+    }
+
+    return result;
+}
@@ -0,0 +1,10 @@
+#version 330 core
+layout(location = 0) in vec3 aPosition;
+
+// The view projection of the light drawing the shadow map layer.
+uniform mat4 lightMatrix;
+uniform mat4 model;
+
+void main() {
+    gl_Position = lightMatrix * model * vec4(aPosition, 1.0);
+}
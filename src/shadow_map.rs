@@ -0,0 +1,160 @@
+use gl;
+use gl_object::{GlHandle, GlObjectKind};
+use shader::{Shader, SHADOWS_BLOCK_BINDING};
+use shadows::{SHADOWS_BLOCK_LENGTH, SHADOW_MAP_LAYERS, SHADOW_MAP_RESOLUTION};
+use std::ptr;
+use uniform_buffer::UniformBuffer;
+
+const DEPTH_VERTEX_SHADER_PATH: &str = "src/shaders/shadow.vs";
+const DEPTH_FRAGMENT_SHADER_PATH: &str = "src/shaders/shadow.fs";
+
+/// The texture unit of the shadow map. It is the last of the 16 units OpenGL 3.3 guarantees, so it
+/// does not collide with the textures of the materials.
+pub const SHADOW_MAP_TEXTURE_UNIT: u32 = 15;
+
+/// The depth textures of the shadow casters, one layer of a texture array per cascade or spot
+/// light, along with the shader drawing in them and the `Shadows` uniform block.
+#[derive(Debug)]
+pub struct ShadowMap {
+    texture: GlHandle,
+    framebuffer: GlHandle,
+    uniform_buffer: UniformBuffer,
+    /// `None` when the depth shader does not build, the shadow map then stays empty.
+    depth_shader: Option<Shader>,
+}
+
+impl Default for ShadowMap {
+    fn default() -> Self {
+        let depth_shader = match Shader::new(DEPTH_VERTEX_SHADER_PATH, DEPTH_FRAGMENT_SHADER_PATH) {
+            Ok(shader) => Some(shader),
+            Err(error) => {
+                error!("The shadows are disabled: {}", error);
+                None
+            }
+        };
+
+        unsafe {
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::DEPTH_COMPONENT24 as i32,
+                SHADOW_MAP_RESOLUTION as i32,
+                SHADOW_MAP_RESOLUTION as i32,
+                SHADOW_MAP_LAYERS as i32,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                ptr::null(),
+            );
+            // The lookups compare the depth themselves, and the linear filtering blends the four
+            // closest comparisons.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+            // Everything outside of the shadow map is lit.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            let border_color = [1.0f32; 4];
+            gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, border_color.as_ptr());
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+            let mut framebuffer = 0;
+            gl::GenFramebuffers(1, &mut framebuffer);
+
+            let shadow_map = Self {
+                texture: GlHandle::new(GlObjectKind::Texture, texture),
+                framebuffer: GlHandle::new(GlObjectKind::Framebuffer, framebuffer),
+                uniform_buffer: UniformBuffer::new(SHADOWS_BLOCK_BINDING, SHADOWS_BLOCK_LENGTH),
+                depth_shader,
+            };
+
+            // Clear every layer once, so the unused ones are lit.
+            let previous_framebuffer = shadow_map.begin_depth_pass();
+            for layer in 0..SHADOW_MAP_LAYERS {
+                shadow_map.bind_layer(layer);
+            }
+            shadow_map.end_depth_pass(previous_framebuffer);
+
+            shadow_map
+        }
+    }
+}
+
+impl ShadowMap {
+    /// Create the shadow map, on the thread owning the OpenGL context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shader drawing the depth of the casters, with the `lightMatrix` and `model` uniforms.
+    pub fn depth_shader(&self) -> Option<&Shader> {
+        self.depth_shader.as_ref()
+    }
+
+    /// Start drawing in the shadow map and return the state `end_depth_pass` restores: the bound
+    /// framebuffer and the viewport.
+    pub fn begin_depth_pass(&self) -> (i32, [i32; 4]) {
+        unsafe {
+            let mut previous_framebuffer = 0;
+            let mut previous_viewport = [0; 4];
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+            gl::GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.id());
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            gl::Viewport(0, 0, SHADOW_MAP_RESOLUTION as i32, SHADOW_MAP_RESOLUTION as i32);
+            // Push the depth of the casters back against shadow acne.
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+
+            (previous_framebuffer, previous_viewport)
+        }
+    }
+
+    /// Draw the next casters in `layer`, which is cleared.
+    pub fn bind_layer(&self, layer: usize) {
+        unsafe {
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.texture.id(),
+                0,
+                layer as i32,
+            );
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    pub fn end_depth_pass(&self, (previous_framebuffer, previous_viewport): (i32, [i32; 4])) {
+        unsafe {
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+            gl::Viewport(
+                previous_viewport[0],
+                previous_viewport[1],
+                previous_viewport[2],
+                previous_viewport[3],
+            );
+        }
+    }
+
+    /// Upload the `Shadows` uniform block, see `ShadowLayers::pack`, and bind the shadow map to
+    /// `SHADOW_MAP_TEXTURE_UNIT` for the lookups of the shaders.
+    pub fn bind(&self, shadows_block: &[f32]) {
+        self.uniform_buffer.upload(shadows_block);
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_TEXTURE_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.texture.id());
+        }
+    }
+}
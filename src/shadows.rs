@@ -0,0 +1,344 @@
+use cgmath::prelude::*;
+use cgmath::{ortho, perspective, Deg, Matrix4, Point3, Vector3, Vector4};
+use components::camera::Camera;
+
+/// The number of cascades of the main directional light. Each cascade covers a farther slice of
+/// the view frustum with the same resolution.
+pub const CASCADE_COUNT: usize = 3;
+/// The maximum number of spot lights casting shadows in a frame.
+pub const MAX_SHADOWED_SPOT_LIGHTS: usize = 4;
+/// The layers of the shadow map: the cascades first, then one per spot light.
+pub const SHADOW_MAP_LAYERS: usize = CASCADE_COUNT + MAX_SHADOWED_SPOT_LIGHTS;
+/// The width and height of each layer of the shadow map.
+pub const SHADOW_MAP_RESOLUTION: u32 = 1024;
+/// The number of floats of the `Shadows` uniform block of `lighting.glsl`, in the std140 layout.
+pub const SHADOWS_BLOCK_LENGTH: usize = SHADOW_MAP_LAYERS * 16 + 4 * 4;
+/// Shadows are not drawn farther than this distance from the camera, so the cascades keep a
+/// useful resolution with a far camera plane.
+pub const MAX_SHADOW_DISTANCE: f32 = 60.0;
+/// How much the cascade splits follow a logarithmic distribution rather than a uniform one.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+/// How far behind a cascade the casters are still drawn in the shadow map, so objects out of the
+/// view still cast their shadow in it.
+const CASTER_DISTANCE: f32 = 50.0;
+/// Subtracted from the depth of the fragments before the comparison with the shadow map, so the
+/// surfaces do not shadow themselves. The shaders scale it with the slope and the cascade.
+const DEPTH_BIAS: f32 = 0.0005;
+
+/// A light whose shadows are drawn in the frame. The `LightGathering` system finds them and the
+/// `Render` system draws their shadow maps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowCaster {
+    /// The main directional light, drawn in the `CASCADE_COUNT` first layers.
+    Directional { direction: Vector3<f32> },
+    Spot {
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        outer_angle: f32,
+        range: f32,
+        layer: usize,
+    },
+}
+
+/// The view projection matrices of the layers of the shadow map in a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowLayers {
+    /// The matrix of each layer, `None` when no light uses the layer.
+    pub matrices: [Option<Matrix4<f32>>; SHADOW_MAP_LAYERS],
+    /// The distances from the camera where the cascades end.
+    pub cascade_far_distances: [f32; CASCADE_COUNT],
+}
+
+impl ShadowLayers {
+    /// Place the layers of `casters` for `camera`, whose view matrix is `view`. Each cascade of the
+    /// directional light covers a slice of the frustum of the camera.
+    pub fn new(casters: &[ShadowCaster], camera: &Camera, view: Matrix4<f32>) -> Self {
+        let cascade_far_distances = cascade_splits(camera.near, camera.far.min(MAX_SHADOW_DISTANCE));
+        let mut matrices = [None; SHADOW_MAP_LAYERS];
+
+        for caster in casters {
+            match *caster {
+                ShadowCaster::Directional { direction } => {
+                    let mut near = camera.near;
+
+                    for (cascade, &far) in cascade_far_distances.iter().enumerate() {
+                        let projection = perspective(Deg(camera.fov), camera.aspect_ratio, near, far);
+                        let corners = frustum_corners(projection * view);
+
+                        matrices[cascade] =
+                            Some(directional_shadow_matrix(direction, &corners, SHADOW_MAP_RESOLUTION));
+                        near = far;
+                    }
+                }
+                ShadowCaster::Spot {
+                    position,
+                    direction,
+                    outer_angle,
+                    range,
+                    layer,
+                } => {
+                    matrices[layer] = Some(spot_shadow_matrix(position, direction, outer_angle, range));
+                }
+            }
+        }
+
+        Self {
+            matrices,
+            cascade_far_distances,
+        }
+    }
+
+    /// Pack the layers in the std140 layout of the `Shadows` uniform block: the matrix of each
+    /// layer mapping the world to the shadow map, the far distances of the cascades, the position
+    /// and forward direction of the camera to find the cascade of a fragment, then the size of a
+    /// texel and the depth bias.
+    pub fn pack(&self, camera_position: Point3<f32>, camera_forward: Vector3<f32>) -> Vec<f32> {
+        let mut shadows_block = Vec::with_capacity(SHADOWS_BLOCK_LENGTH);
+
+        for matrix in &self.matrices {
+            let matrix = to_shadow_map_space(matrix.unwrap_or_else(Matrix4::identity));
+            let columns: &[f32; 16] = matrix.as_ref();
+            shadows_block.extend_from_slice(columns);
+        }
+
+        let mut cascade_far_distances = [0.0; 4];
+        cascade_far_distances[..CASCADE_COUNT].copy_from_slice(&self.cascade_far_distances);
+        shadows_block.extend_from_slice(&cascade_far_distances);
+
+        shadows_block.extend_from_slice(&[camera_position.x, camera_position.y, camera_position.z, 1.0]);
+        shadows_block.extend_from_slice(&[camera_forward.x, camera_forward.y, camera_forward.z, 0.0]);
+        shadows_block.extend_from_slice(&[1.0 / SHADOW_MAP_RESOLUTION as f32, DEPTH_BIAS, 0.0, 0.0]);
+
+        shadows_block
+    }
+}
+
+/// The distances from the camera where the cascades end, from the closest one. They are a blend of
+/// a uniform and a logarithmic distribution, the latter giving more resolution close to the camera.
+pub fn cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+    let mut splits = [far; CASCADE_COUNT];
+
+    for (index, split) in splits.iter_mut().enumerate() {
+        let ratio = (index + 1) as f32 / CASCADE_COUNT as f32;
+        let logarithmic_split = near * (far / near).powf(ratio);
+        let uniform_split = near + (far - near) * ratio;
+
+        *split = CASCADE_SPLIT_LAMBDA * logarithmic_split + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform_split;
+    }
+
+    splits
+}
+
+/// The world space corners of the frustum of a view projection matrix.
+pub fn frustum_corners(view_projection: Matrix4<f32>) -> [Point3<f32>; 8] {
+    let inverse = view_projection.invert().unwrap_or_else(Matrix4::identity);
+    let mut corners = [Point3::new(0.0, 0.0, 0.0); 8];
+
+    for (index, corner) in corners.iter_mut().enumerate() {
+        let x = if index & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if index & 2 == 0 { -1.0 } else { 1.0 };
+        let z = if index & 4 == 0 { -1.0 } else { 1.0 };
+        let point = inverse * Vector4::new(x, y, z, 1.0);
+
+        *corner = Point3::from_homogeneous(point);
+    }
+
+    corners
+}
+
+/// The view projection matrix of the shadow map of a directional light covering `corners`. The
+/// area is the bounding sphere of the corners and it moves by whole texels, so the shadows do
+/// not shimmer when the camera moves or turns.
+pub fn directional_shadow_matrix(
+    direction: Vector3<f32>,
+    corners: &[Point3<f32>; 8],
+    resolution: u32,
+) -> Matrix4<f32> {
+    let center = corners
+        .iter()
+        .fold(Vector3::zero(), |sum, corner| sum + corner.to_vec())
+        / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| corner.to_vec().distance(center))
+        .fold(0.0, f32::max)
+        .max(0.001);
+
+    let light_view = Matrix4::look_at_dir(Point3::new(0.0, 0.0, 0.0), direction.normalize(), any_up(direction));
+    let light_space_center = light_view.transform_point(Point3::from_vec(center));
+    let texel_size = 2.0 * radius / resolution as f32;
+    let x = (light_space_center.x / texel_size).floor() * texel_size;
+    let y = (light_space_center.y / texel_size).floor() * texel_size;
+    // The camera looks along -z in the light space.
+    let z = light_space_center.z;
+
+    ortho(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -z - radius - CASTER_DISTANCE,
+        -z + radius,
+    ) * light_view
+}
+
+/// The view projection matrix of the shadow map of a spot light, covering its outer cone.
+pub fn spot_shadow_matrix(
+    position: Point3<f32>,
+    direction: Vector3<f32>,
+    outer_angle: f32,
+    range: f32,
+) -> Matrix4<f32> {
+    let fov = (2.0 * outer_angle).clamp(1.0, 170.0);
+    let near = (range * 0.01).max(0.01);
+    let light_view = Matrix4::look_at_dir(position, direction.normalize(), any_up(direction));
+
+    perspective(Deg(fov), 1.0, near, range.max(near * 2.0)) * light_view
+}
+
+/// Map the clip space of a shadow matrix to the texture coordinates and depth of the shadow map.
+pub fn to_shadow_map_space(shadow_matrix: Matrix4<f32>) -> Matrix4<f32> {
+    Matrix4::from_translation(Vector3::new(0.5, 0.5, 0.5)) * Matrix4::from_scale(0.5) * shadow_matrix
+}
+
+/// An up vector which is not parallel to `direction`.
+fn any_up(direction: Vector3<f32>) -> Vector3<f32> {
+    let direction = direction.normalize();
+
+    if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cascade_splits, directional_shadow_matrix, frustum_corners, spot_shadow_matrix,
+        to_shadow_map_space, ShadowCaster, ShadowLayers, CASCADE_COUNT, MAX_SHADOW_DISTANCE,
+        SHADOWS_BLOCK_LENGTH, SHADOW_MAP_LAYERS,
+    };
+    use cgmath::prelude::*;
+    use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+    use components::camera::Camera;
+
+    fn project(matrix: Matrix4<f32>, point: Point3<f32>) -> Point3<f32> {
+        Point3::from_homogeneous(matrix * point.to_homogeneous())
+    }
+
+    fn is_in_clip_space(point: Point3<f32>) -> bool {
+        [point.x, point.y, point.z].iter().all(|value| value.abs() <= 1.0001)
+    }
+
+    #[test]
+    fn split_cascades_closer_to_the_camera() {
+        let splits = cascade_splits(0.1, 60.0);
+
+        assert_eq!(splits.len(), CASCADE_COUNT);
+        assert!((splits[CASCADE_COUNT - 1] - 60.0).abs() < 0.001);
+        assert!(splits[0] > 0.1 && splits[0] < 60.0 / CASCADE_COUNT as f32);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn compute_frustum_corners() {
+        let view = Matrix4::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+        let corners = frustum_corners(perspective(Deg(90.0), 1.0, 1.0, 10.0) * view);
+
+        assert!((corners[0] - Point3::new(-1.0, -1.0, -1.0)).magnitude() < 0.001);
+        assert!((corners[7] - Point3::new(10.0, 10.0, -10.0)).magnitude() < 0.001);
+    }
+
+    #[test]
+    fn cover_the_frustum_with_directional_shadows() {
+        let view = Matrix4::look_at(Point3::new(3.0, 2.0, 1.0), Point3::new(0.0, 0.0, -5.0), Vector3::unit_y());
+        let corners = frustum_corners(perspective(Deg(45.0), 1.5, 0.1, 20.0) * view);
+
+        for &direction in &[Vector3::new(-1.0, -2.0, 0.5), Vector3::new(0.0, -1.0, 0.0)] {
+            let shadow_matrix = directional_shadow_matrix(direction, &corners, 1024);
+
+            for corner in &corners {
+                assert!(is_in_clip_space(project(shadow_matrix, *corner)), "{:?} is not covered", corner);
+            }
+
+            // A caster between the light and the frustum.
+            let caster = corners[0] - direction.normalize() * 20.0;
+            assert!(is_in_clip_space(project(shadow_matrix, caster)));
+        }
+    }
+
+    #[test]
+    fn move_directional_shadows_by_whole_texels() {
+        let direction = Vector3::new(0.0, -1.0, -1.0);
+        let corners = frustum_corners(perspective(Deg(45.0), 1.0, 0.1, 10.0));
+        let moved_corners = {
+            let mut moved_corners = corners;
+            for corner in moved_corners.iter_mut() {
+                *corner += Vector3::new(0.001, 0.0, 0.0);
+            }
+            moved_corners
+        };
+
+        assert_eq!(
+            directional_shadow_matrix(direction, &corners, 1024),
+            directional_shadow_matrix(direction, &moved_corners, 1024),
+            "a tiny move should not change the shadow map"
+        );
+    }
+
+    #[test]
+    fn cover_the_cone_of_spot_lights() {
+        let position = Point3::new(0.0, 5.0, 0.0);
+        let shadow_matrix = spot_shadow_matrix(position, Vector3::new(0.0, -1.0, 0.0), 30.0, 10.0);
+
+        let center = project(shadow_matrix, Point3::new(0.0, 0.0, 0.0));
+        assert!(center.x.abs() < 0.001 && center.y.abs() < 0.001 && is_in_clip_space(center));
+
+        let inside_cone = project(shadow_matrix, Point3::new(2.0, 0.0, 0.0));
+        let outside_cone = project(shadow_matrix, Point3::new(4.0, 0.0, 0.0));
+        let out_of_range = project(shadow_matrix, Point3::new(0.0, -6.0, 0.0));
+        assert!(is_in_clip_space(inside_cone));
+        assert!(!is_in_clip_space(outside_cone));
+        assert!(!is_in_clip_space(out_of_range));
+    }
+
+    #[test]
+    fn map_clip_space_to_the_shadow_map() {
+        let corner = project(to_shadow_map_space(Matrix4::identity()), Point3::new(-1.0, 1.0, 0.0));
+
+        assert_eq!(corner, Point3::new(0.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn place_the_layers_of_the_casters() {
+        let casters = [
+            ShadowCaster::Directional {
+                direction: Vector3::new(-1.0, -2.0, 0.5),
+            },
+            ShadowCaster::Spot {
+                position: Point3::new(0.0, 5.0, 0.0),
+                direction: Vector3::new(0.0, -1.0, 0.0),
+                outer_angle: 30.0,
+                range: 10.0,
+                layer: CASCADE_COUNT + 1,
+            },
+        ];
+        let camera = Camera::new();
+        let view = Matrix4::look_at(Point3::new(0.0, 2.0, 10.0), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        let layers = ShadowLayers::new(&casters, &camera, view);
+
+        let used_layers: Vec<usize> = (0..SHADOW_MAP_LAYERS)
+            .filter(|&layer| layers.matrices[layer].is_some())
+            .collect();
+        assert_eq!(used_layers, vec![0, 1, 2, CASCADE_COUNT + 1]);
+        assert!((layers.cascade_far_distances[CASCADE_COUNT - 1] - MAX_SHADOW_DISTANCE).abs() < 0.001);
+
+        let shadows_block = layers.pack(Point3::new(0.0, 2.0, 10.0), Vector3::new(0.0, 0.0, -1.0));
+        let first_matrix = to_shadow_map_space(layers.matrices[0].unwrap());
+        let first_columns: &[f32; 16] = first_matrix.as_ref();
+        assert_eq!(shadows_block.len(), SHADOWS_BLOCK_LENGTH);
+        assert_eq!(&shadows_block[..16], &first_columns[..]);
+        assert_eq!(shadows_block[SHADOW_MAP_LAYERS * 16 + 2], layers.cascade_far_distances[2]);
+    }
+}
@@ -4,6 +4,7 @@ extern crate imgui;
 extern crate imgui_opengl_renderer;
 
 use self::glutin::GlContext;
+use gl_object::delete_dropped_gl_objects;
 use specs::{System, Write};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -27,6 +28,7 @@ impl<'a> System<'a> for AfterRender {
         let mut time = data;
 
         self.window.borrow().gl_window.swap_buffers().unwrap();
+        delete_dropped_gl_objects();
         time.frame_render_done();
     }
 }
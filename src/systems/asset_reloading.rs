@@ -0,0 +1,40 @@
+use asset_manager::AssetManager;
+use specs::{System, WriteExpect};
+use std::time::{Duration, Instant};
+
+/// How often the files of the assets are checked for changes.
+const POLL_INTERVAL_IN_MILLISECONDS: u64 = 500;
+
+/// Reload the shaders and `.vox` models edited while the game is running.
+pub struct AssetReloading {
+    last_poll_time: Instant,
+}
+
+impl Default for AssetReloading {
+    fn default() -> Self {
+        Self {
+            last_poll_time: Instant::now(),
+        }
+    }
+}
+
+impl AssetReloading {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for AssetReloading {
+    type SystemData = WriteExpect<'a, AssetManager>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let mut assets = data;
+
+        if self.last_poll_time.elapsed() < Duration::from_millis(POLL_INTERVAL_IN_MILLISECONDS) {
+            return;
+        }
+
+        self.last_poll_time = Instant::now();
+        assets.reload_changed_assets();
+    }
+}
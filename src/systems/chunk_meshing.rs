@@ -0,0 +1,165 @@
+use asset_manager::AssetManager;
+use components::mesh_render::MeshRender;
+use components::voxel_chunk::VoxelChunk;
+use components::world_chunk::WorldChunk;
+use specs::{Join, ReadStorage, System, WriteExpect, WriteStorage};
+use voxel::voxel_mesh_builder::MeshingMode;
+use voxel::world::VoxelWorld;
+
+/// Rebuild the meshes of the dirty chunks and upload them in place, so voxel edits show up on the
+/// next frame. Chunks without a `MeshRender` stay dirty until they get one. The chunks of the
+/// `VoxelWorld` are meshed along with their neighbours, with `MeshingMode::Greedy`.
+#[derive(Default, Debug)]
+pub struct ChunkMeshing;
+
+impl ChunkMeshing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for ChunkMeshing {
+    type SystemData = (
+        WriteExpect<'a, AssetManager>,
+        WriteExpect<'a, VoxelWorld>,
+        WriteStorage<'a, VoxelChunk>,
+        ReadStorage<'a, WorldChunk>,
+        ReadStorage<'a, MeshRender>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut assets,
+            mut voxel_world,
+            mut voxel_chunk_storage,
+            world_chunk_storage,
+            mesh_render_storage,
+        ) = data;
+
+        for (voxel_chunk, mesh_render) in (&mut voxel_chunk_storage, &mesh_render_storage).join() {
+            let meshing_mode = voxel_chunk.meshing_mode();
+
+            if let Some(mesh_data) = voxel_chunk.chunk.update_mesh_data(meshing_mode) {
+                if let Some(mesh) = assets.mesh_mut(mesh_render.mesh) {
+                    mesh.update(mesh_data);
+                }
+            }
+        }
+
+        for (world_chunk, mesh_render) in (&world_chunk_storage, &mesh_render_storage).join() {
+            let mesh_data = voxel_world.update_chunk_mesh(&world_chunk.position, MeshingMode::Greedy);
+
+            if let Some(mesh_data) = mesh_data {
+                if let Some(mesh) = assets.mesh_mut(mesh_render.mesh) {
+                    mesh.update(&mesh_data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkMeshing;
+    use asset_manager::AssetManager;
+    use components::mesh_render::MeshRender;
+    use components::voxel_chunk::VoxelChunk;
+    use components::world_chunk::WorldChunk;
+    use headless::HeadlessContext;
+    use material::Material;
+    use mesh::Mesh;
+    use mesh_data::MeshData;
+    use specs::{Builder, RunNow, World};
+    use voxel::chunk::Chunk;
+    use voxel::position::Position;
+    use voxel::world::{VoxelWorld, CHUNK_SIZE};
+
+    fn chunk_meshing_world() -> World {
+        let mut world = World::new();
+        world.register::<VoxelChunk>();
+        world.register::<WorldChunk>();
+        world.register::<MeshRender>();
+        world.add_resource(VoxelWorld::new());
+
+        world
+    }
+
+    /// Needs an OpenGL driver, see `HeadlessContext`.
+    #[test]
+    #[ignore]
+    fn update_the_mesh_of_edited_chunks() {
+        let _context = HeadlessContext::new(32, 32).unwrap();
+        let mut world = chunk_meshing_world();
+
+        let mut assets = AssetManager::new();
+        let material = Material::new(&mut assets).unwrap();
+        let mesh_render = MeshRender::new(material, assets.add_mesh(Mesh::new(MeshData::new(), Vec::default())));
+        let mesh_handle = mesh_render.mesh;
+        world.add_resource(assets);
+
+        let entity = world
+            .create_entity()
+            .with(VoxelChunk::new(Chunk::new(4, 4, 4)))
+            .with(mesh_render)
+            .build();
+
+        let mut chunk_meshing_system = ChunkMeshing::new();
+        chunk_meshing_system.run_now(&world.res);
+
+        assert!(world.read_resource::<AssetManager>().mesh(mesh_handle).unwrap().indices.is_empty());
+
+        for x in 0..4 {
+            world
+                .write_storage::<VoxelChunk>()
+                .get_mut(entity)
+                .unwrap()
+                .chunk
+                .set_voxel(x, 0, x, 1)
+                .unwrap();
+
+            chunk_meshing_system.run_now(&world.res);
+
+            let assets = world.read_resource::<AssetManager>();
+
+            assert_eq!(assets.mesh(mesh_handle).unwrap().indices.len(), 36 * (x as usize + 1));
+        }
+
+        assert!(!world.read_storage::<VoxelChunk>().get(entity).unwrap().chunk.is_dirty());
+    }
+
+    /// Needs an OpenGL driver, see `HeadlessContext`.
+    #[test]
+    #[ignore]
+    fn update_the_mesh_of_edited_world_chunks() {
+        let _context = HeadlessContext::new(32, 32).unwrap();
+        let mut world = chunk_meshing_world();
+        let size = i64::from(CHUNK_SIZE);
+
+        world.write_resource::<VoxelWorld>().set_voxel(&Position::new(size - 1, 0, 0), 1);
+
+        let mut assets = AssetManager::new();
+        let material = Material::new(&mut assets).unwrap();
+        let mesh_render = MeshRender::new(material, assets.add_mesh(Mesh::new(MeshData::new(), Vec::default())));
+        let mesh_handle = mesh_render.mesh;
+        world.add_resource(assets);
+
+        world
+            .create_entity()
+            .with(WorldChunk::new(Position::new(0, 0, 0)))
+            .with(mesh_render)
+            .build();
+
+        let mut chunk_meshing_system = ChunkMeshing::new();
+        chunk_meshing_system.run_now(&world.res);
+
+        assert_eq!(world.read_resource::<AssetManager>().mesh(mesh_handle).unwrap().vertices.len(), 6 * 4);
+
+        // The edited voxel is in the neighbouring chunk, which has no entity.
+        world.write_resource::<VoxelWorld>().set_voxel(&Position::new(size, 0, 0), 1);
+        chunk_meshing_system.run_now(&world.res);
+
+        let assets = world.read_resource::<AssetManager>();
+
+        assert_eq!(assets.mesh(mesh_handle).unwrap().vertices.len(), 5 * 4, "the east face should be culled");
+    }
+}
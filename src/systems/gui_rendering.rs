@@ -6,20 +6,38 @@ extern crate imgui_opengl_renderer;
 use self::glutin::GlContext;
 use self::imgui::*;
 use self::imgui_opengl_renderer::Renderer;
-use cgmath::Vector3;
+use asset_manager::AssetManager;
+use cgmath::{Point3, Vector3};
+use components::camera::Camera;
+use components::light::Light;
+use components::mesh_render::MeshRender;
+use components::mesh_source::MeshSource;
+use components::parent::Parent;
+use components::player::Player;
 use components::transform::Transform;
+use components::voxel_chunk::VoxelChunk;
+use components::world_chunk::WorldChunk;
+use gl_object::GlObjectCounts;
 use input::Input;
-use specs::{Join, Read, ReadStorage, System};
+use resources::active_camera::ActiveCamera;
+use resources::render_stats::RenderStats;
+use resources::rotating_entity::RotatingEntity;
+use scene_tree::SceneTree;
+use specs::{Entities, Entity, Read, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 use std::cell::RefCell;
 use std::rc::Rc;
+use systems::hierarchy_panel::{apply_hierarchy_action, draw_hierarchy, HierarchyStorages};
 use time::Time;
 use window::Window;
 
+/// The maximum length of an entity name edited in the inspector.
+const NAME_CAPACITY: usize = 64;
+
 pub struct GuiRendering {
     window: Rc<RefCell<Window>>,
     ui_renderer: Renderer,
     imgui: ImGui,
-    selected_entity_index: i32,
+    selected_entity: Option<Entity>,
 }
 
 impl GuiRendering {
@@ -40,16 +58,59 @@ impl GuiRendering {
             window,
             ui_renderer,
             imgui,
-            selected_entity_index: -1,
+            selected_entity: None,
         }
     }
 }
 
 impl<'a> System<'a> for GuiRendering {
-    type SystemData = (Read<'a, Time>, Read<'a, Input>, ReadStorage<'a, Transform>);
+    type SystemData = (
+        Read<'a, Time>,
+        Read<'a, Input>,
+        Read<'a, RenderStats>,
+        Entities<'a>,
+        ReadExpect<'a, SceneTree>,
+        Option<Read<'a, ActiveCamera>>,
+        Option<Read<'a, RotatingEntity>>,
+        WriteExpect<'a, AssetManager>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, MeshRender>,
+        WriteStorage<'a, MeshSource>,
+        WriteStorage<'a, Camera>,
+        WriteStorage<'a, Player>,
+        WriteStorage<'a, Light>,
+        WriteStorage<'a, VoxelChunk>,
+        ReadStorage<'a, WorldChunk>,
+    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (time, input, tranform_storage) = data;
+        let (
+            time,
+            input,
+            render_stats,
+            entities,
+            scene_tree,
+            active_camera,
+            rotating_entity,
+            mut assets,
+            mut tranform_storage,
+            mut parent_storage,
+            mut mesh_render_storage,
+            mut mesh_source_storage,
+            mut camera_storage,
+            mut player_storage,
+            mut light_storage,
+            mut voxel_chunk_storage,
+            world_chunk_storage,
+        ) = data;
+
+        // The selected entity may have been deleted.
+        if let Some(entity) = self.selected_entity {
+            if !entities.is_alive(entity) || tranform_storage.get(entity).is_none() {
+                self.selected_entity = None;
+            }
+        }
 
         let delta_time_in_seconds = time.get_delta_time_in_seconds();
         let average_delta_time_in_seconds = time.get_average_delta_time_in_seconds();
@@ -88,19 +149,43 @@ impl<'a> System<'a> for GuiRendering {
         //         ));
         //     });
 
-        let selected_entity_index = &mut self.selected_entity_index;
-        ui.window(im_str!("Inspector"))
-            .size((370.0, 130.0), ImGuiCond::FirstUseEver)
-            .build(|| {
-                let mut tranform_names: Vec<ImString> = Vec::new();
+        let selected_entity = self.selected_entity;
+        let mut hierarchy_actions = Vec::new();
+        // The entities pointed to by resources cannot be deleted.
+        let mut protected_entities = Vec::new();
+        protected_entities.extend(active_camera.map(|active_camera| active_camera.0));
+        protected_entities.extend(rotating_entity.map(|rotating_entity| rotating_entity.0));
+        let reload_errors = assets.reload_errors();
 
-                for (transform,) in (&tranform_storage,).join() {
-                    tranform_names.push(ImString::new(transform.name));
-                }
+        // The previous version of the assets is used until they are fixed.
+        if !reload_errors.is_empty() {
+            ui.window(im_str!("Asset errors"))
+                .size((500.0, 200.0), ImGuiCond::FirstUseEver)
+                .position((270.0, 10.0), ImGuiCond::FirstUseEver)
+                .build(|| {
+                    for reload_error in &reload_errors {
+                        ui.text_colored((1.0, 0.4, 0.4, 1.0), im_str!("{}", reload_error));
+                        ui.separator();
+                    }
+                });
+        }
 
-                let tranform_names: Vec<&ImStr> =
-                    tranform_names.iter().map(|s| s.as_ref()).collect();
+        ui.window(im_str!("Hierarchy"))
+            .size((250.0, 400.0), ImGuiCond::FirstUseEver)
+            .position((10.0, 10.0), ImGuiCond::FirstUseEver)
+            .build(|| {
+                hierarchy_actions = draw_hierarchy(
+                    &ui,
+                    &scene_tree,
+                    &tranform_storage,
+                    selected_entity,
+                    &protected_entities,
+                );
+            });
 
+        ui.window(im_str!("Inspector"))
+            .size((370.0, 220.0), ImGuiCond::FirstUseEver)
+            .build(|| {
                 let average_idle_time = delta_time_in_seconds - average_render_time_in_seconds;
                 let average_idle_time_percent =
                     (delta_time_in_seconds - average_render_time_in_seconds) * 100.0
@@ -113,22 +198,60 @@ impl<'a> System<'a> for GuiRendering {
                     average_idle_time_percent,
                     average_idle_time * 1000.0,
                 ));
+
+                let gl_object_counts = GlObjectCounts::live();
+                ui.text(im_str!(
+                    "GL objects: {} vertex arrays, {} buffers, {} textures, {} programs, {} framebuffers",
+                    gl_object_counts.vertex_arrays,
+                    gl_object_counts.buffers,
+                    gl_object_counts.textures,
+                    gl_object_counts.programs,
+                    gl_object_counts.framebuffers,
+                ));
+                ui.text(im_str!(
+                    "Meshes: {} drawn in {} batches, {} culled",
+                    render_stats.drawn_meshes,
+                    render_stats.batches,
+                    render_stats.culled_meshes,
+                ));
                 ui.separator();
 
-                ui.combo(
-                    im_str!("Entity"),
-                    selected_entity_index,
-                    &tranform_names[..],
-                    -1,
-                );
+                if let Some(entity) = selected_entity {
+                    let parent_name = parent_storage
+                        .get(entity)
+                        .and_then(|parent| tranform_storage.get(parent.entity))
+                        .map_or_else(|| "None".to_string(), |parent| parent.name.clone());
+
+                    let mut component_names = vec!["Transform"];
+                    if parent_storage.get(entity).is_some() {
+                        component_names.push("Parent");
+                    }
+                    if mesh_render_storage.get(entity).is_some() {
+                        component_names.push("MeshRender");
+                    }
+                    if mesh_source_storage.get(entity).is_some() {
+                        component_names.push("MeshSource");
+                    }
+                    if camera_storage.get(entity).is_some() {
+                        component_names.push("Camera");
+                    }
+                    if player_storage.get(entity).is_some() {
+                        component_names.push("Player");
+                    }
+                    if light_storage.get(entity).is_some() {
+                        component_names.push("Light");
+                    }
+                    if voxel_chunk_storage.get(entity).is_some() {
+                        component_names.push("VoxelChunk");
+                    }
+                    if world_chunk_storage.get(entity).is_some() {
+                        component_names.push("WorldChunk");
+                    }
 
-                if *selected_entity_index != -1 {
-                    let selected_transform = (&tranform_storage,)
-                        .join()
-                        .nth(*selected_entity_index as usize)
-                        .unwrap()
-                        .0;
+                    let selected_transform = tranform_storage.get_mut(entity).unwrap();
 
+                    let mut name = ImString::with_capacity(NAME_CAPACITY);
+                    name.push_str(&selected_transform.name);
                     let mut position: [f32; 3] = [
                         selected_transform.local_position.x,
                         selected_transform.local_position.y,
@@ -137,13 +260,66 @@ impl<'a> System<'a> for GuiRendering {
                     let rotation_vector: Vector3<f32> = selected_transform.to_euler_angles();
                     let mut rotation: [f32; 3] =
                         [rotation_vector.x, rotation_vector.y, rotation_vector.z];
+                    let mut scale: [f32; 3] = [
+                        selected_transform.local_scale.x,
+                        selected_transform.local_scale.y,
+                        selected_transform.local_scale.z,
+                    ];
+
+                    if ui.input_text(im_str!("Name"), &mut name).build() {
+                        selected_transform.name = name.to_str().to_string();
+                    }
+
+                    ui.text(im_str!("Parent: {}", parent_name));
+
+                    if ui.input_float3(im_str!("Position"), &mut position).build() {
+                        selected_transform.set_local_position(Point3::from(position));
+                    }
 
-                    ui.input_float3(im_str!("Position"), &mut position).build();
-                    ui.input_float3(im_str!("Rotation"), &mut rotation).build();
+                    if ui.input_float3(im_str!("Rotation"), &mut rotation).build() {
+                        selected_transform.set_rotation(rotation[0], rotation[1], rotation[2]);
+                    }
+
+                    if ui.input_float3(im_str!("Scale"), &mut scale).build() {
+                        selected_transform.set_local_scale(Vector3::from(scale));
+                    }
+
+                    if let Some(mesh_render) = mesh_render_storage.get_mut(entity) {
+                        ui.checkbox(im_str!("Casts shadows"), &mut mesh_render.casts_shadows);
+                        ui.checkbox(im_str!("Receives shadows"), &mut mesh_render.receives_shadows);
+                    }
+
+                    ui.separator();
+                    ui.text(im_str!("Components: {}", component_names.join(", ")));
+                } else {
+                    ui.text(im_str!("Select an entity in the hierarchy."));
                 }
             });
 
         self.ui_renderer.render(ui);
+
+        let mut storages = HierarchyStorages {
+            entities: &entities,
+            transforms: &mut tranform_storage,
+            parents: &mut parent_storage,
+            mesh_renders: &mut mesh_render_storage,
+            mesh_sources: &mut mesh_source_storage,
+            cameras: &mut camera_storage,
+            players: &mut player_storage,
+            lights: &mut light_storage,
+            voxel_chunks: &mut voxel_chunk_storage,
+            assets: &mut assets,
+        };
+
+        for action in hierarchy_actions {
+            self.selected_entity = apply_hierarchy_action(
+                action,
+                &scene_tree,
+                &mut storages,
+                &protected_entities,
+                self.selected_entity,
+            );
+        }
     }
 }
 
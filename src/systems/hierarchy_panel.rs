@@ -0,0 +1,566 @@
+extern crate imgui;
+
+use self::imgui::sys::{self, ImGuiDragDropFlags};
+use self::imgui::*;
+use asset_manager::AssetManager;
+use components::camera::Camera;
+use components::light::Light;
+use components::mesh_render::MeshRender;
+use components::mesh_source::MeshSource;
+use components::parent::Parent;
+use components::player::Player;
+use components::transform::Transform;
+use components::voxel_chunk::VoxelChunk;
+use cgmath::Point3;
+use mesh::Mesh;
+use mesh_data::MeshData;
+use scene_tree::SceneTree;
+use specs::{Entities, Entity, WriteStorage};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::mem::size_of;
+use std::os::raw::c_void;
+
+const ENTITY_PAYLOAD_TYPE: &str = "ENTITY";
+
+/// What the user asked for in the hierarchy panel. Actions are applied once the panel is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyAction {
+    Select(Entity),
+    /// Move the entity under another one, or to the root when there is no parent.
+    Reparent { entity: Entity, parent: Option<Entity> },
+    Create,
+    Delete(Entity),
+    Duplicate(Entity),
+}
+
+/// The storages edited by the hierarchy actions.
+pub struct HierarchyStorages<'a, 'b: 'a> {
+    pub entities: &'a Entities<'b>,
+    pub transforms: &'a mut WriteStorage<'b, Transform>,
+    pub parents: &'a mut WriteStorage<'b, Parent>,
+    pub mesh_renders: &'a mut WriteStorage<'b, MeshRender>,
+    pub mesh_sources: &'a mut WriteStorage<'b, MeshSource>,
+    pub cameras: &'a mut WriteStorage<'b, Camera>,
+    pub players: &'a mut WriteStorage<'b, Player>,
+    pub lights: &'a mut WriteStorage<'b, Light>,
+    pub voxel_chunks: &'a mut WriteStorage<'b, VoxelChunk>,
+    pub assets: &'a mut AssetManager,
+}
+
+/// Draw the entities of the scene tree as a tree view and return the actions of the user.
+/// Entities can be dragged onto another one to change their parent, or onto the "Drop here to
+/// move to the root" text to remove it. The protected entities, such as the active camera, and their
+/// ancestors cannot be deleted, as resources still point to them.
+pub fn draw_hierarchy(
+    ui: &Ui,
+    scene_tree: &SceneTree,
+    transforms: &WriteStorage<Transform>,
+    selected_entity: Option<Entity>,
+    protected_entities: &[Entity],
+) -> Vec<HierarchyAction> {
+    let mut actions = Vec::new();
+
+    if ui.small_button(im_str!("Create")) {
+        actions.push(HierarchyAction::Create);
+    }
+
+    if let Some(entity) = selected_entity {
+        ui.same_line(0.0);
+        if ui.small_button(im_str!("Duplicate")) {
+            actions.push(HierarchyAction::Duplicate(entity));
+        }
+
+        if !holds_protected_entity(scene_tree, entity, protected_entities) {
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Delete")) {
+                actions.push(HierarchyAction::Delete(entity));
+            }
+        }
+    }
+
+    ui.separator();
+
+    let root_entities: Vec<Entity> = match scene_tree.root_entities() {
+        Some(root_entities) => root_entities.collect(),
+        None => Vec::new(),
+    };
+    let payload_type = CString::new(ENTITY_PAYLOAD_TYPE).unwrap();
+    let label_format = CString::new("%s").unwrap();
+
+    for root_entity in root_entities {
+        // The number of tree nodes currently open, nodes deeper than that are collapsed.
+        let mut open_depth: u32 = 0;
+
+        for (entity, depth) in scene_tree.descendant_entities_with_depth(&root_entity) {
+            if depth > open_depth {
+                continue;
+            }
+
+            while open_depth > depth {
+                unsafe { sys::igTreePop() };
+                open_depth -= 1;
+            }
+
+            let name = transforms
+                .get(entity)
+                .map_or_else(String::new, |transform| transform.name.clone());
+            let label = CString::new(name.replace('\0', "")).unwrap();
+            let has_children = scene_tree.has_children(&entity);
+
+            let mut flags = ImGuiTreeNodeFlags::OpenOnArrow | ImGuiTreeNodeFlags::OpenOnDoubleClick;
+            if !has_children {
+                flags |= ImGuiTreeNodeFlags::Leaf | ImGuiTreeNodeFlags::NoTreePushOnOpen;
+            }
+            if selected_entity == Some(entity) {
+                flags |= ImGuiTreeNodeFlags::Selected;
+            }
+
+            unsafe {
+                let is_open = sys::igTreeNodeExPtr(
+                    entity.id() as usize as *const c_void,
+                    flags,
+                    label_format.as_ptr(),
+                    label.as_ptr(),
+                );
+
+                if sys::igIsItemClicked(0) {
+                    actions.push(HierarchyAction::Select(entity));
+                }
+
+                // Drag with the left mouse button.
+                if sys::igBeginDragDropSource(ImGuiDragDropFlags::empty(), 0) {
+                    sys::igSetDragDropPayload(
+                        payload_type.as_ptr(),
+                        &entity as *const Entity as *const c_void,
+                        size_of::<Entity>(),
+                        ImGuiCond::empty(),
+                    );
+                    sys::igText(label_format.as_ptr(), label.as_ptr());
+                    sys::igEndDragDropSource();
+                }
+
+                if let Some(dropped_entity) = accept_entity_payload(&payload_type) {
+                    actions.push(HierarchyAction::Reparent {
+                        entity: dropped_entity,
+                        parent: Some(entity),
+                    });
+                }
+
+                if is_open && has_children {
+                    open_depth += 1;
+                }
+            }
+        }
+
+        while open_depth > 0 {
+            unsafe { sys::igTreePop() };
+            open_depth -= 1;
+        }
+    }
+
+    ui.separator();
+    ui.text_disabled(im_str!("Drop here to move to the root"));
+
+    if let Some(dropped_entity) = accept_entity_payload(&payload_type) {
+        actions.push(HierarchyAction::Reparent {
+            entity: dropped_entity,
+            parent: None,
+        });
+    }
+
+    actions
+}
+
+/// Make the last item a drop target and return the entity dropped on it.
+fn accept_entity_payload(payload_type: &CString) -> Option<Entity> {
+    unsafe {
+        if !sys::igBeginDragDropTarget() {
+            return None;
+        }
+
+        let payload = sys::igAcceptDragDropPayload(payload_type.as_ptr(), ImGuiDragDropFlags::empty());
+        let entity = if payload.is_null() || (*payload).data.is_null() {
+            None
+        } else {
+            Some(*((*payload).data as *const Entity))
+        };
+
+        sys::igEndDragDropTarget();
+        entity
+    }
+}
+
+/// Return true if `entity` is one of the protected entities or one of their ancestors.
+fn holds_protected_entity(scene_tree: &SceneTree, entity: Entity, protected_entities: &[Entity]) -> bool {
+    protected_entities
+        .iter()
+        .any(|protected_entity| scene_tree.is_ancestor_of(&entity, protected_entity))
+}
+
+/// Apply an action and return the entity to select afterward. The entities holding a protected
+/// entity are not deleted.
+pub fn apply_hierarchy_action(
+    action: HierarchyAction,
+    scene_tree: &SceneTree,
+    storages: &mut HierarchyStorages,
+    protected_entities: &[Entity],
+    selected_entity: Option<Entity>,
+) -> Option<Entity> {
+    match action {
+        HierarchyAction::Select(entity) => Some(entity),
+        HierarchyAction::Reparent { entity, parent } => {
+            match parent {
+                Some(parent) => {
+                    if scene_tree.is_ancestor_of(&entity, &parent) {
+                        warn!("An entity cannot be moved under itself or one of its descendants.");
+                        return selected_entity;
+                    }
+
+                    storages
+                        .parents
+                        .insert(entity, Parent { entity: parent })
+                        .expect("the entity should be alive");
+                }
+                None => {
+                    storages.parents.remove(entity);
+                }
+            }
+
+            if let Some(transform) = storages.transforms.get_mut(entity) {
+                transform.is_dirty = true;
+            }
+
+            selected_entity
+        }
+        HierarchyAction::Create => {
+            let entity = storages.entities.create();
+
+            storages
+                .transforms
+                .insert(entity, Transform::new(Point3::new(0.0, 0.0, 0.0), "Entity"))
+                .expect("the entity should be alive");
+
+            Some(entity)
+        }
+        HierarchyAction::Delete(entity) => {
+            if holds_protected_entity(scene_tree, entity, protected_entities) {
+                warn!("An entity pointed to by a resource, or one of its ancestors, cannot be deleted.");
+                return selected_entity;
+            }
+
+            // The descendants are deleted as well, so no entity is left with a dead parent.
+            for descendant in subtree_entities(scene_tree, entity) {
+                if let Err(error) = storages.entities.delete(descendant) {
+                    warn!("Cannot delete an entity: {:?}", error);
+                }
+            }
+
+            None
+        }
+        HierarchyAction::Duplicate(entity) => {
+            let mut copies: HashMap<Entity, Entity> = HashMap::new();
+            // Parents appear before their descendants, so their copy already exists.
+            for original in subtree_entities(scene_tree, entity) {
+                let copy = storages.entities.create();
+                copies.insert(original, copy);
+
+                duplicate_components(original, copy, storages);
+
+                let parent = storages.parents.get(original).map(|parent| parent.entity);
+                if let Some(parent) = parent {
+                    let parent = copies.get(&parent).cloned().unwrap_or(parent);
+
+                    storages
+                        .parents
+                        .insert(copy, Parent { entity: parent })
+                        .expect("the copy should be alive");
+                }
+            }
+
+            copies.get(&entity).cloned()
+        }
+    }
+}
+
+/// The entity followed by its descendants. Entities created during this frame are not in the
+/// scene tree yet and have no descendants.
+fn subtree_entities(scene_tree: &SceneTree, entity: Entity) -> Vec<Entity> {
+    if scene_tree.contains_entity(&entity) {
+        scene_tree.descendant_entities(&entity).collect()
+    } else {
+        vec![entity]
+    }
+}
+
+fn duplicate_components(original: Entity, copy: Entity, storages: &mut HierarchyStorages) {
+    if let Some(transform) = storages.transforms.get(original).cloned() {
+        storages.transforms.insert(copy, transform).expect("the copy should be alive");
+    }
+
+    if let Some(mesh_render) = storages.mesh_renders.get(original).cloned() {
+        storages.mesh_renders.insert(copy, mesh_render).expect("the copy should be alive");
+    }
+
+    if let Some(mesh_source) = storages.mesh_sources.get(original).cloned() {
+        storages.mesh_sources.insert(copy, mesh_source).expect("the copy should be alive");
+    }
+
+    if let Some(camera) = storages.cameras.get(original).cloned() {
+        storages.cameras.insert(copy, camera).expect("the copy should be alive");
+    }
+
+    if storages.players.get(original).is_some() {
+        storages.players.insert(copy, Player).expect("the copy should be alive");
+    }
+
+    if let Some(light) = storages.lights.get(original).cloned() {
+        storages.lights.insert(copy, light).expect("the copy should be alive");
+    }
+
+    if let Some(mut voxel_chunk) = storages.voxel_chunks.get(original).cloned() {
+        // The copy gets its own mesh, built by `ChunkMeshing`, so editing one of the chunks does
+        // not change the other.
+        voxel_chunk.chunk.mark_dirty();
+
+        if let Some(mesh_render) = storages.mesh_renders.get_mut(copy) {
+            mesh_render.mesh = storages
+                .assets
+                .add_mesh(Mesh::new(MeshData::new(), Vec::default()));
+        }
+
+        storages.voxel_chunks.insert(copy, voxel_chunk).expect("the copy should be alive");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_hierarchy_action, HierarchyAction, HierarchyStorages};
+    use asset_manager::AssetManager;
+    use cgmath::Point3;
+    use components::camera::Camera;
+    use components::light::Light;
+    use components::mesh_render::MeshRender;
+    use components::mesh_source::MeshSource;
+    use components::parent::Parent;
+    use components::player::Player;
+    use components::transform::Transform;
+    use components::voxel_chunk::VoxelChunk;
+    use scene::scene_world;
+    use scene_tree::SceneTree;
+    use specs::{Builder, Entity, Join, RunNow, World};
+    use systems::transformation::Transformation;
+
+    /// A world with a rig holding the camera, and a parent with a child and a grandchild.
+    struct TestScene {
+        world: World,
+        transformation_system: Transformation,
+        scene_root_entity: Entity,
+        rig: Entity,
+        camera: Entity,
+        parent: Entity,
+        child: Entity,
+        grandchild: Entity,
+    }
+
+    impl TestScene {
+        fn new() -> Self {
+            let mut world = scene_world();
+            world.add_resource(AssetManager::new());
+
+            let scene_root_entity = world.create_entity().build();
+            let rig = create_entity(&mut world, "Rig", None);
+            let camera = world
+                .create_entity()
+                .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Camera"))
+                .with(Parent { entity: rig })
+                .with(Camera::new())
+                .build();
+            let parent = create_entity(&mut world, "Parent", None);
+            let child = create_entity(&mut world, "Child", Some(parent));
+            let grandchild = create_entity(&mut world, "Grandchild", Some(child));
+
+            let mut transformation_system = Transformation::new(scene_root_entity);
+            transformation_system.setup(&mut world.res);
+            transformation_system.run_now(&world.res);
+
+            Self {
+                world,
+                transformation_system,
+                scene_root_entity,
+                rig,
+                camera,
+                parent,
+                child,
+                grandchild,
+            }
+        }
+
+        /// Apply the action like `GuiRendering` does, then update the scene tree.
+        fn apply(&mut self, action: HierarchyAction, protected_entities: &[Entity]) -> Option<Entity> {
+            let selected_entity = {
+                let scene_tree = self.world.read_resource::<SceneTree>();
+                let entities = self.world.entities();
+                let mut transforms = self.world.write_storage::<Transform>();
+                let mut parents = self.world.write_storage::<Parent>();
+                let mut mesh_renders = self.world.write_storage::<MeshRender>();
+                let mut mesh_sources = self.world.write_storage::<MeshSource>();
+                let mut cameras = self.world.write_storage::<Camera>();
+                let mut players = self.world.write_storage::<Player>();
+                let mut lights = self.world.write_storage::<Light>();
+                let mut voxel_chunks = self.world.write_storage::<VoxelChunk>();
+                let mut assets = self.world.write_resource::<AssetManager>();
+
+                let mut storages = HierarchyStorages {
+                    entities: &entities,
+                    transforms: &mut transforms,
+                    parents: &mut parents,
+                    mesh_renders: &mut mesh_renders,
+                    mesh_sources: &mut mesh_sources,
+                    cameras: &mut cameras,
+                    players: &mut players,
+                    lights: &mut lights,
+                    voxel_chunks: &mut voxel_chunks,
+                    assets: &mut assets,
+                };
+
+                apply_hierarchy_action(action, &scene_tree, &mut storages, protected_entities, None)
+            };
+
+            self.world.maintain();
+            self.transformation_system.run_now(&self.world.res);
+            self.world.maintain();
+
+            selected_entity
+        }
+
+        fn parent_of(&self, entity: Entity) -> Option<Entity> {
+            self.world.read_storage::<Parent>().get(entity).map(|parent| parent.entity)
+        }
+
+        fn name_of(&self, entity: Entity) -> String {
+            self.world.read_storage::<Transform>().get(entity).unwrap().name.clone()
+        }
+
+        /// The entities whose parent is `parent`.
+        fn children_of(&self, parent: Entity) -> Vec<Entity> {
+            let entities = self.world.entities();
+            let parent_storage = self.world.read_storage::<Parent>();
+            let children: Vec<Entity> = (&*entities, &parent_storage)
+                .join()
+                .filter(|(_, entity_parent)| entity_parent.entity == parent)
+                .map(|(entity, _)| entity)
+                .collect();
+
+            children
+        }
+    }
+
+    fn create_entity(world: &mut World, name: &str, parent: Option<Entity>) -> Entity {
+        let mut entity_builder = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), name));
+
+        if let Some(parent) = parent {
+            entity_builder = entity_builder.with(Parent { entity: parent });
+        }
+
+        entity_builder.build()
+    }
+
+    #[test]
+    fn reparent_entities() {
+        let mut scene = TestScene::new();
+        let (rig, parent, child, grandchild) = (scene.rig, scene.parent, scene.child, scene.grandchild);
+
+        scene.apply(HierarchyAction::Reparent { entity: child, parent: Some(rig) }, &[]);
+
+        assert_eq!(scene.parent_of(child), Some(rig));
+        assert_eq!(scene.world.read_resource::<SceneTree>().get_parent_entity(&child), Some(&rig));
+        assert_eq!(scene.parent_of(grandchild), Some(child));
+
+        scene.apply(HierarchyAction::Reparent { entity: child, parent: None }, &[]);
+
+        assert_eq!(scene.parent_of(child), None);
+
+        scene.apply(HierarchyAction::Reparent { entity: child, parent: Some(grandchild) }, &[]);
+
+        assert_eq!(scene.parent_of(child), None, "an entity cannot be moved under its descendant");
+        assert_eq!(scene.parent_of(parent), None);
+    }
+
+    #[test]
+    fn delete_entities_with_their_descendants() {
+        let mut scene = TestScene::new();
+        let (parent, child, grandchild) = (scene.parent, scene.child, scene.grandchild);
+
+        assert_eq!(scene.apply(HierarchyAction::Delete(child), &[scene.camera]), None);
+
+        assert!(scene.world.is_alive(parent));
+        assert!(!scene.world.is_alive(child));
+        assert!(!scene.world.is_alive(grandchild));
+        assert!(!scene.world.read_resource::<SceneTree>().contains_entity(&grandchild));
+    }
+
+    #[test]
+    fn keep_protected_entities() {
+        let mut scene = TestScene::new();
+        let (rig, camera, parent, child) = (scene.rig, scene.camera, scene.parent, scene.child);
+        // The camera is the active camera and the child is the rotating entity.
+        let protected_entities = [camera, child];
+
+        scene.apply(HierarchyAction::Delete(rig), &protected_entities);
+        scene.apply(HierarchyAction::Delete(camera), &protected_entities);
+        scene.apply(HierarchyAction::Delete(parent), &protected_entities);
+        scene.apply(HierarchyAction::Delete(child), &protected_entities);
+
+        for entity in &[rig, camera, parent, child] {
+            assert!(scene.world.is_alive(*entity), "{} should not be deleted", scene.name_of(*entity));
+        }
+
+        assert_eq!(scene.parent_of(camera), Some(rig));
+        assert!(scene.world.read_storage::<Camera>().get(camera).is_some());
+    }
+
+    #[test]
+    fn duplicate_entities_with_their_descendants() {
+        let mut scene = TestScene::new();
+        let (parent, child, grandchild) = (scene.parent, scene.child, scene.grandchild);
+
+        let parent_copy = scene.apply(HierarchyAction::Duplicate(parent), &[]).unwrap();
+
+        assert_ne!(parent_copy, parent);
+        assert_eq!(scene.name_of(parent_copy), "Parent");
+        assert_eq!(scene.parent_of(parent_copy), None);
+
+        let child_copies = scene.children_of(parent_copy);
+        assert_eq!(child_copies.len(), 1);
+        assert_ne!(child_copies[0], child);
+        assert_eq!(scene.name_of(child_copies[0]), "Child");
+
+        let grandchild_copies = scene.children_of(child_copies[0]);
+        assert_eq!(grandchild_copies.len(), 1);
+        assert_ne!(grandchild_copies[0], grandchild);
+        assert_eq!(scene.name_of(grandchild_copies[0]), "Grandchild");
+
+        assert_eq!(scene.children_of(parent), vec![child], "the original should be left as is");
+
+        let child_copy = scene.apply(HierarchyAction::Duplicate(child), &[]).unwrap();
+
+        assert_eq!(scene.parent_of(child_copy), Some(parent), "the copy should keep the parent");
+    }
+
+    #[test]
+    fn create_entities_at_the_root() {
+        let mut scene = TestScene::new();
+
+        let entity = scene.apply(HierarchyAction::Create, &[]).unwrap();
+
+        assert_eq!(scene.name_of(entity), "Entity");
+        assert_eq!(scene.parent_of(entity), None);
+        assert_eq!(
+            scene.world.read_resource::<SceneTree>().get_parent_entity(&entity),
+            Some(&scene.scene_root_entity)
+        );
+    }
+}
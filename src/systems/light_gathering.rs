@@ -0,0 +1,273 @@
+use cgmath::{Angle, Deg, InnerSpace, MetricSpace, Point3, Vector3};
+use components::light::{Light, LightKind};
+use components::transform::Transform;
+use resources::active_camera::ActiveCamera;
+use resources::lighting::Lighting;
+use resources::shadow_casters::ShadowCasters;
+use shader::LIGHTS_BLOCK_BINDING;
+use shadows::{ShadowCaster, CASCADE_COUNT, MAX_SHADOWED_SPOT_LIGHTS};
+use specs::{Join, Read, ReadStorage, System, Write};
+use std::cmp::Ordering;
+use uniform_buffer::UniformBuffer;
+
+/// The maximum number of lights of a frame, given to the shaders as `MAX_LIGHTS`.
+pub const MAX_LIGHTS: usize = 16;
+
+/// The number of floats of the `Lights` uniform block of `lighting.glsl`, in the std140 layout.
+const LIGHTS_BLOCK_LENGTH: usize = 4 + MAX_LIGHTS * LIGHT_LENGTH;
+const LIGHT_LENGTH: usize = 16;
+
+const POINT_LIGHT: f32 = 0.0;
+const DIRECTIONAL_LIGHT: f32 = 1.0;
+const SPOT_LIGHT: f32 = 2.0;
+/// The shadow map layer of the lights without shadows.
+const NO_SHADOW_LAYER: f32 = -1.0;
+
+/// Upload the ambient color of the `Lighting` resource and the `Light` components to the `Lights`
+/// uniform block shared by the shaders. When there are more than `MAX_LIGHTS` lights, the
+/// directional ones are kept first, then the ones closest to the active camera.
+///
+/// The first directional light casting shadows gets cascaded shadows, and the closest
+/// `MAX_SHADOWED_SPOT_LIGHTS` spot lights casting shadows get a shadow map. They are written to the
+/// `ShadowCasters` resource for the `Render` system.
+#[derive(Default, Debug)]
+pub struct LightGathering {
+    uniform_buffer: Option<UniformBuffer>,
+}
+
+impl LightGathering {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for LightGathering {
+    type SystemData = (
+        Option<Read<'a, ActiveCamera>>,
+        Read<'a, Lighting>,
+        Write<'a, ShadowCasters>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Light>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (active_camera, lighting, mut shadow_casters, tranform_storage, light_storage) = data;
+        let camera_position = active_camera
+            .and_then(|active_camera| tranform_storage.get(active_camera.0))
+            .map_or_else(|| Point3::new(0.0, 0.0, 0.0), Transform::world_position);
+
+        let lights: Vec<(&Light, Point3<f32>, Vector3<f32>)> = (&tranform_storage, &light_storage)
+            .join()
+            .map(|(transform, light)| (light, transform.world_position(), transform.world_forward()))
+            .collect();
+        let (lights_block, casters) = pack_lights(lighting.ambient_color, lights, camera_position);
+        shadow_casters.0 = casters;
+
+        self.uniform_buffer
+            .get_or_insert_with(|| UniformBuffer::new(LIGHTS_BLOCK_BINDING, LIGHTS_BLOCK_LENGTH))
+            .upload(&lights_block);
+    }
+}
+
+/// Pack the lights, with their world position and direction, in the std140 layout of the
+/// `Lights` uniform block, and return them along with the shadow casters. Each light takes four
+/// `vec4`: the position and the type, the direction and the range, the color times the intensity,
+/// and the cosines of the spot angles followed by the shadow map layer.
+fn pack_lights(
+    ambient_color: Vector3<f32>,
+    mut lights: Vec<(&Light, Point3<f32>, Vector3<f32>)>,
+    camera_position: Point3<f32>,
+) -> (Vec<f32>, Vec<ShadowCaster>) {
+    let is_directional = |light: &Light| light.kind == LightKind::Directional;
+
+    lights.sort_by(|&(light, position, _), &(other_light, other_position, _)| {
+        match (is_directional(light), is_directional(other_light)) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => camera_position
+                .distance2(position)
+                .partial_cmp(&camera_position.distance2(other_position))
+                .unwrap_or(Ordering::Equal),
+        }
+    });
+    lights.truncate(MAX_LIGHTS);
+
+    let mut shadow_casters = Vec::new();
+    let mut has_directional_shadows = false;
+    let mut shadowed_spot_lights = 0;
+    let mut lights_block = Vec::with_capacity(LIGHTS_BLOCK_LENGTH);
+    lights_block.extend_from_slice(&[
+        ambient_color.x,
+        ambient_color.y,
+        ambient_color.z,
+        lights.len() as f32,
+    ]);
+
+    for (light, position, direction) in lights {
+        let (light_type, range, inner_angle, outer_angle) = match light.kind {
+            LightKind::Point { range } => (POINT_LIGHT, range, 0.0, 0.0),
+            LightKind::Directional => (DIRECTIONAL_LIGHT, 0.0, 0.0, 0.0),
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            } => (SPOT_LIGHT, range, inner_angle, outer_angle),
+        };
+        let direction = if direction.magnitude2() > 0.0 {
+            direction.normalize()
+        } else {
+            direction
+        };
+        let color = light.color * light.intensity;
+
+        let shadow_layer = match light.kind {
+            LightKind::Directional if light.casts_shadows && !has_directional_shadows => {
+                has_directional_shadows = true;
+                shadow_casters.push(ShadowCaster::Directional { direction });
+                0.0
+            }
+            LightKind::Spot { range, outer_angle, .. }
+                if light.casts_shadows && shadowed_spot_lights < MAX_SHADOWED_SPOT_LIGHTS =>
+            {
+                let layer = CASCADE_COUNT + shadowed_spot_lights;
+                shadowed_spot_lights += 1;
+                shadow_casters.push(ShadowCaster::Spot {
+                    position,
+                    direction,
+                    outer_angle,
+                    range,
+                    layer,
+                });
+                layer as f32
+            }
+            _ => NO_SHADOW_LAYER,
+        };
+
+        lights_block.extend_from_slice(&[
+            position.x,
+            position.y,
+            position.z,
+            light_type,
+            direction.x,
+            direction.y,
+            direction.z,
+            range,
+            color.x,
+            color.y,
+            color.z,
+            0.0,
+            Deg(inner_angle).cos(),
+            Deg(outer_angle).cos(),
+            shadow_layer,
+            0.0,
+        ]);
+    }
+
+    lights_block.resize(LIGHTS_BLOCK_LENGTH, 0.0);
+    (lights_block, shadow_casters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack_lights, LIGHTS_BLOCK_LENGTH, LIGHT_LENGTH, MAX_LIGHTS};
+    use cgmath::{Point3, Vector3};
+    use components::light::Light;
+    use shadows::{ShadowCaster, CASCADE_COUNT, MAX_SHADOWED_SPOT_LIGHTS};
+
+    const WHITE: Vector3<f32> = Vector3 {
+        x: 1.0,
+        y: 1.0,
+        z: 1.0,
+    };
+
+    fn light_at(lights_block: &[f32], index: usize) -> &[f32] {
+        let start = 4 + index * LIGHT_LENGTH;
+
+        &lights_block[start..start + LIGHT_LENGTH]
+    }
+
+    #[test]
+    fn pack_lights_in_the_std140_layout() {
+        let mut spot = Light::spot(Vector3::new(1.0, 0.5, 0.0), 10.0, 60.0, 90.0);
+        spot.intensity = 2.0;
+        let (lights_block, shadow_casters) = pack_lights(
+            Vector3::new(0.1, 0.2, 0.3),
+            vec![(&spot, Point3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 0.0, -2.0))],
+            Point3::new(0.0, 0.0, 0.0),
+        );
+
+        assert_eq!(lights_block.len(), LIGHTS_BLOCK_LENGTH);
+        assert_eq!(&lights_block[..4], &[0.1, 0.2, 0.3, 1.0]);
+
+        let light = light_at(&lights_block, 0);
+        assert_eq!(&light[..12], &[1.0, 2.0, 3.0, 2.0, 0.0, 0.0, -1.0, 10.0, 2.0, 1.0, 0.0, 0.0]);
+        assert!((light[12] - 0.5).abs() < 0.0001, "it should store the cosine of the inner angle");
+        assert!(light[13].abs() < 0.0001, "it should store the cosine of the outer angle");
+        assert_eq!(light[14], -1.0, "the light should have no shadow map");
+        assert!(shadow_casters.is_empty());
+        assert!(light_at(&lights_block, 1).iter().all(|&value| value == 0.0));
+    }
+
+    #[test]
+    fn keep_directional_then_closest_lights() {
+        let point = Light::point(WHITE, 5.0);
+        let directional = Light::directional(WHITE);
+        let forward = Vector3::new(0.0, 0.0, 1.0);
+        let camera_position = Point3::new(100.0, 0.0, 0.0);
+
+        let mut lights = vec![(&directional, Point3::new(-1000.0, 0.0, 0.0), forward)];
+        for x in 0..(MAX_LIGHTS + 4) {
+            lights.push((&point, Point3::new(x as f32 * 10.0, 0.0, 0.0), forward));
+        }
+
+        let (lights_block, _) = pack_lights(WHITE, lights, camera_position);
+
+        assert_eq!(lights_block[3], MAX_LIGHTS as f32);
+        assert_eq!(light_at(&lights_block, 0)[3], 1.0, "it should keep the directional light first");
+        assert_eq!(light_at(&lights_block, 1)[0], 100.0);
+        assert_eq!(light_at(&lights_block, 2)[0].min(light_at(&lights_block, 3)[0]), 90.0);
+
+        let farthest_x = (1..MAX_LIGHTS)
+            .map(|index| (light_at(&lights_block, index)[0] - 100.0).abs())
+            .fold(0.0, f32::max);
+        assert_eq!(farthest_x, 70.0, "it should drop the lights far from the camera");
+    }
+
+    #[test]
+    fn assign_shadow_map_layers() {
+        let mut sun = Light::directional(WHITE);
+        sun.casts_shadows = true;
+        let mut spot = Light::spot(WHITE, 10.0, 20.0, 30.0);
+        spot.casts_shadows = true;
+        let point = Light::point(WHITE, 10.0);
+        let down = Vector3::new(0.0, -1.0, 0.0);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let mut lights = vec![(&sun, origin, down), (&sun, origin, down), (&point, origin, down)];
+        for x in 0..(MAX_SHADOWED_SPOT_LIGHTS + 1) {
+            lights.push((&spot, Point3::new(x as f32, 0.0, 0.0), down));
+        }
+
+        let (lights_block, shadow_casters) = pack_lights(WHITE, lights, origin);
+        let layers: Vec<f32> = (0..(MAX_SHADOWED_SPOT_LIGHTS + 4))
+            .map(|index| light_at(&lights_block, index)[14])
+            .collect();
+
+        assert_eq!(&layers[..3], &[0.0, -1.0, -1.0], "only the first directional light has shadows");
+        assert_eq!(layers[3], CASCADE_COUNT as f32);
+        assert_eq!(layers[2 + MAX_SHADOWED_SPOT_LIGHTS], (CASCADE_COUNT + MAX_SHADOWED_SPOT_LIGHTS - 1) as f32);
+        assert_eq!(layers[3 + MAX_SHADOWED_SPOT_LIGHTS], -1.0, "the farthest spot light has no shadows");
+        assert_eq!(shadow_casters.len(), 1 + MAX_SHADOWED_SPOT_LIGHTS);
+        assert_eq!(shadow_casters[0], ShadowCaster::Directional { direction: down });
+        assert_eq!(
+            shadow_casters[1],
+            ShadowCaster::Spot {
+                position: origin,
+                direction: down,
+                outer_angle: 30.0,
+                range: 10.0,
+                layer: CASCADE_COUNT,
+            }
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use asset_manager::{AssetManager, Handle};
+use components::mesh_render::MeshRender;
+use mesh::Mesh;
+use specs::{
+    BitSet, Entities, InsertedFlag, Join, ModifiedFlag, ReadStorage, ReaderId, RemovedFlag,
+    Resources, System, SystemData, WriteExpect, WriteStorage,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Remove the meshes of the `AssetManager` which were used by a removed `MeshRender`, or by an
+/// entity deleted since, once no other `MeshRender` uses them. Their GL objects are deleted after
+/// the frame. The meshes which were never used by a `MeshRender` are left alone.
+#[derive(Default)]
+pub struct MeshRemoval {
+    inserted_mesh_render_reader: Option<ReaderId<InsertedFlag>>,
+    modified_mesh_render_reader: Option<ReaderId<ModifiedFlag>>,
+    removed_mesh_render_reader: Option<ReaderId<RemovedFlag>>,
+    changed_mesh_renders: BitSet,
+    removed_mesh_renders: BitSet,
+    /// The mesh of the `MeshRender` of each entity, as the removed components cannot be read.
+    meshes_by_entity: HashMap<u32, Handle<Mesh>>,
+}
+
+impl MeshRemoval {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> System<'a> for MeshRemoval {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, AssetManager>,
+        ReadStorage<'a, MeshRender>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+
+        let entities = Entities::fetch(res);
+        let mut mesh_render_storage = WriteStorage::<MeshRender>::fetch(res);
+        self.inserted_mesh_render_reader = Some(mesh_render_storage.track_inserted());
+        self.modified_mesh_render_reader = Some(mesh_render_storage.track_modified());
+        self.removed_mesh_render_reader = Some(mesh_render_storage.track_removed());
+
+        // The components added before the setup have sent their events already.
+        for (entity, mesh_render) in (&*entities, &mesh_render_storage).join() {
+            self.meshes_by_entity.insert(entity.id(), mesh_render.mesh);
+        }
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut assets, mesh_render_storage) = data;
+
+        self.changed_mesh_renders.clear();
+        self.removed_mesh_renders.clear();
+        mesh_render_storage.populate_inserted(
+            self.inserted_mesh_render_reader.as_mut().unwrap(),
+            &mut self.changed_mesh_renders,
+        );
+        mesh_render_storage.populate_modified(
+            self.modified_mesh_render_reader.as_mut().unwrap(),
+            &mut self.changed_mesh_renders,
+        );
+        mesh_render_storage.populate_removed(
+            self.removed_mesh_render_reader.as_mut().unwrap(),
+            &mut self.removed_mesh_renders,
+        );
+
+        let mut removed_meshes: Vec<Handle<Mesh>> = Vec::new();
+
+        for id in (&self.removed_mesh_renders).join() {
+            if let Some(mesh) = self.meshes_by_entity.remove(&id) {
+                removed_meshes.push(mesh);
+            }
+        }
+
+        // A `MeshRender` given another mesh no longer uses its previous one.
+        for (entity, mesh_render, _) in (&*entities, &mesh_render_storage, &self.changed_mesh_renders).join() {
+            match self.meshes_by_entity.insert(entity.id(), mesh_render.mesh) {
+                Some(previous_mesh) if previous_mesh != mesh_render.mesh => removed_meshes.push(previous_mesh),
+                _ => {}
+            }
+        }
+
+        if removed_meshes.is_empty() {
+            return;
+        }
+
+        let used_meshes: HashSet<Handle<Mesh>> = mesh_render_storage
+            .join()
+            .map(|mesh_render| mesh_render.mesh)
+            .collect();
+        let removed_mesh_count = assets.remove_unused_meshes(&removed_meshes, &used_meshes);
+
+        debug!("Removed {} unused meshes.", removed_mesh_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MeshRemoval;
+    use asset_manager::AssetManager;
+    use components::mesh_render::MeshRender;
+    use gl_object::{delete_dropped_gl_objects, GlObjectCounts};
+    use headless::HeadlessContext;
+    use material::Material;
+    use mesh::Mesh;
+    use mesh_data::MeshData;
+    use specs::{Builder, RunNow, World};
+
+    /// Needs an OpenGL driver, see `HeadlessContext`.
+    #[test]
+    #[ignore]
+    fn free_the_meshes_of_deleted_entities() {
+        let _context = HeadlessContext::new(32, 32).unwrap();
+        let mut world = World::new();
+        world.register::<MeshRender>();
+
+        let mut assets = AssetManager::new();
+        let material = Material::new(&mut assets).unwrap();
+        world.add_resource(assets);
+
+        let mut mesh_removal_system = MeshRemoval::new();
+        mesh_removal_system.setup(&mut world.res);
+
+        // Delete the objects dropped by other tests, so they do not change the counts below.
+        delete_dropped_gl_objects();
+        let counts_before = GlObjectCounts::live();
+
+        let (shared_mesh, own_mesh, unattached_mesh) = {
+            let mut assets = world.write_resource::<AssetManager>();
+
+            (
+                assets.add_mesh(Mesh::new(MeshData::new(), Vec::default())),
+                assets.add_mesh(Mesh::new(MeshData::new(), Vec::default())),
+                assets.add_mesh(Mesh::new(MeshData::new(), Vec::default())),
+            )
+        };
+        let counts_with_meshes = GlObjectCounts::live();
+
+        assert!(counts_with_meshes.vertex_arrays > counts_before.vertex_arrays);
+        assert!(counts_with_meshes.buffers > counts_before.buffers);
+
+        let shared_entities: Vec<_> = (0..2)
+            .map(|_| {
+                world
+                    .create_entity()
+                    .with(MeshRender::new(material.clone(), shared_mesh))
+                    .build()
+            })
+            .collect();
+        let own_entity = world
+            .create_entity()
+            .with(MeshRender::new(material.clone(), own_mesh))
+            .build();
+
+        world.delete_entity(shared_entities[0]).unwrap();
+        world.delete_entity(own_entity).unwrap();
+        world.maintain();
+        mesh_removal_system.run_now(&world.res);
+        delete_dropped_gl_objects();
+
+        {
+            let assets = world.read_resource::<AssetManager>();
+
+            assert!(assets.mesh(shared_mesh).is_some(), "an entity still uses the mesh");
+            assert!(assets.mesh(own_mesh).is_none());
+            assert!(
+                assets.mesh(unattached_mesh).is_some(),
+                "no removed `MeshRender` used the mesh"
+            );
+        }
+
+        world.delete_entity(shared_entities[1]).unwrap();
+        world.maintain();
+        mesh_removal_system.run_now(&world.res);
+        delete_dropped_gl_objects();
+
+        assert!(world.read_resource::<AssetManager>().mesh(shared_mesh).is_none());
+
+        world.write_resource::<AssetManager>().remove_mesh(unattached_mesh);
+        delete_dropped_gl_objects();
+
+        let counts_after = GlObjectCounts::live();
+
+        assert_eq!(counts_after.vertex_arrays, counts_before.vertex_arrays);
+        assert_eq!(counts_after.buffers, counts_before.buffers);
+    }
+}
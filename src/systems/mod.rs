@@ -2,8 +2,13 @@ pub mod render;
 pub mod window_event;
 pub mod player_movement;
 pub mod gui_rendering;
+mod hierarchy_panel;
 pub mod mouse_control;
 pub mod transformation;
+pub mod chunk_meshing;
+pub mod asset_reloading;
+pub mod light_gathering;
+pub mod mesh_removal;
 
 mod rotator;
 pub use self::rotator::Rotator;
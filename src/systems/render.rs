@@ -3,11 +3,24 @@ extern crate glutin;
 extern crate imgui;
 extern crate imgui_opengl_renderer;
 
+use asset_manager::AssetManager;
+use bounds::{Aabb, Frustum};
+use cgmath::prelude::*;
 use cgmath::{Matrix4, Vector3};
+use components::camera::Camera;
 use components::mesh_render::MeshRender;
 use components::transform::Transform;
+use material::MaterialKey;
+use render_queue::RenderQueue;
 use resources::active_camera::ActiveCamera;
-use specs::{Join, ReadExpect, ReadStorage, System};
+use resources::render_stats::RenderStats;
+use resources::shadow_casters::ShadowCasters;
+use shader::CAMERA_BLOCK_BINDING;
+use shadow_map::{ShadowMap, SHADOW_MAP_TEXTURE_UNIT};
+use shadows::ShadowLayers;
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+use std::ffi::CStr;
+use uniform_buffer::UniformBuffer;
 
 const CAMERA_UP: Vector3<f32> = Vector3 {
     x: 0.0,
@@ -15,8 +28,21 @@ const CAMERA_UP: Vector3<f32> = Vector3 {
     z: 0.0,
 };
 
+/// The length in floats of the `Camera` uniform block: the view and projection matrices.
+const CAMERA_BLOCK_LENGTH: usize = 2 * 16;
+
+/// Draw the shadow maps of the `ShadowCasters` found by the `LightGathering` system, then the
+/// meshes seen by the active camera. The meshes whose bounds are out of the frustum of the camera,
+/// or of the light of a shadow map, are not drawn. The visible meshes go through a `RenderQueue`
+/// sorted by material, so each material is bound once per frame, and only the uniforms of the
+/// meshes are set between the draws. The counts of the frame are in `RenderStats`.
 #[derive(Default, Debug)]
-pub struct Render;
+pub struct Render {
+    /// Created by the first frame, on the thread owning the OpenGL context.
+    shadow_map: Option<ShadowMap>,
+    /// The `Camera` uniform block, created along with the shadow map.
+    camera_buffer: Option<UniformBuffer>,
+}
 
 impl Render {
     pub fn new() -> Self {
@@ -26,19 +52,103 @@ impl Render {
 
 impl<'a> System<'a> for Render {
     type SystemData = (
-        ReadExpect<'a, ActiveCamera>,
+        Option<Read<'a, ActiveCamera>>,
+        ReadExpect<'a, AssetManager>,
+        Read<'a, ShadowCasters>,
+        Write<'a, RenderStats>,
         ReadStorage<'a, Transform>,
+        ReadStorage<'a, Camera>,
         ReadStorage<'a, MeshRender>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (active_camera, tranform_storage, mesh_render_storage) = data;
-        let camera_transform = tranform_storage.get(active_camera.0).unwrap();
+        let (
+            active_camera,
+            assets,
+            shadow_casters,
+            mut render_stats,
+            tranform_storage,
+            camera_storage,
+            mesh_render_storage,
+        ) = data;
+        let active_camera = match active_camera {
+            Some(active_camera) => active_camera.0,
+            None => {
+                warn!("The scene has no active camera, the frame is not rendered.");
+                return;
+            }
+        };
+        let camera_transform = match tranform_storage.get(active_camera) {
+            Some(camera_transform) => camera_transform,
+            None => {
+                warn!("The active camera has no transform, the frame is not rendered.");
+                return;
+            }
+        };
+        let camera = camera_storage.get(active_camera).cloned().unwrap_or_default();
+
+        let camera_pos = camera_transform.world_position();
+        let camera_forward = camera_transform.world_forward();
+        let view: Matrix4<f32> = Matrix4::look_at(camera_pos, camera_pos + camera_forward, CAMERA_UP);
+        let projection = camera.projection_matrix();
+
+        let shadow_map = self.shadow_map.get_or_insert_with(ShadowMap::new);
+        let shadow_layers = ShadowLayers::new(&shadow_casters.0, &camera, view);
+
+        render_shadow_maps(&shadow_layers, shadow_map, &tranform_storage, &mesh_render_storage, &assets);
+        shadow_map.bind(&shadow_layers.pack(camera_pos, camera_forward));
 
         clear_screen();
 
+        let view_columns: &[f32; 16] = view.as_ref();
+        let projection_columns: &[f32; 16] = projection.as_ref();
+        let mut camera_block = Vec::with_capacity(CAMERA_BLOCK_LENGTH);
+        camera_block.extend_from_slice(view_columns);
+        camera_block.extend_from_slice(projection_columns);
+        self.camera_buffer
+            .get_or_insert_with(|| UniformBuffer::new(CAMERA_BLOCK_BINDING, CAMERA_BLOCK_LENGTH))
+            .upload(&camera_block);
+
+        let frustum = Frustum::from_matrix(&(projection * view));
+        let mut render_queue: RenderQueue<MaterialKey, _> = RenderQueue::new();
+        *render_stats = RenderStats::default();
+
         for (mesh_transform, mesh_render) in (&tranform_storage, &mesh_render_storage).join() {
-            render_mesh(&mesh_transform, &mesh_render, &camera_transform);
+            match world_bounds(mesh_transform, mesh_render, &assets) {
+                Some(bounds) if frustum.intersects_aabb(&bounds) => {
+                    let depth = (bounds.center() - camera_pos).dot(camera_forward);
+                    render_queue.push(mesh_render.material.key(), depth, (mesh_transform, mesh_render));
+                }
+                _ => render_stats.culled_meshes += 1,
+            }
+        }
+
+        render_queue.sort();
+        render_stats.drawn_meshes = render_queue.len();
+
+        for batch in render_queue.batches() {
+            // The lights are in the `Lights` uniform block, see `LightGathering`, the shadows in the
+            // `Shadows` one and the camera in the `Camera` one, so only the uniforms of the meshes
+            // are left.
+            let (_, first_mesh_render) = batch[0].item;
+            let material = first_mesh_render.material.bind(&assets);
+            material.set_integer("shadowMap", SHADOW_MAP_TEXTURE_UNIT as i32);
+
+            for draw_item in batch {
+                let (mesh_transform, mesh_render) = draw_item.item;
+
+                if let Some(mesh) = assets.mesh(mesh_render.mesh) {
+                    material.set_matrix4("model", &mesh_transform.world_matrix);
+                    material.set_matrix3("normalMatrix", &mesh_transform.get_normal_matrix());
+                    material.set_integer("receiveShadows", mesh_render.receives_shadows as i32);
+
+                    unsafe {
+                        mesh.Draw();
+                    }
+                }
+            }
+
+            render_stats.batches += 1;
         }
     }
 }
@@ -50,26 +160,60 @@ fn clear_screen() {
     }
 }
 
-fn render_mesh(mesh_transform: &Transform, mesh_render: &MeshRender, camera_tranform: &Transform) {
-    let camera_pos = camera_tranform.local_position;
-    let camera_forward = camera_tranform.forward();
+/// Draw the depth of the meshes casting shadows in each used layer of the shadow map.
+fn render_shadow_maps(
+    shadow_layers: &ShadowLayers,
+    shadow_map: &ShadowMap,
+    tranform_storage: &ReadStorage<Transform>,
+    mesh_render_storage: &ReadStorage<MeshRender>,
+    assets: &AssetManager,
+) {
+    let depth_shader = match shadow_map.depth_shader() {
+        Some(depth_shader) => depth_shader,
+        None => return,
+    };
 
     unsafe {
-        // TODO: Batch entities with the same material.
-        mesh_render.material.bind();
+        let previous_state = shadow_map.begin_depth_pass();
+        depth_shader.useProgram();
 
-        let view: Matrix4<f32> =
-            Matrix4::look_at(camera_pos, camera_pos + camera_forward, CAMERA_UP);
-        mesh_render.material.set_matrix4("view", &view);
+        for (layer, light_matrix) in shadow_layers.matrices.iter().enumerate() {
+            let light_matrix = match light_matrix {
+                Some(light_matrix) => light_matrix,
+                None => continue,
+            };
 
-        mesh_render
-            .material
-            .set_matrix4("model", &mesh_transform.world_matrix);
+            shadow_map.bind_layer(layer);
+            depth_shader.setMat4(c_str!("lightMatrix"), light_matrix);
+            let frustum = Frustum::from_matrix(light_matrix);
 
-        mesh_render
-            .material
-            .set_matrix3("normalMatrix", &mesh_transform.get_normal_matrix());
+            for (mesh_transform, mesh_render) in (tranform_storage, mesh_render_storage).join() {
+                let is_visible = world_bounds(mesh_transform, mesh_render, assets)
+                    .map_or(false, |bounds| frustum.intersects_aabb(&bounds));
+                if !mesh_render.casts_shadows || !is_visible {
+                    continue;
+                }
 
-        mesh_render.mesh.Draw();
+                if let Some(mesh) = assets.mesh(mesh_render.mesh) {
+                    depth_shader.setMat4(c_str!("model"), &mesh_transform.world_matrix);
+                    mesh.Draw();
+                }
+            }
+        }
+
+        shadow_map.end_depth_pass(previous_state);
     }
 }
+
+/// The bounds of the mesh in world space, `None` for meshes without vertices, which are never
+/// visible.
+fn world_bounds(
+    mesh_transform: &Transform,
+    mesh_render: &MeshRender,
+    assets: &AssetManager,
+) -> Option<Aabb> {
+    assets
+        .mesh(mesh_render.mesh)
+        .and_then(|mesh| mesh.bounds())
+        .map(|bounds| bounds.transform(&mesh_transform.world_matrix))
+}
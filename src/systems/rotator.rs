@@ -1,7 +1,7 @@
-use specs::{WriteStorage, WriteExpect, System, Read};
+use specs::{WriteStorage, Read, System, ReadExpect};
 use resources::rotating_entity::RotatingEntity;
 use components::transform::Transform;
-use time::Time;
+use time::FixedTime;
 
 const SENSITIVITY: f32 = 5.0;
 
@@ -23,16 +23,25 @@ impl Rotator {
     }
 }
 
+/// Spin the entity of the `RotatingEntity` resource around its y axis. Nothing rotates when the
+/// scene did not add the resource or the entity has no transform.
 impl<'a> System<'a> for Rotator {
     type SystemData = (
-        WriteExpect<'a, RotatingEntity>,
+        Option<Read<'a, RotatingEntity>>,
         WriteStorage<'a, Transform>,
-        Read<'a, Time>
+        ReadExpect<'a, FixedTime>
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (active_camera, mut tranform_storage, time) = data;
-        let transform = tranform_storage.get_mut(active_camera.0).unwrap();
+        let (rotating_entity, mut tranform_storage, time) = data;
+        let rotating_entity = match rotating_entity {
+            Some(rotating_entity) => rotating_entity.0,
+            None => return,
+        };
+        let transform = match tranform_storage.get_mut(rotating_entity) {
+            Some(transform) => transform,
+            None => return,
+        };
         let delta_time_in_seconds = time.get_delta_time_in_seconds();
 
         self.yaw = (self.yaw + SENSITIVITY * delta_time_in_seconds) % 360.0;
@@ -1,48 +1,200 @@
+use cgmath::{Matrix4, SquareMatrix};
 use components::transform::Transform;
 use components::parent::Parent;
-use specs::{System, ReadStorage, WriteStorage, Entities, Join, Entity};
-use scene_tree::SceneTree;
-use cgmath::Matrix4;
-use math::point_to_vector;
+use specs::{
+    BitSet, Entities, Entity, InsertedFlag, Join, ModifiedFlag, ReaderId, RemovedFlag, Resources,
+    System, SystemData, WriteExpect, WriteStorage,
+};
+use scene_tree::{SceneTree, SceneTreeError};
+use std::mem;
 
 pub struct Transformation {
-    scene_tree: SceneTree,
+    scene_root_entity: Entity,
+    inserted_transform_reader: Option<ReaderId<InsertedFlag>>,
+    removed_transform_reader: Option<ReaderId<RemovedFlag>>,
+    inserted_parent_reader: Option<ReaderId<InsertedFlag>>,
+    modified_parent_reader: Option<ReaderId<ModifiedFlag>>,
+    removed_parent_reader: Option<ReaderId<RemovedFlag>>,
+    inserted_transforms: BitSet,
+    removed_transforms: BitSet,
+    changed_parents: BitSet,
+    removed_parents: BitSet,
+    /// The parents which could not be set yet because an entity was not in the scene tree.
+    pending_parents: BitSet,
 }
 
 impl Transformation {
     pub fn new(entity: Entity) -> Self {
         Self {
-            scene_tree: SceneTree::new(entity),
+            scene_root_entity: entity,
+            inserted_transform_reader: None,
+            removed_transform_reader: None,
+            inserted_parent_reader: None,
+            modified_parent_reader: None,
+            removed_parent_reader: None,
+            inserted_transforms: BitSet::new(),
+            removed_transforms: BitSet::new(),
+            changed_parents: BitSet::new(),
+            removed_parents: BitSet::new(),
+            pending_parents: BitSet::new(),
         }
     }
 }
 
-/// This system is meant to compute the local transform based on the parent transform.
+/// This system is meant to compute the local transform based on the parent transform. It also
+/// keeps the `SceneTree` resource in sync with the `Transform` and `Parent` components, so other
+/// systems can walk the hierarchy.
+///
+/// Deleting an entity, or removing its transform, deletes its descendants as well. A parent
+/// whose entity has no transform yet is set once it has one. A parent which would create a cycle
+/// is logged and reverted to the parent the entity had in the tree.
 impl<'a> System<'a> for Transformation {
     type SystemData = (
         Entities<'a>,
         WriteStorage<'a, Transform>,
-        ReadStorage<'a, Parent>,
+        WriteStorage<'a, Parent>,
+        WriteExpect<'a, SceneTree>,
     );
 
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+
+        let scene_tree = {
+            let entities = Entities::fetch(res);
+            let mut transform_storage = WriteStorage::<Transform>::fetch(res);
+            let mut parent_storage = WriteStorage::<Parent>::fetch(res);
+
+            self.inserted_transform_reader = Some(transform_storage.track_inserted());
+            self.removed_transform_reader = Some(transform_storage.track_removed());
+            self.inserted_parent_reader = Some(parent_storage.track_inserted());
+            self.modified_parent_reader = Some(parent_storage.track_modified());
+            self.removed_parent_reader = Some(parent_storage.track_removed());
+
+            // The entities created before the setup have sent their events already.
+            let mut scene_tree = SceneTree::new(self.scene_root_entity);
+
+            for (entity, _transform) in (&*entities, &transform_storage).join() {
+                scene_tree.insert_entity(entity);
+            }
+
+            // The failures are logged and handled by the first run.
+            for (entity, _transform, parent) in (&*entities, &transform_storage, &parent_storage).join() {
+                if scene_tree.set_parent(&entity, Some(&parent.entity)).is_err() {
+                    self.pending_parents.add(entity.id());
+                }
+            }
+
+            scene_tree
+        };
+
+        res.insert(scene_tree);
+    }
+
     fn run(&mut self, data: Self::SystemData) {
-        let (entity, mut tranform_storage, parent_storage) = data;
+        let (entities, mut tranform_storage, mut parent_storage, mut scene_tree) = data;
+
+        self.inserted_transforms.clear();
+        self.removed_transforms.clear();
+        self.changed_parents.clear();
+        self.removed_parents.clear();
 
-        // Clear the scene tree.
-        self.scene_tree.reset();
+        tranform_storage.populate_inserted(
+            self.inserted_transform_reader.as_mut().expect("the system should be set up"),
+            &mut self.inserted_transforms,
+        );
+        tranform_storage.populate_removed(
+            self.removed_transform_reader.as_mut().expect("the system should be set up"),
+            &mut self.removed_transforms,
+        );
+        parent_storage.populate_inserted(
+            self.inserted_parent_reader.as_mut().expect("the system should be set up"),
+            &mut self.changed_parents,
+        );
+        parent_storage.populate_modified(
+            self.modified_parent_reader.as_mut().expect("the system should be set up"),
+            &mut self.changed_parents,
+        );
+        parent_storage.populate_removed(
+            self.removed_parent_reader.as_mut().expect("the system should be set up"),
+            &mut self.removed_parents,
+        );
+
+        // Removed entities are only known by their id, which may have been reused since.
+        for id in (&self.removed_transforms).join() {
+            let entity = match scene_tree.entity_with_id(id) {
+                Some(entity) => entity,
+                None => continue,
+            };
+
+            if entities.is_alive(entity) && tranform_storage.get(entity).is_some() {
+                continue;
+            }
 
-        // Assign a scene node to every positioned entitiy.
-        for (entity, _transform) in (&*entity, &tranform_storage).join() {
-            self.scene_tree.add_entity_node(entity);
+            for descendant in scene_tree.remove_entity(&entity) {
+                if entities.is_alive(descendant) {
+                    if let Err(error) = entities.delete(descendant) {
+                        warn!("Cannot delete an entity: {:?}", error);
+                    }
+                }
+            }
         }
 
-        // Iterate over all entities with a parent component to create the scene tree.
-        for (entity, _transform, parent) in (&*entity, &tranform_storage, &parent_storage).join() {
-            self.scene_tree.set_entity_child(&parent.entity, &entity);
+        for (entity, _transform, _) in (&*entities, &tranform_storage, &self.inserted_transforms).join() {
+            scene_tree.insert_entity(entity);
+        }
+
+        for id in (&self.removed_parents).join() {
+            if let Some(entity) = scene_tree.entity_with_id(id) {
+                if parent_storage.get(entity).is_none() && scene_tree.set_parent(&entity, None).is_ok() {
+                    if let Some(transform) = tranform_storage.get_mut(entity) {
+                        transform.is_dirty = true;
+                    }
+                }
+            }
+        }
+
+        // The parents which could not be set are retried until their entities are in the tree.
+        let pending_parents = mem::replace(&mut self.pending_parents, BitSet::new());
+        self.changed_parents |= &pending_parents;
+        let mut rejected_parents = Vec::new();
+
+        for (entity, parent, _) in (&*entities, &parent_storage, &self.changed_parents).join() {
+            match scene_tree.set_parent(&entity, Some(&parent.entity)) {
+                Ok(()) => {
+                    if let Some(transform) = tranform_storage.get_mut(entity) {
+                        transform.is_dirty = true;
+                    }
+                }
+                Err(error @ SceneTreeError::MissingEntity { .. }) => {
+                    if !pending_parents.contains(entity.id()) {
+                        warn!("Cannot set the parent of an entity yet: {}", error);
+                    }
+
+                    self.pending_parents.add(entity.id());
+                }
+                Err(error) => {
+                    error!("Cannot set the parent of an entity, it is reverted: {}", error);
+                    rejected_parents.push(entity);
+                }
+            }
+        }
+
+        // Put back the parent of the tree, so the storage matches it.
+        for entity in rejected_parents {
+            match scene_tree.get_parent_entity(&entity).cloned() {
+                Some(parent) if parent != self.scene_root_entity => {
+                    parent_storage
+                        .insert(entity, Parent { entity: parent })
+                        .expect("the entity should be alive");
+                }
+                _ => {
+                    parent_storage.remove(entity);
+                }
+            }
         }
 
         let root_entities = {
-            match self.scene_tree.root_entities() {
+            match scene_tree.root_entities() {
                 Some(root_entities) => root_entities,
                 _ => {
                     return;
@@ -50,13 +202,20 @@ impl<'a> System<'a> for Transformation {
             }
         };
 
-        let mut current_higher_dirty_depth: u32 = 0;
+        // Whether the entity at each depth of the current branch, or one of its ancestors, has
+        // been recomputed. Its descendants must then be recomputed as well.
+        let mut dirty_branch: Vec<bool> = Vec::new();
 
         for root_entity in root_entities {
-            let descendant_entities = self.scene_tree.descendant_entities_with_depth(&root_entity);
+            let mut descendant_entities = scene_tree.descendant_entities_with_depth(&root_entity).peekable();
+            let root_depth = descendant_entities.peek().map_or(0, |&(_, depth)| depth);
+            dirty_branch.clear();
 
             for (entity, depth) in descendant_entities {
-                let parent_world_matrix_option = if let Some(parent_entity) = self.scene_tree.get_parent_entity(&entity) {
+                dirty_branch.truncate((depth - root_depth) as usize);
+                let is_parent_dirty = dirty_branch.last().cloned().unwrap_or(false);
+
+                let parent_world_matrix_option = if let Some(parent_entity) = scene_tree.get_parent_entity(&entity) {
                     if let Some(parent_transform) = tranform_storage.get(*parent_entity) {
                         Some(parent_transform.world_matrix)
                     } else {
@@ -66,27 +225,274 @@ impl<'a> System<'a> for Transformation {
                     None
                 };
 
-                if let Some(transform) = tranform_storage.get_mut(entity) {
-                    let is_dirty = transform.is_dirty || depth > current_higher_dirty_depth;
+                let is_dirty = match tranform_storage.get_mut(entity) {
+                    Some(transform) => {
+                        let is_dirty = transform.is_dirty || is_parent_dirty;
 
-                    if is_dirty {
-                        let local_rotation = Matrix4::from(transform.local_rotation);
-                        let translation = Matrix4::from_translation(point_to_vector(transform.local_position));
-                        let local_matrix: Matrix4<f32> = translation * local_rotation;
+                        if is_dirty {
+                            let local_matrix = transform.compute_local_matrix();
 
-                        transform.local_matrix = local_matrix;
+                            transform.local_matrix = local_matrix;
+                            transform.parent_world_matrix =
+                                parent_world_matrix_option.unwrap_or_else(Matrix4::identity);
+                            transform.world_matrix = transform.parent_world_matrix * local_matrix;
 
-                        if let Some(parent_world_matrix) = parent_world_matrix_option {
-                            transform.world_matrix = parent_world_matrix * local_matrix;
-                        } else {
-                            transform.world_matrix = local_matrix;
+                            transform.is_dirty = false;
                         }
 
-                        transform.is_dirty = false;
-                        current_higher_dirty_depth = depth;
+                        is_dirty
                     }
-                }
+                    None => is_parent_dirty,
+                };
+
+                dirty_branch.push(is_dirty);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Transformation;
+    use cgmath::{Point3, Vector3, Vector4};
+    use components::parent::Parent;
+    use components::transform::Transform;
+    use scene_tree::SceneTree;
+    use specs::{Builder, RunNow, World};
+
+    #[test]
+    fn propagate_scale_to_children() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let mut parent_transform = Transform::new(Point3::new(1.0, 0.0, 0.0), "Parent");
+        parent_transform.set_local_scale(Vector3::new(2.0, 3.0, 1.0));
+        let parent = world.create_entity().with(parent_transform).build();
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(1.0, 1.0, 0.0), "Child"))
+            .with(Parent { entity: parent })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        let transform_storage = world.read_storage::<Transform>();
+        let child_world_matrix = transform_storage.get(child).unwrap().world_matrix;
+
+        assert_eq!(child_world_matrix.w, Vector4::new(3.0, 3.0, 0.0, 1.0));
+        assert_eq!(child_world_matrix.x, Vector4::new(2.0, 0.0, 0.0, 0.0));
+        assert_eq!(child_world_matrix.y, Vector4::new(0.0, 3.0, 0.0, 0.0));
+        assert_eq!(
+            transform_storage.get(child).unwrap().world_position(),
+            Point3::new(3.0, 3.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn propagate_moves_to_every_descendant() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let parent = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Parent"))
+            .build();
+        let first_child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(1.0, 0.0, 0.0), "First child"))
+            .with(Parent { entity: parent })
+            .build();
+        let grandchild = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 1.0, 0.0), "Grandchild"))
+            .with(Parent { entity: first_child })
+            .build();
+        let second_child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 1.0), "Second child"))
+            .with(Parent { entity: parent })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        world
+            .write_storage::<Transform>()
+            .get_mut(parent)
+            .unwrap()
+            .set_local_position(Point3::new(10.0, 0.0, 0.0));
+        transformation_system.run_now(&world.res);
+
+        let transform_storage = world.read_storage::<Transform>();
+        assert_eq!(
+            transform_storage.get(first_child).unwrap().world_matrix.w,
+            Vector4::new(11.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            transform_storage.get(grandchild).unwrap().world_matrix.w,
+            Vector4::new(11.0, 1.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            transform_storage.get(second_child).unwrap().world_matrix.w,
+            Vector4::new(10.0, 0.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            transform_storage.get(second_child).unwrap().parent_world_matrix.w,
+            Vector4::new(10.0, 0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn update_scene_tree_incrementally() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let parent = world
+            .create_entity()
+            .with(Transform::new(Point3::new(1.0, 0.0, 0.0), "Parent"))
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 1.0, 0.0), "Child"))
+            .with(Parent { entity: parent })
+            .build();
+        transformation_system.run_now(&world.res);
+        world.maintain();
+
+        assert_eq!(world.read_resource::<SceneTree>().get_parent_entity(&child), Some(&parent));
+        assert_eq!(
+            world.read_storage::<Transform>().get(child).unwrap().world_matrix.w,
+            Vector4::new(1.0, 1.0, 0.0, 1.0)
+        );
+
+        world.write_storage::<Parent>().remove(child);
+        transformation_system.run_now(&world.res);
+        world.maintain();
+
+        assert_eq!(
+            world.read_resource::<SceneTree>().get_parent_entity(&child),
+            Some(&scene_root_entity)
+        );
+        assert_eq!(
+            world.read_storage::<Transform>().get(child).unwrap().world_matrix.w,
+            Vector4::new(0.0, 1.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn revert_parent_cycles() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let parent = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Parent"))
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Child"))
+            .with(Parent { entity: parent })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+
+        world
+            .write_storage::<Parent>()
+            .insert(parent, Parent { entity: child })
+            .unwrap();
+        transformation_system.run_now(&world.res);
+
+        let scene_tree = world.read_resource::<SceneTree>();
+        assert_eq!(scene_tree.get_parent_entity(&parent), Some(&scene_root_entity));
+        assert_eq!(scene_tree.get_parent_entity(&child), Some(&parent));
+        assert!(
+            world.read_storage::<Parent>().get(parent).is_none(),
+            "the cyclic parent should be removed"
+        );
+    }
+
+    #[test]
+    fn retry_parents_without_transform() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let parent = world.create_entity().build();
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Child"))
+            .with(Parent { entity: parent })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        assert_eq!(
+            world.read_resource::<SceneTree>().get_parent_entity(&child),
+            Some(&scene_root_entity)
+        );
+
+        world
+            .write_storage::<Transform>()
+            .insert(parent, Transform::new(Point3::new(0.0, 0.0, 0.0), "Parent"))
+            .unwrap();
+        transformation_system.run_now(&world.res);
+
+        assert_eq!(world.read_resource::<SceneTree>().get_parent_entity(&child), Some(&parent));
+    }
+
+    #[test]
+    fn delete_descendants_of_deleted_entities() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+
+        let scene_root_entity = world.create_entity().build();
+        let parent = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Parent"))
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Child"))
+            .with(Parent { entity: parent })
+            .build();
+        let grandchild = world
+            .create_entity()
+            .with(Transform::new(Point3::new(0.0, 0.0, 0.0), "Grandchild"))
+            .with(Parent { entity: child })
+            .build();
+
+        let mut transformation_system = Transformation::new(scene_root_entity);
+        transformation_system.setup(&mut world.res);
+        transformation_system.run_now(&world.res);
+
+        world.delete_entity(parent).unwrap();
+        world.maintain();
+        transformation_system.run_now(&world.res);
+        world.maintain();
+
+        assert!(!world.is_alive(child));
+        assert!(!world.is_alive(grandchild));
+        assert!(!world.read_resource::<SceneTree>().contains_entity(&child));
+    }
+}
@@ -0,0 +1,30 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// A file of the temporary directory for the tests. Its name includes the id of the process, so
+/// concurrent test runs do not share it, and the file is removed once dropped, even when the test
+/// fails.
+pub struct TemporaryFile {
+    path: PathBuf,
+}
+
+impl TemporaryFile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            path: env::temp_dir().join(format!("bigseed_{}_{}", process::id(), name)),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.to_str().expect("the temporary directory should be valid UTF-8")
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        // The file may not have been created, or may have been removed by the test already.
+        let _ = fs::remove_file(&self.path);
+    }
+}
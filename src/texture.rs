@@ -1,5 +1,7 @@
 use std::sync::atomic::{self, AtomicUsize};
+use failure::Error;
 use gl;
+use gl_object::{GlHandle, GlObjectKind};
 use image;
 use image::GenericImage;
 use std::path::Path;
@@ -10,12 +12,14 @@ static OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(0);
 #[derive(Debug, Clone)]
 pub struct Texture {
     pub path: String,
-    id: u32,
+    handle: GlHandle,
     index: u32,
 }
 
 impl Texture {
-    pub fn new(path: &str) -> Self {
+    pub fn new(path: &str) -> Result<Self, Error> {
+        let img = image::open(&Path::new(path))
+            .map_err(|error| format_err!("Cannot load the texture at '{}': {}", path, error))?;
         let index = OBJECT_COUNTER.fetch_add(1, atomic::Ordering::SeqCst) as u32;
 
         let id = unsafe {
@@ -29,8 +33,7 @@ impl Texture {
             // set texture filtering parameters
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-            // load image, create texture and generate mipmaps
-            let img = image::open(&Path::new(path)).expect("Failed to load texture");
+            // create texture and generate mipmaps
             let data = img.raw_pixels();
             gl::TexImage2D(gl::TEXTURE_2D,
                            0,
@@ -46,17 +49,17 @@ impl Texture {
             id
         };
 
-        Texture {
-            id,
-            path: String::default(),
+        Ok(Texture {
+            handle: GlHandle::new(GlObjectKind::Texture, id),
+            path: path.to_string(),
             index,
-        }
+        })
     }
 
     pub fn bind(&self) {
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + self.index);
-            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::BindTexture(gl::TEXTURE_2D, self.handle.id());
         }
     }
 
@@ -67,3 +67,39 @@ impl Default for Time {
         }
     }
 }
+
+/// The time of the fixed update systems, which run at a constant rate.
+pub struct FixedTime {
+    delta_time: Duration,
+    delta_time_in_seconds: f32,
+    alpha: f32,
+}
+
+impl FixedTime {
+    pub fn new(delta_time: Duration) -> Self {
+        Self {
+            delta_time,
+            delta_time_in_seconds: delta_time.as_secs() as f32
+                + delta_time.subsec_nanos() as f32 / 1_000_000_000.0,
+            alpha: 0.0,
+        }
+    }
+
+    pub fn get_delta_time(&self) -> Duration {
+        self.delta_time
+    }
+
+    pub fn get_delta_time_in_seconds(&self) -> f32 {
+        self.delta_time_in_seconds
+    }
+
+    /// How far the current frame is between the last fixed update and the next one, from 0 to 1.
+    /// Use it to interpolate the state computed by the fixed update systems when rendering.
+    pub fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+}
@@ -0,0 +1,64 @@
+use gl;
+use gl_object::{GlHandle, GlObjectKind};
+use std::mem::{size_of, size_of_val};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// A buffer holding a uniform block shared by the shaders, such as the `Lights` one. It is bound to
+/// the binding point of the block, see `shader::UNIFORM_BLOCK_BINDINGS`.
+#[derive(Debug)]
+pub struct UniformBuffer {
+    handle: GlHandle,
+    binding: u32,
+    /// The number of floats the buffer holds.
+    length: usize,
+}
+
+impl UniformBuffer {
+    /// Allocate a buffer of `length` floats for the block at `binding`.
+    pub fn new(binding: u32, length: usize) -> Self {
+        let id = unsafe {
+            let mut id = 0;
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                (length * size_of::<f32>()) as isize,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+            id
+        };
+
+        Self {
+            handle: GlHandle::new(GlObjectKind::Buffer, id),
+            binding,
+            length,
+        }
+    }
+
+    /// Write `data`, in the std140 layout of the block, at the start of the buffer and bind it to
+    /// its binding point. `data` must fit in the buffer.
+    pub fn upload(&self, data: &[f32]) {
+        assert!(
+            data.len() <= self.length,
+            "{} floats do not fit in a uniform buffer of {} floats",
+            data.len(),
+            self.length
+        );
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.handle.id());
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                size_of_val(data) as isize,
+                data.as_ptr() as *const c_void,
+            );
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.handle.id());
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+}
@@ -9,6 +9,9 @@ use cgmath::prelude::*;
 pub struct Vertex {
     pub position: Vector3<f32>,
     pub color_index: u8,
+    /// From 0 when the neighbouring voxels fully occlude the vertex to 3 when none does, see
+    /// `voxel::ambient_occlusion`.
+    pub ambient_occlusion: u8,
     pub normal: Vector3<f32>,
 }
 
@@ -17,6 +20,7 @@ impl Default for Vertex {
         Vertex {
             position: Vector3::zero(),
             color_index: 0,
+            ambient_occlusion: 3,
             normal: Vector3::zero(),
         }
     }
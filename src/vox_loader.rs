@@ -1,70 +1,473 @@
 extern crate dot_vox;
 
-use self::dot_vox::load;
+use self::dot_vox::{load_bytes, DotVoxData, Model};
+use cgmath::{Matrix, Matrix3, Point3, Quaternion, SquareMatrix, Vector3};
 use failure::Error;
+use palette::Palette;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use vox_scene::{VoxScene, VoxSceneNode};
 use voxel::chunk::Chunk;
 
-const ASSETS_DIRECTORY: &str = "./resources/voxs";
-const SUPPORTED_VOX_VERSION: u32 = 150;
+pub const ASSETS_DIRECTORY: &str = "./resources/voxs";
+pub const SUPPORTED_VOX_VERSION: u32 = 150;
 
 #[derive(Default)]
 pub struct VoxLoader {}
 
 impl VoxLoader {
-    pub fn load(asset_name: &str) -> Result<Chunk, Error> {
-        let filepath = format!("{}/{}", ASSETS_DIRECTORY, asset_name);
-        let dot_vox_data = match load(&filepath) {
-            Err(message) => {
-                return Err(format_err!(
-                    "Tryed to open the asset file at '{}' and got the following error: {}.",
-                    filepath,
-                    message,
-                ))
-            }
-            Ok(dot_vox_data) => dot_vox_data,
+    /// Load the first model of the asset file along with the palette of the file.
+    pub fn load(asset_name: &str) -> Result<(Chunk, Palette), Error> {
+        Self::load_file(&format!("{}/{}", ASSETS_DIRECTORY, asset_name))
+    }
+
+    /// Same as `load` but with a path which is not relative to the assets directory.
+    pub fn load_file(filepath: &str) -> Result<(Chunk, Palette), Error> {
+        let dot_vox_data = load_dot_vox_data(filepath, &read_file(filepath)?)?;
+
+        if dot_vox_data.models.len() > 1 {
+            warn!("Mutliple models are found in the asset file at '{}', but only one the first one will be loaded. Use `VoxLoader::load_scene` to load all of them.", filepath);
+        }
+
+        Ok((
+            model_to_chunk(&dot_vox_data.models[0])?,
+            Palette::from_slice(&dot_vox_data.palette)?,
+        ))
+    }
+
+    /// Load every model of the asset file, along with the scene graph describing how they are
+    /// laid out.
+    pub fn load_scene(asset_name: &str) -> Result<VoxScene, Error> {
+        Self::load_scene_file(&format!("{}/{}", ASSETS_DIRECTORY, asset_name))
+    }
+
+    /// Same as `load_scene` but with a path which is not relative to the assets directory.
+    pub fn load_scene_file(filepath: &str) -> Result<VoxScene, Error> {
+        let asset_name = Path::new(filepath)
+            .file_name()
+            .map_or_else(|| filepath.to_string(), |name| name.to_string_lossy().into_owned());
+        let asset_name = asset_name.as_str();
+        let bytes = read_file(filepath)?;
+        let dot_vox_data = load_dot_vox_data(filepath, &bytes)?;
+
+        let models = dot_vox_data
+            .models
+            .iter()
+            .map(model_to_chunk)
+            .collect::<Result<Vec<Chunk>, Error>>()?;
+
+        let scene_nodes = parse_scene_nodes(&bytes)
+            .map_err(|error| format_err!("Cannot read the scene graph of the asset file at '{}': {}", filepath, error))?;
+
+        let nodes = if scene_nodes.is_empty() {
+            // Files without a scene graph place every model at the origin.
+            models
+                .iter()
+                .enumerate()
+                .map(|(index, _)| VoxSceneNode {
+                    name: format!("{} #{}", asset_name, index),
+                    parent: None,
+                    position: Point3::new(0.0, 0.0, 0.0),
+                    rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                    model: Some(index),
+                })
+                .collect()
+        } else {
+            flatten_scene_nodes(asset_name, &scene_nodes, &dot_vox_data.models)?
         };
 
-        if dot_vox_data.version != SUPPORTED_VOX_VERSION {
+        Ok(VoxScene {
+            models,
+            nodes,
+            palette: Palette::from_slice(&dot_vox_data.palette)?,
+        })
+    }
+}
+
+fn read_file(filepath: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+
+    File::open(filepath)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|error| {
+            format_err!(
+                "Tryed to open the asset file at '{}' and got the following error: {}.",
+                filepath,
+                error,
+            )
+        })?;
+
+    Ok(bytes)
+}
+
+fn load_dot_vox_data(filepath: &str, bytes: &[u8]) -> Result<DotVoxData, Error> {
+    let dot_vox_data = match load_bytes(bytes) {
+        Err(message) => {
             return Err(format_err!(
-                "Cannot load the asset file at '{}' because its vox version is {} and only the version 150 is supported.",
+                "Tryed to open the asset file at '{}' and got the following error: {}.",
                 filepath,
-                dot_vox_data.version,
-            ));
+                message,
+            ))
         }
+        Ok(dot_vox_data) => dot_vox_data,
+    };
 
-        let nb_models = dot_vox_data.models.len();
+    if dot_vox_data.version != SUPPORTED_VOX_VERSION {
+        return Err(format_err!(
+            "Cannot load the asset file at '{}' because its vox version is {} and only the version 150 is supported.",
+            filepath,
+            dot_vox_data.version,
+        ));
+    }
 
-        if nb_models == 0 {
-            return Err(format_err!(
-                "No models found in the asset file at '{}'",
-                filepath
-            ));
+    if dot_vox_data.models.is_empty() {
+        return Err(format_err!(
+            "No models found in the asset file at '{}'",
+            filepath
+        ));
+    }
+
+    Ok(dot_vox_data)
+}
+
+/// MagicaVoxel is z-up while we are y-up, so the y and z axes are swapped.
+fn model_to_chunk(model: &Model) -> Result<Chunk, Error> {
+    let mut chunk = Chunk::new(model.size.x as u8, model.size.z as u8, model.size.y as u8);
+
+    for &voxel in model.voxels.iter() {
+        chunk.set_voxel(
+            i64::from(voxel.x),
+            i64::from(voxel.z),
+            i64::from(model.size.y - u32::from(voxel.y) - 1),
+            voxel.i,
+        )?;
+    }
+
+    Ok(chunk)
+}
+
+/// A node of the MagicaVoxel scene graph, in MagicaVoxel coordinates.
+#[derive(Debug, PartialEq)]
+enum SceneNode {
+    Transform {
+        name: Option<String>,
+        child: i32,
+        translation: Vector3<f32>,
+        rotation: Matrix3<f32>,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<usize>,
+    },
+}
+
+/// Read the `nTRN`, `nGRP` and `nSHP` chunks of a vox file, indexed by node id.
+fn parse_scene_nodes(bytes: &[u8]) -> Result<HashMap<i32, SceneNode>, Error> {
+    let mut reader = ByteReader::new(bytes);
+    let mut nodes = HashMap::new();
+
+    if reader.read_bytes(4)? != b"VOX " {
+        return Err(format_err!("the file does not start with the 'VOX ' magic number"));
+    }
+    reader.read_u32()?;
+
+    // The MAIN chunk has no content, only children.
+    if reader.read_bytes(4)? != b"MAIN" {
+        return Err(format_err!("the MAIN chunk is missing"));
+    }
+    let main_content_size = reader.read_u32()? as usize;
+    reader.read_u32()?;
+    reader.read_bytes(main_content_size)?;
+
+    while !reader.is_empty() {
+        let id = reader.read_bytes(4)?.to_vec();
+        let content_size = reader.read_u32()? as usize;
+        let children_size = reader.read_u32()? as usize;
+        let mut content = ByteReader::new(reader.read_bytes(content_size)?);
+        reader.read_bytes(children_size)?;
+
+        match &id[..] {
+            b"nTRN" => {
+                let node_id = content.read_i32()?;
+                let attributes = content.read_dict()?;
+                let child = content.read_i32()?;
+                let _reserved_id = content.read_i32()?;
+                let _layer_id = content.read_i32()?;
+                let frame_count = content.read_i32()?;
+                let frame = if frame_count > 0 {
+                    content.read_dict()?
+                } else {
+                    HashMap::new()
+                };
+
+                let translation = match frame.get("_t") {
+                    Some(value) => parse_translation(value)?,
+                    None => Vector3::new(0.0, 0.0, 0.0),
+                };
+                let rotation = match frame.get("_r") {
+                    Some(value) => decode_rotation(value.parse::<u8>()?)?,
+                    None => Matrix3::identity(),
+                };
+
+                nodes.insert(
+                    node_id,
+                    SceneNode::Transform {
+                        name: attributes.get("_name").cloned(),
+                        child,
+                        translation,
+                        rotation,
+                    },
+                );
+            }
+            b"nGRP" => {
+                let node_id = content.read_i32()?;
+                content.read_dict()?;
+                let child_count = content.read_i32()?;
+                let children = (0..child_count)
+                    .map(|_| content.read_i32())
+                    .collect::<Result<Vec<i32>, Error>>()?;
+
+                nodes.insert(node_id, SceneNode::Group { children });
+            }
+            b"nSHP" => {
+                let node_id = content.read_i32()?;
+                content.read_dict()?;
+                let model_count = content.read_i32()?;
+                let mut models = Vec::with_capacity(model_count.max(0) as usize);
+
+                for _ in 0..model_count {
+                    models.push(content.read_i32()? as usize);
+                    content.read_dict()?;
+                }
+
+                nodes.insert(node_id, SceneNode::Shape { models });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(nodes)
+}
+
+fn parse_translation(value: &str) -> Result<Vector3<f32>, Error> {
+    let components = value
+        .split_whitespace()
+        .map(|component| component.parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    if components.len() != 3 {
+        return Err(format_err!("invalid translation '{}'", value));
+    }
+
+    Ok(Vector3::new(components[0], components[1], components[2]))
+}
+
+/// Decode the rotation byte of a `nTRN` frame. Bits 0-1 and 2-3 hold the column of the non-zero
+/// entry of the first and second rows, bits 4, 5 and 6 hold the signs of the three rows.
+fn decode_rotation(rotation: u8) -> Result<Matrix3<f32>, Error> {
+    let first_index = (rotation & 0b11) as usize;
+    let second_index = ((rotation >> 2) & 0b11) as usize;
+
+    if first_index > 2 || second_index > 2 || first_index == second_index {
+        return Err(format_err!("invalid rotation {}", rotation));
+    }
+
+    let third_index = 3 - first_index - second_index;
+    let mut rows = [[0.0_f32; 3]; 3];
+
+    for (row, index) in [first_index, second_index, third_index].iter().enumerate() {
+        let is_negative = (rotation >> (4 + row)) & 1 == 1;
+        rows[row][*index] = if is_negative { -1.0 } else { 1.0 };
+    }
+
+    // `Matrix3::new` takes the values column by column.
+    Ok(Matrix3::new(
+        rows[0][0], rows[1][0], rows[2][0],
+        rows[0][1], rows[1][1], rows[2][1],
+        rows[0][2], rows[1][2], rows[2][2],
+    ))
+}
+
+/// MagicaVoxel is z-up, this matrix converts its coordinates to ours the same way `model_to_chunk`
+/// does: x stays x, z becomes y and y becomes -z.
+fn vox_to_engine_axes() -> Matrix3<f32> {
+    Matrix3::new(
+        1.0, 0.0, 0.0,
+        0.0, 0.0, -1.0,
+        0.0, 1.0, 0.0,
+    )
+}
+
+/// The position of the center of the model in the coordinates of the chunk built by
+/// `model_to_chunk`. MagicaVoxel places models around this point.
+fn model_pivot(model: &Model) -> Vector3<f32> {
+    let half = |size: u32| (size / 2) as f32;
+
+    Vector3::new(
+        half(model.size.x) - 0.5,
+        half(model.size.z) - 0.5,
+        model.size.y as f32 - half(model.size.y) - 0.5,
+    )
+}
+
+/// Walk the scene graph from its root and list the nodes in the engine coordinates, parents
+/// before their children.
+fn flatten_scene_nodes(
+    asset_name: &str,
+    scene_nodes: &HashMap<i32, SceneNode>,
+    models: &[Model],
+) -> Result<Vec<VoxSceneNode>, Error> {
+    let mut nodes = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(i32, Option<usize>)> = vec![(0, None)];
+
+    while let Some((node_id, parent)) = stack.pop() {
+        if !visited.insert(node_id) {
+            return Err(format_err!("the node {} is referenced more than once", node_id));
         }
 
-        if nb_models > 1 {
-            warn!("Mutliple models are found in the asset file at '{}', but only one the first one will be loaded.", filepath);
+        match scene_nodes.get(&node_id) {
+            Some(SceneNode::Transform {
+                name,
+                child,
+                translation,
+                rotation,
+            }) => {
+                let axes = vox_to_engine_axes();
+                let rotation = axes * *rotation * axes.transpose();
+                let translation = axes * *translation;
+
+                if rotation.determinant() < 0.0 {
+                    warn!("The node {} of '{}' is mirrored, which is not supported: its rotation is ignored.", node_id, asset_name);
+                }
+
+                let quaternion = if rotation.determinant() < 0.0 {
+                    Quaternion::new(1.0, 0.0, 0.0, 0.0)
+                } else {
+                    Quaternion::from(rotation)
+                };
+                let name = name
+                    .clone()
+                    .unwrap_or_else(|| format!("{} #{}", asset_name, node_id));
+
+                match scene_nodes.get(child) {
+                    Some(SceneNode::Shape { models: shape_models }) => {
+                        visited.insert(*child);
+
+                        for model_index in shape_models {
+                            let model = models.get(*model_index).ok_or_else(|| {
+                                format_err!("the node {} uses the unknown model {}", child, model_index)
+                            })?;
+                            // Move the model so its pivot lands on the translation.
+                            let position = translation - quaternion * model_pivot(model);
+
+                            nodes.push(VoxSceneNode {
+                                name: name.clone(),
+                                parent,
+                                position: Point3::new(position.x, position.y, position.z),
+                                rotation: quaternion,
+                                model: Some(*model_index),
+                            });
+                        }
+                    }
+                    Some(SceneNode::Group { children }) => {
+                        visited.insert(*child);
+
+                        nodes.push(VoxSceneNode {
+                            name,
+                            parent,
+                            position: Point3::new(translation.x, translation.y, translation.z),
+                            rotation: quaternion,
+                            model: None,
+                        });
+
+                        let index = nodes.len() - 1;
+                        for grand_child in children.iter().rev() {
+                            stack.push((*grand_child, Some(index)));
+                        }
+                    }
+                    _ => {
+                        return Err(format_err!("the transform node {} has an invalid child {}", node_id, child));
+                    }
+                }
+            }
+            _ => {
+                return Err(format_err!("the node {} is missing or is not a transform node", node_id));
+            }
         }
+    }
 
-        let model = &dot_vox_data.models[0];
-        let mut chunk = Chunk::new(model.size.x as u8, model.size.z as u8, model.size.y as u8);
+    Ok(nodes)
+}
 
-        for &voxel in model.voxels.iter() {
-            chunk.set_voxel(
-                i64::from(voxel.x),
-                i64::from(voxel.z),
-                i64::from(model.size.y - u32::from(voxel.y) - 1),
-                voxel.i,
-            )?;
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if count > self.bytes.len() {
+            return Err(format_err!("unexpected end of file"));
         }
 
-        Ok(chunk)
+        let (read, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+
+        Ok(read)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.read_bytes(4)?;
+
+        Ok(u32::from(bytes[0])
+            | u32::from(bytes[1]) << 8
+            | u32::from(bytes[2]) << 16
+            | u32::from(bytes[3]) << 24)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, Error> {
+        Ok(self.read_u32()? as i32)
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        let size = self.read_u32()? as usize;
+
+        Ok(String::from_utf8_lossy(self.read_bytes(size)?).into_owned())
+    }
+
+    fn read_dict(&mut self) -> Result<HashMap<String, String>, Error> {
+        let count = self.read_u32()?;
+        let mut dict = HashMap::new();
+
+        for _ in 0..count {
+            let key = self.read_string()?;
+            let value = self.read_string()?;
+            dict.insert(key, value);
+        }
+
+        Ok(dict)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::VoxLoader;
+    use super::dot_vox::{Model, Size};
+    use super::{decode_rotation, flatten_scene_nodes, parse_scene_nodes, SceneNode, VoxLoader};
+    use cgmath::{EuclideanSpace, InnerSpace, Matrix3, SquareMatrix, Vector3};
     use errors::print_errors_and_exit;
+    use std::collections::HashMap;
 
     #[test]
     fn load_vox() {
@@ -73,7 +476,7 @@ mod tests {
             print_errors_and_exit(e);
         }
 
-        let chunk = result.unwrap();
+        let (chunk, _palette) = result.unwrap();
 
         assert_eq!(chunk.get_voxel(0, 0, 2).unwrap(), 78);
         assert_eq!(chunk.get_voxel(0, 0, 1).unwrap(), 78);
@@ -82,4 +485,184 @@ mod tests {
         assert_eq!(chunk.get_voxel(0, 1, 0).unwrap(), 78);
         assert_eq!(chunk.get_voxel(1, 3, 2).unwrap(), 0);
     }
+
+    #[test]
+    fn decode_rotations() {
+        // 0b0000100 is the identity: row 0 -> x, row 1 -> y, row 2 -> z, all positive.
+        assert_eq!(decode_rotation(0b000_0100).unwrap(), Matrix3::identity());
+        assert!(decode_rotation(0b000_0000).is_err());
+
+        // Row 0 -> y, row 1 -> x, third row negative.
+        let rotation = decode_rotation(0b100_0001).unwrap();
+        assert_eq!(rotation * Vector3::new(1.0, 2.0, 3.0), Vector3::new(2.0, 1.0, -3.0));
+    }
+
+    fn push_string(bytes: &mut Vec<u8>, value: &str) {
+        push_i32(bytes, value.len() as i32);
+        bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn push_i32(bytes: &mut Vec<u8>, value: i32) {
+        let value = value as u32;
+        bytes.extend_from_slice(&[
+            value as u8,
+            (value >> 8) as u8,
+            (value >> 16) as u8,
+            (value >> 24) as u8,
+        ]);
+    }
+
+    fn push_chunk(bytes: &mut Vec<u8>, id: &[u8], content: &[u8]) {
+        bytes.extend_from_slice(id);
+        push_i32(bytes, content.len() as i32);
+        push_i32(bytes, 0);
+        bytes.extend_from_slice(content);
+    }
+
+    #[test]
+    fn parse_scene_graph() {
+        let mut root_transform = Vec::new();
+        push_i32(&mut root_transform, 0);
+        push_i32(&mut root_transform, 0);
+        push_i32(&mut root_transform, 1);
+        push_i32(&mut root_transform, -1);
+        push_i32(&mut root_transform, -1);
+        push_i32(&mut root_transform, 1);
+        push_i32(&mut root_transform, 0);
+
+        let mut group = Vec::new();
+        push_i32(&mut group, 1);
+        push_i32(&mut group, 0);
+        push_i32(&mut group, 1);
+        push_i32(&mut group, 2);
+
+        let mut transform = Vec::new();
+        push_i32(&mut transform, 2);
+        push_i32(&mut transform, 1);
+        push_string(&mut transform, "_name");
+        push_string(&mut transform, "head");
+        push_i32(&mut transform, 3);
+        push_i32(&mut transform, -1);
+        push_i32(&mut transform, 0);
+        push_i32(&mut transform, 1);
+        push_i32(&mut transform, 2);
+        push_string(&mut transform, "_t");
+        push_string(&mut transform, "1 -2 3");
+        push_string(&mut transform, "_r");
+        push_string(&mut transform, "4");
+
+        let mut shape = Vec::new();
+        push_i32(&mut shape, 3);
+        push_i32(&mut shape, 0);
+        push_i32(&mut shape, 1);
+        push_i32(&mut shape, 0);
+        push_i32(&mut shape, 0);
+
+        let mut children = Vec::new();
+        push_chunk(&mut children, b"nTRN", &root_transform);
+        push_chunk(&mut children, b"nGRP", &group);
+        push_chunk(&mut children, b"nTRN", &transform);
+        push_chunk(&mut children, b"nSHP", &shape);
+
+        let mut bytes = b"VOX ".to_vec();
+        push_i32(&mut bytes, 150);
+        bytes.extend_from_slice(b"MAIN");
+        push_i32(&mut bytes, 0);
+        push_i32(&mut bytes, children.len() as i32);
+        bytes.extend_from_slice(&children);
+
+        let nodes = parse_scene_nodes(&bytes).unwrap();
+
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[&1], SceneNode::Group { children: vec![2] });
+        assert_eq!(nodes[&3], SceneNode::Shape { models: vec![0] });
+        assert_eq!(
+            nodes[&2],
+            SceneNode::Transform {
+                name: Some("head".to_string()),
+                child: 3,
+                translation: Vector3::new(1.0, -2.0, 3.0),
+                rotation: Matrix3::identity(),
+            }
+        );
+    }
+
+    fn model(x: u32, y: u32, z: u32) -> Model {
+        Model {
+            size: Size { x, y, z },
+            voxels: Vec::new(),
+        }
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 0.0001, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn place_rotated_models() {
+        let mut scene_nodes = HashMap::new();
+        // A quarter turn around the up axis of MagicaVoxel: x -> y, y -> -x.
+        scene_nodes.insert(
+            0,
+            SceneNode::Transform {
+                name: Some("box".to_string()),
+                child: 1,
+                translation: Vector3::new(1.0, 2.0, 3.0),
+                rotation: decode_rotation(0b001_0001).unwrap(),
+            },
+        );
+        scene_nodes.insert(1, SceneNode::Shape { models: vec![0] });
+
+        let nodes = flatten_scene_nodes("scene.vox", &scene_nodes, &[model(4, 2, 6)]).unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name, "box");
+        assert_eq!(nodes[0].parent, None);
+        assert_eq!(nodes[0].model, Some(0));
+        // Our x axis is the x axis of MagicaVoxel, its y axis is our -z axis.
+        assert_close(nodes[0].rotation * Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_close(nodes[0].rotation * Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        // The translation (1, 3, -2) minus the rotated pivot (1.5, 2.5, 0.5) of the chunk.
+        assert_close(nodes[0].position.to_vec(), Vector3::new(0.5, 0.5, -0.5));
+    }
+
+    #[test]
+    fn flatten_nested_groups() {
+        let transform = |name: &str, child: i32, translation: Vector3<f32>| SceneNode::Transform {
+            name: Some(name.to_string()),
+            child,
+            translation,
+            rotation: Matrix3::identity(),
+        };
+        let mut scene_nodes = HashMap::new();
+        scene_nodes.insert(
+            0,
+            SceneNode::Transform {
+                name: None,
+                child: 1,
+                translation: Vector3::new(0.0, 0.0, 0.0),
+                rotation: Matrix3::identity(),
+            },
+        );
+        scene_nodes.insert(1, SceneNode::Group { children: vec![2, 4] });
+        scene_nodes.insert(2, transform("arm", 3, Vector3::new(2.0, 0.0, 0.0)));
+        scene_nodes.insert(3, SceneNode::Group { children: vec![6] });
+        scene_nodes.insert(6, transform("hand", 7, Vector3::new(0.0, 0.0, 1.0)));
+        scene_nodes.insert(7, SceneNode::Shape { models: vec![0] });
+        scene_nodes.insert(4, transform("leg", 5, Vector3::new(0.0, 4.0, 0.0)));
+        scene_nodes.insert(5, SceneNode::Shape { models: vec![0] });
+
+        let nodes = flatten_scene_nodes("scene.vox", &scene_nodes, &[model(1, 1, 1)]).unwrap();
+        let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+        let parents: Vec<Option<usize>> = nodes.iter().map(|node| node.parent).collect();
+
+        assert_eq!(names, vec!["scene.vox #0", "arm", "hand", "leg"]);
+        assert_eq!(parents, vec![None, Some(0), Some(1), Some(0)]);
+        assert_eq!(nodes[1].model, None);
+        assert_eq!(nodes[2].model, Some(0));
+        assert_close(nodes[1].position.to_vec(), Vector3::new(2.0, 0.0, 0.0));
+        // The positions are relative to the parent, minus the pivot (-0.5, -0.5, 0.5) of the chunk.
+        assert_close(nodes[2].position.to_vec(), Vector3::new(0.5, 1.5, -0.5));
+        assert_close(nodes[3].position.to_vec(), Vector3::new(0.5, 0.5, -4.5));
+    }
 }
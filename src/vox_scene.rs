@@ -0,0 +1,81 @@
+use asset_manager::AssetManager;
+use cgmath::{Point3, Quaternion};
+use components::mesh_render::MeshRender;
+use components::parent::Parent;
+use components::transform::Transform;
+use failure::Error;
+use material::Material;
+use palette::Palette;
+use specs::{Builder, Entity, World};
+use voxel::chunk::Chunk;
+
+/// A node of a scene loaded from a MagicaVoxel file, already converted to our coordinates.
+#[derive(Debug, Clone)]
+pub struct VoxSceneNode {
+    pub name: String,
+    /// Index of the parent node in `VoxScene::nodes`.
+    pub parent: Option<usize>,
+    pub position: Point3<f32>,
+    pub rotation: Quaternion<f32>,
+    /// Index of the model in `VoxScene::models`.
+    pub model: Option<usize>,
+}
+
+/// Every model of a MagicaVoxel file along with the hierarchy placing them.
+#[derive(Debug, Clone)]
+pub struct VoxScene {
+    pub models: Vec<Chunk>,
+    /// Parent nodes appear before their children.
+    pub nodes: Vec<VoxSceneNode>,
+    pub palette: Palette,
+}
+
+/// Load a `.vox` file of the assets directory with `AssetManager::load_vox_scene()`, so its
+/// meshes are rebuilt when the file changes, and create one entity per node, with a `Transform`
+/// and a `Parent` component, so the `Transformation` system lays them out as authored. The meshes
+/// are rendered with `material` using the palette of the file. Return the entities without parent.
+pub fn spawn_vox_scene(
+    world: &mut World,
+    asset_name: &str,
+    material: &Material,
+) -> Result<Vec<Entity>, Error> {
+    let (material, vox_scene) = {
+        let mut assets = world.write_resource::<AssetManager>();
+        let handle = assets.load_vox_scene(asset_name)?;
+        let vox_scene = assets
+            .vox_scene(handle)
+            .expect("the scene should be loaded")
+            .clone();
+
+        (material.with_palette(vox_scene.palette), vox_scene)
+    };
+
+    let mut entities: Vec<Entity> = Vec::with_capacity(vox_scene.nodes.len());
+
+    for node in &vox_scene.nodes {
+        let mut transform = Transform::new(node.position, &node.name);
+        transform.set_local_rotation(node.rotation);
+
+        let mut entity_builder = world.create_entity().with(transform);
+
+        if let Some(parent) = node.parent {
+            entity_builder = entity_builder.with(Parent {
+                entity: entities[parent],
+            });
+        }
+
+        if let Some(Some(mesh)) = node.model.map(|model| vox_scene.meshes[model]) {
+            entity_builder = entity_builder.with(MeshRender::new(material.clone(), mesh));
+        }
+
+        entities.push(entity_builder.build());
+    }
+
+    Ok(vox_scene
+        .nodes
+        .iter()
+        .zip(entities)
+        .filter(|(node, _)| node.parent.is_none())
+        .map(|(_, entity)| entity)
+        .collect())
+}
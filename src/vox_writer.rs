@@ -0,0 +1,233 @@
+use failure::Error;
+use palette::Palette;
+use std::fs::File;
+use std::io::Write;
+use vox_loader::{ASSETS_DIRECTORY, SUPPORTED_VOX_VERSION};
+use voxel::chunk::Chunk;
+use voxel::is_solid;
+
+/// The highest voxel value that can be saved, MagicaVoxel color indices are shifted by one.
+const MAX_VOXEL_VALUE: u8 = 254;
+
+#[derive(Default)]
+pub struct VoxWriter {}
+
+impl VoxWriter {
+    /// Save the chunks and the palette as a MagicaVoxel file in the assets directory.
+    pub fn save(asset_name: &str, chunks: &[Chunk], palette: &Palette) -> Result<(), Error> {
+        Self::save_file(&format!("{}/{}", ASSETS_DIRECTORY, asset_name), chunks, palette)
+    }
+
+    /// Same as `save` but with a path which is not relative to the assets directory.
+    pub fn save_file(filepath: &str, chunks: &[Chunk], palette: &Palette) -> Result<(), Error> {
+        let bytes = Self::to_bytes(chunks, palette)
+            .map_err(|error| format_err!("Cannot save the asset file at '{}': {}", filepath, error))?;
+
+        File::create(filepath)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|error| {
+                format_err!(
+                    "Tryed to write the asset file at '{}' and got the following error: {}.",
+                    filepath,
+                    error,
+                )
+            })?;
+
+        Ok(())
+    }
+
+    /// Serialize the chunks into a version 150 vox file. Each chunk is a model, placed at the
+    /// origin by a minimal scene graph so MagicaVoxel displays all of them.
+    pub fn to_bytes(chunks: &[Chunk], palette: &Palette) -> Result<Vec<u8>, Error> {
+        if chunks.is_empty() {
+            return Err(format_err!("there is no chunk to save"));
+        }
+
+        let mut children = Vec::new();
+
+        for chunk in chunks {
+            write_model(&mut children, chunk)?;
+        }
+
+        write_scene_graph(&mut children, chunks.len());
+
+        let mut palette_content = Vec::with_capacity(palette.colors().len() * 4);
+        for color in palette.colors() {
+            write_u32(&mut palette_content, *color);
+        }
+        write_chunk(&mut children, b"RGBA", &palette_content);
+
+        let mut bytes = Vec::with_capacity(children.len() + 20);
+        bytes.extend_from_slice(b"VOX ");
+        write_u32(&mut bytes, SUPPORTED_VOX_VERSION);
+        bytes.extend_from_slice(b"MAIN");
+        write_u32(&mut bytes, 0);
+        write_u32(&mut bytes, children.len() as u32);
+        bytes.extend_from_slice(&children);
+
+        Ok(bytes)
+    }
+}
+
+/// Write the `SIZE` and `XYZI` chunks of a model. This undoes the axis swap of `VoxLoader`:
+/// MagicaVoxel is z-up while we are y-up.
+fn write_model(bytes: &mut Vec<u8>, chunk: &Chunk) -> Result<(), Error> {
+    let mut size_content = Vec::with_capacity(12);
+    write_u32(&mut size_content, u32::from(chunk.size_x));
+    write_u32(&mut size_content, u32::from(chunk.size_z));
+    write_u32(&mut size_content, u32::from(chunk.size_y));
+    write_chunk(bytes, b"SIZE", &size_content);
+
+    let mut voxels = Vec::new();
+
+    for x in 0..chunk.size_x {
+        for y in 0..chunk.size_y {
+            for z in 0..chunk.size_z {
+                let voxel = chunk.get_voxel(i64::from(x), i64::from(y), i64::from(z))?;
+
+                if !is_solid(voxel) {
+                    continue;
+                }
+
+                if voxel > MAX_VOXEL_VALUE {
+                    return Err(format_err!(
+                        "the voxel at ({}, {}, {}) has the value {}, the maximum is {}",
+                        x,
+                        y,
+                        z,
+                        voxel,
+                        MAX_VOXEL_VALUE,
+                    ));
+                }
+
+                // The file color indices start at 1, `dot_vox` shifts them back when loading.
+                voxels.extend_from_slice(&[x, chunk.size_z - z - 1, y, voxel + 1]);
+            }
+        }
+    }
+
+    let mut xyzi_content = Vec::with_capacity(voxels.len() + 4);
+    write_u32(&mut xyzi_content, (voxels.len() / 4) as u32);
+    xyzi_content.extend_from_slice(&voxels);
+    write_chunk(bytes, b"XYZI", &xyzi_content);
+
+    Ok(())
+}
+
+/// Write a root transform, a group and one transform and shape per model.
+fn write_scene_graph(bytes: &mut Vec<u8>, model_count: usize) {
+    let model_count = model_count as u32;
+    let mut root_transform = Vec::new();
+    write_transform_node(&mut root_transform, 0, 1, -1);
+    write_chunk(bytes, b"nTRN", &root_transform);
+
+    let mut group = Vec::new();
+    write_u32(&mut group, 1);
+    write_u32(&mut group, 0);
+    write_u32(&mut group, model_count);
+    for model in 0..model_count {
+        write_u32(&mut group, 2 + model * 2);
+    }
+    write_chunk(bytes, b"nGRP", &group);
+
+    for model in 0..model_count {
+        let node_id = 2 + model * 2;
+
+        let mut transform = Vec::new();
+        write_transform_node(&mut transform, node_id, node_id + 1, 0);
+        write_chunk(bytes, b"nTRN", &transform);
+
+        let mut shape = Vec::new();
+        write_u32(&mut shape, node_id + 1);
+        write_u32(&mut shape, 0);
+        write_u32(&mut shape, 1);
+        write_u32(&mut shape, model);
+        write_u32(&mut shape, 0);
+        write_chunk(bytes, b"nSHP", &shape);
+    }
+}
+
+fn write_transform_node(bytes: &mut Vec<u8>, node_id: u32, child_id: u32, layer_id: i32) {
+    write_u32(bytes, node_id);
+    // Attributes.
+    write_u32(bytes, 0);
+    write_u32(bytes, child_id);
+    // Reserved id.
+    write_u32(bytes, -1_i32 as u32);
+    write_u32(bytes, layer_id as u32);
+    // One frame without attributes.
+    write_u32(bytes, 1);
+    write_u32(bytes, 0);
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8], content: &[u8]) {
+    bytes.extend_from_slice(id);
+    write_u32(bytes, content.len() as u32);
+    write_u32(bytes, 0);
+    bytes.extend_from_slice(content);
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&[
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VoxWriter;
+    use palette::Palette;
+    use temporary_file::TemporaryFile;
+    use vox_loader::VoxLoader;
+    use voxel::chunk::Chunk;
+
+    #[test]
+    fn load_save_load() {
+        let (chunk, palette) = VoxLoader::load("chr_old.vox").unwrap();
+        let file = TemporaryFile::new("load_save_load.vox");
+        let filepath = file.path();
+
+        VoxWriter::save_file(filepath, &[chunk.clone()], &palette).unwrap();
+        let (saved_chunk, saved_palette) = VoxLoader::load_file(filepath).unwrap();
+
+        assert_eq!(
+            (saved_chunk.size_x, saved_chunk.size_y, saved_chunk.size_z),
+            (chunk.size_x, chunk.size_y, chunk.size_z)
+        );
+        assert_eq!(saved_chunk.voxels, chunk.voxels, "it should save the same voxels");
+        assert_eq!(saved_palette, palette, "it should save the same palette");
+    }
+
+    #[test]
+    fn save_multiple_chunks() {
+        let mut chunk = Chunk::new(2, 3, 4);
+        let mut chunk2 = Chunk::new(1, 1, 1);
+
+        chunk.set_voxel(1, 2, 3, 7).unwrap();
+        chunk.set_voxel(0, 0, 0, 254).unwrap();
+        chunk2.set_voxel(0, 0, 0, 1).unwrap();
+
+        let file = TemporaryFile::new("save_multiple_chunks.vox");
+        let filepath = file.path();
+
+        VoxWriter::save_file(filepath, &[chunk.clone(), chunk2.clone()], &Palette::default()).unwrap();
+        let scene = VoxLoader::load_scene_file(filepath).unwrap();
+
+        assert_eq!(scene.models.len(), 2);
+        assert_eq!(scene.models[0].voxels, chunk.voxels);
+        assert_eq!(scene.models[1].voxels, chunk2.voxels);
+        assert_eq!(scene.nodes.iter().filter(|node| node.model.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn reject_unsavable_voxels() {
+        let mut chunk = Chunk::new(1, 1, 1);
+        chunk.set_voxel(0, 0, 0, 255).unwrap();
+
+        assert!(VoxWriter::to_bytes(&[chunk], &Palette::default()).is_err());
+        assert!(VoxWriter::to_bytes(&[], &Palette::default()).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use super::direction::Direction;
+use super::position::Position;
+use super::voxel_geometry::face_corners;
+
+/// The ambient occlusion of a vertex no voxel occludes.
+pub const UNOCCLUDED: u8 = 3;
+
+/// The ambient occlusion of a corner of a face, from 0 when it is fully occluded to `UNOCCLUDED`.
+/// `side_1` and `side_2` tell whether the voxels along the two edges of the corner are solid and
+/// `corner` whether the voxel diagonal to it is. Two solid sides hide the corner voxel, so they
+/// fully occlude the corner on their own.
+pub fn vertex_ambient_occlusion(side_1: bool, side_2: bool, corner: bool) -> u8 {
+    if side_1 && side_2 {
+        return 0;
+    }
+
+    UNOCCLUDED - (u8::from(side_1) + u8::from(side_2) + u8::from(corner))
+}
+
+/// The ambient occlusion of each vertex of the face of the voxel at `position` facing `direction`,
+/// in the order of `face_corners`. It looks up the voxels around the face in the layer in front of
+/// it with `is_solid`.
+pub fn face_ambient_occlusion(
+    position: &Position,
+    direction: Direction,
+    is_solid: &dyn Fn(&Position) -> bool,
+) -> [u8; 4] {
+    let front = position.add_direction(direction);
+    let mut ambient_occlusion = [UNOCCLUDED; 4];
+
+    for (vertex_occlusion, corner) in
+        ambient_occlusion.iter_mut().zip(face_corners(direction).iter())
+    {
+        // Split the corner along the two axes of the face.
+        let (side_1, side_2) = match direction {
+            Direction::North | Direction::South => {
+                (Position::new(corner.x, 0, 0), Position::new(0, corner.y, 0))
+            }
+            Direction::East | Direction::West => {
+                (Position::new(0, corner.y, 0), Position::new(0, 0, corner.z))
+            }
+            Direction::Up | Direction::Down => {
+                (Position::new(corner.x, 0, 0), Position::new(0, 0, corner.z))
+            }
+        };
+
+        *vertex_occlusion = vertex_ambient_occlusion(
+            is_solid(&(front + side_1)),
+            is_solid(&(front + side_2)),
+            is_solid(&(front + side_1 + side_2)),
+        );
+    }
+
+    ambient_occlusion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{face_ambient_occlusion, vertex_ambient_occlusion, UNOCCLUDED};
+    use voxel::direction::Direction;
+    use voxel::position::Position;
+
+    #[test]
+    fn count_the_occluding_voxels() {
+        assert_eq!(vertex_ambient_occlusion(false, false, false), UNOCCLUDED);
+        assert_eq!(vertex_ambient_occlusion(false, false, true), 2);
+        assert_eq!(vertex_ambient_occlusion(true, false, false), 2);
+        assert_eq!(vertex_ambient_occlusion(false, true, true), 1);
+        assert_eq!(vertex_ambient_occlusion(true, true, false), 0);
+        assert_eq!(vertex_ambient_occlusion(true, true, true), 0);
+    }
+
+    #[test]
+    fn occlude_the_corners_next_to_solid_voxels() {
+        // A floor voxel at the origin with a wall voxel above its west side, and another one on
+        // its north-east diagonal.
+        let solid_positions = [Position::new(-1, 1, 0), Position::new(1, 1, -1)];
+        let is_solid = |position: &Position| solid_positions.contains(position);
+
+        // The corners of the top face: (-x, -z), (-x, +z), (+x, +z), (+x, -z).
+        assert_eq!(
+            face_ambient_occlusion(&Position::new(0, 0, 0), Direction::Up, &is_solid),
+            [2, 2, UNOCCLUDED, 2]
+        );
+        // The bottom face has nothing below it.
+        assert_eq!(
+            face_ambient_occlusion(&Position::new(0, 0, 0), Direction::Down, &is_solid),
+            [UNOCCLUDED; 4]
+        );
+    }
+
+    #[test]
+    fn fully_occlude_inner_corners() {
+        let solid_positions = [Position::new(-1, 1, 0), Position::new(0, 1, -1)];
+        let is_solid = |position: &Position| solid_positions.contains(position);
+
+        assert_eq!(
+            face_ambient_occlusion(&Position::new(0, 0, 0), Direction::Up, &is_solid),
+            [0, 2, UNOCCLUDED, 2]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use super::is_solid;
 use super::position::Position;
-use super::voxel_mesh_builder::build_mesh;
+use super::voxel_mesh_builder::{build_mesh, build_mesh_with_mode, MeshingMode};
 use failure::Fail;
 use mesh_data::MeshData;
 
@@ -16,8 +16,10 @@ pub struct Chunk {
     pub size_x: u8,
     pub size_y: u8,
     pub size_z: u8,
+    /// Writing the voxels directly does not mark the chunk as dirty, see `mark_dirty()`.
     pub voxels: Vec<u8>,
     mesh_data: Option<MeshData>,
+    is_dirty: bool,
 }
 
 // TODO:
@@ -35,6 +37,7 @@ impl Chunk {
             size_z,
             voxels: vec![0; size],
             mesh_data: None,
+            is_dirty: true,
         }
     }
 
@@ -68,7 +71,11 @@ impl Chunk {
             + (y * i64::from(self.size_x))
             + x;
 
-        self.voxels[index as usize] = i;
+        if self.voxels[index as usize] != i {
+            self.voxels[index as usize] = i;
+            self.is_dirty = true;
+        }
+
         Ok(())
     }
 
@@ -85,7 +92,43 @@ impl Chunk {
     }
 
     pub fn build_mesh(&self) -> MeshData {
-        build_mesh(&self)
+        build_mesh(self)
+    }
+
+    pub fn build_mesh_with_mode(&self, mode: MeshingMode) -> MeshData {
+        build_mesh_with_mode(self, mode)
+    }
+
+    /// Whether a voxel changed since the mesh data was last updated. New chunks are dirty.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.is_dirty = true;
+    }
+
+    /// Mark the mesh up to date, for a chunk whose mesh was built elsewhere, such as by
+    /// `VoxelWorld::update_chunk_mesh()`. `mesh_data()` is unchanged.
+    pub fn mark_clean(&mut self) {
+        self.is_dirty = false;
+    }
+
+    /// The mesh data built by the last `update_mesh_data()` call.
+    pub fn mesh_data(&self) -> Option<&MeshData> {
+        self.mesh_data.as_ref()
+    }
+
+    /// Rebuild the mesh data if the chunk is dirty and return it, or return `None` if it is up to
+    /// date.
+    pub fn update_mesh_data(&mut self, mode: MeshingMode) -> Option<&MeshData> {
+        if !self.is_dirty {
+            return None;
+        }
+
+        self.mesh_data = Some(build_mesh_with_mode(self, mode));
+        self.is_dirty = false;
+        self.mesh_data.as_ref()
     }
 
     // TODO: Improve error handling here
@@ -111,6 +154,7 @@ impl Chunk {
 #[cfg(test)]
 mod tests {
     use voxel::chunk::Chunk;
+    use voxel::voxel_mesh_builder::MeshingMode;
 
     #[test]
     fn set_voxel() {
@@ -132,4 +176,36 @@ mod tests {
 
         assert_eq!(voxel.unwrap(), 1, "it should get the correct voxel");
     }
+
+    #[test]
+    fn mark_dirty_when_a_voxel_changes() {
+        let mut chunk = Chunk::new(2, 2, 2);
+
+        assert!(chunk.is_dirty(), "a new chunk should be dirty");
+
+        chunk.update_mesh_data(MeshingMode::Greedy);
+        chunk.set_voxel(1, 1, 1, 0).unwrap();
+        chunk.set_voxel(2, 0, 0, 1).unwrap_err();
+
+        assert!(!chunk.is_dirty(), "it should stay clean when no voxel changes");
+
+        chunk.set_voxel(1, 1, 1, 1).unwrap();
+
+        assert!(chunk.is_dirty(), "it should be dirty once a voxel changes");
+    }
+
+    #[test]
+    fn update_mesh_data_of_dirty_chunks() {
+        let mut chunk = Chunk::new(2, 2, 2);
+
+        assert!(chunk.update_mesh_data(MeshingMode::Greedy).unwrap().vertices.is_empty());
+        assert!(chunk.update_mesh_data(MeshingMode::Greedy).is_none());
+
+        chunk.set_voxel(0, 0, 0, 1).unwrap();
+        let vertex_count = chunk.update_mesh_data(MeshingMode::Greedy).unwrap().vertices.len();
+
+        assert!(vertex_count > 0);
+        assert_eq!(chunk.mesh_data().unwrap().vertices.len(), vertex_count);
+        assert!(!chunk.is_dirty());
+    }
 }
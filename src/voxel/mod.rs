@@ -1,9 +1,11 @@
 pub mod chunk;
 pub mod direction;
 pub mod position;
+pub mod world;
 // TODO: remove pub?
 pub mod voxel_mesh_builder;
 mod voxel_geometry;
+mod ambient_occlusion;
 
 pub fn is_solid(voxel: u8) -> bool {
     voxel != 0
@@ -1,7 +1,7 @@
 use std::ops::Add;
 use super::direction::Direction;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
     pub x: i64,
     pub y: i64,
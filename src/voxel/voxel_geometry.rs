@@ -9,314 +9,114 @@ pub fn create_vertex_position_face(
     position: &Position,
     color_index: u8,
     direction: Direction,
+    ambient_occlusion: [u8; 4],
 ) {
-    match direction {
-        Direction::North => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(0.0, 0.0, -1.0);
-
-            // TODO: vertex values are set twice, the default one, then the vertex.
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
-
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
-
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
-        Direction::East => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(1.0, 0.0, 0.0);
-
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
-
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
-
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
-        Direction::South => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(0.0, 0.0, 1.0);
-
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
-
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
-
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
-        Direction::West => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(-1.0, 0.0, 0.0);
-
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
-
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
-
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
-        Direction::Up => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 + 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(0.0, 1.0, 0.0);
-
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
-
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
-
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
-        Direction::Down => {
-            let vertex_position_0 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-            let vertex_position_1 = Vector3::<f32>::new(
-                position.x as f32 - 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_2 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 - 0.5_f32,
-            );
-            let vertex_position_3 = Vector3::<f32>::new(
-                position.x as f32 + 0.5_f32,
-                position.y as f32 - 0.5_f32,
-                position.z as f32 + 0.5_f32,
-            );
-
-            let normal = Vector3::<f32>::new(0.0, -1.0, 0.0);
-
-            let mut vertex_0 = Vertex::default();
-            let mut vertex_1 = Vertex::default();
-            let mut vertex_2 = Vertex::default();
-            let mut vertex_3 = Vertex::default();
-
-            vertex_0.position = vertex_position_0;
-            vertex_1.position = vertex_position_1;
-            vertex_2.position = vertex_position_2;
-            vertex_3.position = vertex_position_3;
-
-            vertex_0.color_index = color_index;
-            vertex_1.color_index = color_index;
-            vertex_2.color_index = color_index;
-            vertex_3.color_index = color_index;
+    create_vertex_box_face(mesh_data, position, position, color_index, direction, ambient_occlusion);
+}
 
-            vertex_0.normal = normal;
-            vertex_1.normal = normal;
-            vertex_2.normal = normal;
-            vertex_3.normal = normal;
+/// Create the face of the box going from the voxel at `from` to the voxel at `to` (both
+/// included). `from` must be the minimum corner and `to` the maximum one. `ambient_occlusion` is
+/// the value of each vertex, in the order of `face_corners`.
+pub fn create_vertex_box_face(
+    mesh_data: &mut MeshData,
+    from: &Position,
+    to: &Position,
+    color_index: u8,
+    direction: Direction,
+    ambient_occlusion: [u8; 4],
+) {
+    let min_x = from.x as f32 - 0.5_f32;
+    let min_y = from.y as f32 - 0.5_f32;
+    let min_z = from.z as f32 - 0.5_f32;
+    let max_x = to.x as f32 + 0.5_f32;
+    let max_y = to.y as f32 + 0.5_f32;
+    let max_z = to.z as f32 + 0.5_f32;
+
+    let side = |sign: i64, min: f32, max: f32| if sign < 0 { min } else { max };
+    let normal = Position::from_direction(direction);
+    let normal = Vector3::<f32>::new(normal.x as f32, normal.y as f32, normal.z as f32);
+
+    for (corner, &vertex_ambient_occlusion) in face_corners(direction).iter().zip(ambient_occlusion.iter()) {
+        mesh_data.vertices.push(Vertex {
+            position: Vector3::<f32>::new(
+                side(corner.x, min_x, max_x),
+                side(corner.y, min_y, max_y),
+                side(corner.z, min_z, max_z),
+            ),
+            color_index,
+            normal,
+            ambient_occlusion: vertex_ambient_occlusion,
+        });
+    }
+}
 
-            mesh_data.vertices.push(vertex_0);
-            mesh_data.vertices.push(vertex_1);
-            mesh_data.vertices.push(vertex_2);
-            mesh_data.vertices.push(vertex_3);
-        }
+/// The corners of the face of a voxel facing `direction`, as the direction of each vertex from the
+/// center of the voxel. The vertices go around the face, so `0-2` and `1-3` are its diagonals.
+pub fn face_corners(direction: Direction) -> [Position; 4] {
+    match direction {
+        Direction::North => [
+            Position::new(1, -1, -1),
+            Position::new(1, 1, -1),
+            Position::new(-1, 1, -1),
+            Position::new(-1, -1, -1),
+        ],
+        Direction::East => [
+            Position::new(1, -1, -1),
+            Position::new(1, 1, -1),
+            Position::new(1, 1, 1),
+            Position::new(1, -1, 1),
+        ],
+        Direction::South => [
+            Position::new(-1, -1, 1),
+            Position::new(-1, 1, 1),
+            Position::new(1, 1, 1),
+            Position::new(1, -1, 1),
+        ],
+        Direction::West => [
+            Position::new(-1, -1, -1),
+            Position::new(-1, 1, -1),
+            Position::new(-1, 1, 1),
+            Position::new(-1, -1, 1),
+        ],
+        Direction::Up => [
+            Position::new(-1, 1, -1),
+            Position::new(-1, 1, 1),
+            Position::new(1, 1, 1),
+            Position::new(1, 1, -1),
+        ],
+        Direction::Down => [
+            Position::new(-1, -1, 1),
+            Position::new(-1, -1, -1),
+            Position::new(1, -1, -1),
+            Position::new(1, -1, 1),
+        ],
     }
 }
 
+/// Add the two triangles of the last quad. The quad is split along the diagonal whose vertices are
+/// the most occluded, otherwise the interpolation of the ambient occlusion would differ between
+/// the two halves of the quad depending on its orientation.
 pub fn add_quad_triangles(mesh_data: &mut MeshData) {
     let vertices_count = mesh_data.vertices.len() as i32;
-
-    mesh_data.indices.push(vertices_count - 4); // 0
-    mesh_data.indices.push(vertices_count - 3); // 1
-    mesh_data.indices.push(vertices_count - 2); // 2
-
-    mesh_data.indices.push(vertices_count - 4); // 0
-    mesh_data.indices.push(vertices_count - 2); // 2
-    mesh_data.indices.push(vertices_count - 1); // 3
+    let quad = &mesh_data.vertices[mesh_data.vertices.len() - 4..];
+    let occlusion_0_2 = quad[0].ambient_occlusion + quad[2].ambient_occlusion;
+    let occlusion_1_3 = quad[1].ambient_occlusion + quad[3].ambient_occlusion;
+
+    if occlusion_0_2 <= occlusion_1_3 {
+        mesh_data.indices.push(vertices_count - 4); // 0
+        mesh_data.indices.push(vertices_count - 3); // 1
+        mesh_data.indices.push(vertices_count - 2); // 2
+
+        mesh_data.indices.push(vertices_count - 4); // 0
+        mesh_data.indices.push(vertices_count - 2); // 2
+        mesh_data.indices.push(vertices_count - 1); // 3
+    } else {
+        mesh_data.indices.push(vertices_count - 3); // 1
+        mesh_data.indices.push(vertices_count - 2); // 2
+        mesh_data.indices.push(vertices_count - 1); // 3
+
+        mesh_data.indices.push(vertices_count - 3); // 1
+        mesh_data.indices.push(vertices_count - 1); // 3
+        mesh_data.indices.push(vertices_count - 4); // 0
+    }
 }
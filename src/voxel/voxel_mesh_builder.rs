@@ -1,10 +1,43 @@
 use mesh_data::MeshData;
+use super::ambient_occlusion::face_ambient_occlusion;
 use super::chunk::Chunk;
 use super::direction::Direction;
 use super::position::Position;
-use super::voxel_geometry::{add_quad_triangles, create_vertex_position_face};
+use super::voxel_geometry::{add_quad_triangles, create_vertex_box_face, create_vertex_position_face};
+
+/// The algorithm used to turn the voxels of a chunk into a mesh.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One quad per visible voxel face.
+    #[default]
+    Naive,
+    /// Coplanar adjacent faces sharing the same color are merged into larger rectangles.
+    Greedy,
+}
+
+pub fn build_mesh_with_mode(chunk: &Chunk, mode: MeshingMode) -> MeshData {
+    build_mesh_with_neighbours(chunk, mode, &|_| false)
+}
+
+/// Build the mesh of a chunk surrounded by other voxels. `is_neighbour_solid` is called with
+/// positions relative to the chunk which are out of its bounds, so faces touching a solid voxel
+/// of a neighbouring chunk are not built and the neighbouring voxels occlude the vertices.
+pub fn build_mesh_with_neighbours(
+    chunk: &Chunk,
+    mode: MeshingMode,
+    is_neighbour_solid: &dyn Fn(&Position) -> bool,
+) -> MeshData {
+    match mode {
+        MeshingMode::Naive => build_naive_mesh(chunk, is_neighbour_solid),
+        MeshingMode::Greedy => build_greedy_mesh_with_neighbours(chunk, is_neighbour_solid),
+    }
+}
 
 pub fn build_mesh(chunk: &Chunk) -> MeshData {
+    build_naive_mesh(chunk, &|_| false)
+}
+
+fn build_naive_mesh(chunk: &Chunk, is_neighbour_solid: &dyn Fn(&Position) -> bool) -> MeshData {
     // TODO: Instanciate mesh_data with_capacity.
     let mut mesh_data = MeshData::new();
 
@@ -12,7 +45,14 @@ pub fn build_mesh(chunk: &Chunk) -> MeshData {
         for y in 0..chunk.size_y {
             for z in 0..chunk.size_z {
                 if chunk.is_solid(i64::from(x), i64::from(y), i64::from(z)) {
-                    voxel_data(chunk, i64::from(x), i64::from(y), i64::from(z), &mut mesh_data);
+                    voxel_data(
+                        chunk,
+                        i64::from(x),
+                        i64::from(y),
+                        i64::from(z),
+                        &mut mesh_data,
+                        is_neighbour_solid,
+                    );
                 }
             }
         }
@@ -21,17 +61,422 @@ pub fn build_mesh(chunk: &Chunk) -> MeshData {
     mesh_data
 }
 
-fn voxel_data<'a>(chunk: &Chunk, x: i64, y: i64, z: i64, mesh_data: &'a mut MeshData) {
+fn voxel_data<'a>(
+    chunk: &Chunk,
+    x: i64,
+    y: i64,
+    z: i64,
+    mesh_data: &'a mut MeshData,
+    is_neighbour_solid: &dyn Fn(&Position) -> bool,
+) {
     let position: Position = Position::new(x, y, z);
 
-    for direction in Direction::iterator() {
-        let touching_pos: Position = position.add_direction(*direction);
+    let is_solid = |position: &Position| is_voxel_solid(chunk, position, is_neighbour_solid);
 
-        // Build the face if there is no touching cube or if is the side of the chunk.
-        if chunk.is_position_out_of_bound(&touching_pos) || chunk.is_position_air(&touching_pos) {
+    for direction in Direction::iterator() {
+        if is_face_visible(chunk, &position, *direction, is_neighbour_solid) {
             let color_index = chunk.get_voxel(x, y, z).unwrap();
-            create_vertex_position_face(mesh_data, &position, color_index, *direction);
+            let ambient_occlusion = face_ambient_occlusion(&position, *direction, &is_solid);
+            create_vertex_position_face(mesh_data, &position, color_index, *direction, ambient_occlusion);
             add_quad_triangles(mesh_data);
         }
     }
 }
+
+/// Build the face if there is no touching cube, either in the chunk or in its neighbours.
+fn is_face_visible(
+    chunk: &Chunk,
+    position: &Position,
+    direction: Direction,
+    is_neighbour_solid: &dyn Fn(&Position) -> bool,
+) -> bool {
+    let touching_pos: Position = position.add_direction(direction);
+
+    !is_voxel_solid(chunk, &touching_pos, is_neighbour_solid)
+}
+
+/// Whether the voxel at `position` is solid, either in the chunk or in its neighbours.
+fn is_voxel_solid(
+    chunk: &Chunk,
+    position: &Position,
+    is_neighbour_solid: &dyn Fn(&Position) -> bool,
+) -> bool {
+    if chunk.is_position_out_of_bound(position) {
+        is_neighbour_solid(position)
+    } else {
+        chunk.is_position_solid(position)
+    }
+}
+
+/// Build a mesh where the visible faces of each layer are merged into the biggest rectangles
+/// possible. Only faces with the same direction, the same color and the same ambient occlusion are
+/// merged together, so the resulting mesh covers exactly the same surface as the one from
+/// `build_mesh`.
+pub fn build_greedy_mesh(chunk: &Chunk) -> MeshData {
+    build_greedy_mesh_with_neighbours(chunk, &|_| false)
+}
+
+fn build_greedy_mesh_with_neighbours(
+    chunk: &Chunk,
+    is_neighbour_solid: &dyn Fn(&Position) -> bool,
+) -> MeshData {
+    let mut mesh_data = MeshData::new();
+    let is_solid = |position: &Position| is_voxel_solid(chunk, position, is_neighbour_solid);
+
+    for direction in Direction::iterator() {
+        let (layer_count, width, height) = layer_dimensions(chunk, *direction);
+        // The color and the ambient occlusion of the visible faces.
+        let mut mask: Vec<Option<(u8, [u8; 4])>> = vec![None; width * height];
+
+        for layer in 0..layer_count {
+            // Collect the visible faces of the layer.
+            for v in 0..height {
+                for u in 0..width {
+                    let position = layer_position(*direction, layer, u, v);
+
+                    mask[u + v * width] = if chunk.is_position_solid(&position)
+                        && is_face_visible(chunk, &position, *direction, is_neighbour_solid)
+                    {
+                        Some((
+                            chunk.get_voxel(position.x, position.y, position.z).unwrap(),
+                            face_ambient_occlusion(&position, *direction, &is_solid),
+                        ))
+                    } else {
+                        None
+                    };
+                }
+            }
+
+            // Merge the faces into rectangles, growing along `u` first then along `v`.
+            for v in 0..height {
+                let mut u = 0;
+
+                while u < width {
+                    let face = match mask[u + v * width] {
+                        Some(face) => face,
+                        None => {
+                            u += 1;
+                            continue;
+                        }
+                    };
+
+                    let mut quad_width = 1;
+                    while u + quad_width < width
+                        && mask[u + quad_width + v * width] == Some(face)
+                    {
+                        quad_width += 1;
+                    }
+
+                    let mut quad_height = 1;
+                    while v + quad_height < height
+                        && (u..u + quad_width)
+                            .all(|i| mask[i + (v + quad_height) * width] == Some(face))
+                    {
+                        quad_height += 1;
+                    }
+
+                    for j in v..v + quad_height {
+                        for i in u..u + quad_width {
+                            mask[i + j * width] = None;
+                        }
+                    }
+
+                    let from = layer_position(*direction, layer, u, v);
+                    let to = layer_position(
+                        *direction,
+                        layer,
+                        u + quad_width - 1,
+                        v + quad_height - 1,
+                    );
+
+                    let (color_index, ambient_occlusion) = face;
+                    create_vertex_box_face(
+                        &mut mesh_data,
+                        &from,
+                        &to,
+                        color_index,
+                        *direction,
+                        ambient_occlusion,
+                    );
+                    add_quad_triangles(&mut mesh_data);
+
+                    u += quad_width;
+                }
+            }
+        }
+    }
+
+    mesh_data
+}
+
+/// Return the number of layers facing `direction` and the size of each layer.
+fn layer_dimensions(chunk: &Chunk, direction: Direction) -> (usize, usize, usize) {
+    let size_x = chunk.size_x as usize;
+    let size_y = chunk.size_y as usize;
+    let size_z = chunk.size_z as usize;
+
+    match direction {
+        Direction::North | Direction::South => (size_z, size_x, size_y),
+        Direction::East | Direction::West => (size_x, size_z, size_y),
+        Direction::Up | Direction::Down => (size_y, size_x, size_z),
+    }
+}
+
+/// Convert a position in a layer facing `direction` to a position in the chunk.
+fn layer_position(direction: Direction, layer: usize, u: usize, v: usize) -> Position {
+    let (layer, u, v) = (layer as i64, u as i64, v as i64);
+
+    match direction {
+        Direction::North | Direction::South => Position::new(u, v, layer),
+        Direction::East | Direction::West => Position::new(layer, v, u),
+        Direction::Up | Direction::Down => Position::new(u, layer, v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_greedy_mesh, build_mesh, build_mesh_with_neighbours, MeshingMode};
+    use cgmath::prelude::*;
+    use cgmath::Vector3;
+    use mesh_data::MeshData;
+    use voxel::chunk::Chunk;
+
+    /// A unit face: its normal, the position of its minimum corner (doubled to stay on integers),
+    /// its color and the ambient occlusion of its vertices.
+    type UnitFace = ((i64, i64, i64), (i64, i64, i64), u8, [u8; 4]);
+
+    /// Split every quad of the mesh into the unit voxel faces it covers.
+    fn unit_faces(mesh_data: &MeshData) -> Vec<UnitFace> {
+        let mut faces = Vec::new();
+
+        for quad in mesh_data.vertices.chunks(4) {
+            let normal = quad[0].normal;
+            let min = quad.iter().fold(quad[0].position, |acc, vertex| {
+                Vector3::new(
+                    acc.x.min(vertex.position.x),
+                    acc.y.min(vertex.position.y),
+                    acc.z.min(vertex.position.z),
+                )
+            });
+            let max = quad.iter().fold(quad[0].position, |acc, vertex| {
+                Vector3::new(
+                    acc.x.max(vertex.position.x),
+                    acc.y.max(vertex.position.y),
+                    acc.z.max(vertex.position.z),
+                )
+            });
+            let size = max - min;
+            let ambient_occlusion = [
+                quad[0].ambient_occlusion,
+                quad[1].ambient_occlusion,
+                quad[2].ambient_occlusion,
+                quad[3].ambient_occlusion,
+            ];
+            let steps = |extent: f32| (extent.round() as i64).max(1);
+
+            for i in 0..steps(size.x) {
+                for j in 0..steps(size.y) {
+                    for k in 0..steps(size.z) {
+                        faces.push((
+                            (normal.x as i64, normal.y as i64, normal.z as i64),
+                            (
+                                ((min.x + i as f32) * 2.0) as i64,
+                                ((min.y + j as f32) * 2.0) as i64,
+                                ((min.z + k as f32) * 2.0) as i64,
+                            ),
+                            quad[0].color_index,
+                            ambient_occlusion,
+                        ));
+                    }
+                }
+            }
+        }
+
+        faces.sort();
+        faces
+    }
+
+    /// Return, for each face normal, whether the triangle winding agrees with the normal.
+    fn triangle_orientations(mesh_data: &MeshData) -> Vec<((i64, i64, i64), bool)> {
+        let mut orientations: Vec<((i64, i64, i64), bool)> = mesh_data
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let vertex_0 = &mesh_data.vertices[triangle[0] as usize];
+                let vertex_1 = &mesh_data.vertices[triangle[1] as usize];
+                let vertex_2 = &mesh_data.vertices[triangle[2] as usize];
+                let winding_normal = (vertex_1.position - vertex_0.position)
+                    .cross(vertex_2.position - vertex_0.position);
+                let normal = vertex_0.normal;
+
+                (
+                    (normal.x as i64, normal.y as i64, normal.z as i64),
+                    winding_normal.dot(normal) > 0.0,
+                )
+            })
+            .collect();
+
+        orientations.sort();
+        orientations.dedup();
+        orientations
+    }
+
+    fn assert_same_surface(chunk: &Chunk) {
+        let naive_mesh = build_mesh(chunk);
+        let greedy_mesh = build_greedy_mesh(chunk);
+
+        assert_eq!(
+            unit_faces(&naive_mesh),
+            unit_faces(&greedy_mesh),
+            "the greedy mesh should cover the same surface as the naive one"
+        );
+        assert!(
+            greedy_mesh.vertices.len() <= naive_mesh.vertices.len(),
+            "the greedy mesh should not have more vertices than the naive one"
+        );
+        assert_eq!(greedy_mesh.indices.len() / 6, greedy_mesh.vertices.len() / 4);
+
+        assert_eq!(
+            triangle_orientations(&naive_mesh),
+            triangle_orientations(&greedy_mesh),
+            "the greedy mesh triangles should have the same winding as the naive ones"
+        );
+    }
+
+    #[test]
+    fn greedy_mesh_merges_flat_floor() {
+        let mut chunk = Chunk::new(32, 1, 32);
+
+        for x in 0..32 {
+            for z in 0..32 {
+                chunk.set_voxel(x, 0, z, 1).unwrap();
+            }
+        }
+
+        let greedy_mesh = build_greedy_mesh(&chunk);
+
+        assert_eq!(greedy_mesh.vertices.len(), 6 * 4, "it should emit one quad per side");
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn greedy_mesh_keeps_colors_apart() {
+        let mut chunk = Chunk::new(4, 1, 1);
+
+        chunk.set_voxel(0, 0, 0, 1).unwrap();
+        chunk.set_voxel(1, 0, 0, 1).unwrap();
+        chunk.set_voxel(2, 0, 0, 2).unwrap();
+        chunk.set_voxel(3, 0, 0, 2).unwrap();
+
+        let greedy_mesh = build_greedy_mesh(&chunk);
+
+        // Up, down, north and south faces are split by color, east and west are single voxels.
+        assert_eq!(greedy_mesh.vertices.len(), (4 * 2 + 2) * 4);
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn greedy_mesh_covers_same_surface_as_naive_mesh() {
+        let mut chunk = Chunk::new(7, 5, 6);
+        let mut seed: u32 = 12345;
+
+        for x in 0..7 {
+            for y in 0..5 {
+                for z in 0..6 {
+                    // Small deterministic LCG so the test does not depend on a random crate.
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let value = (seed >> 16) % 4;
+                    chunk.set_voxel(x, y, z, value as u8).unwrap();
+                }
+            }
+        }
+
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn occlude_the_vertices_in_corners() {
+        // A floor with a wall along its west side.
+        let mut chunk = Chunk::new(3, 2, 3);
+
+        for x in 0..3 {
+            for z in 0..3 {
+                chunk.set_voxel(x, 0, z, 1).unwrap();
+            }
+        }
+        for z in 0..3 {
+            chunk.set_voxel(0, 1, z, 1).unwrap();
+        }
+
+        let mesh_data = build_mesh(&chunk);
+        let floor_vertex = |x: f32, z: f32| {
+            mesh_data
+                .vertices
+                .iter()
+                .filter(|vertex| vertex.normal == Vector3::unit_y())
+                .filter(|vertex| vertex.position == Vector3::new(x, 0.5, z))
+                .map(|vertex| vertex.ambient_occlusion)
+                .min()
+                .unwrap()
+        };
+
+        assert_eq!(floor_vertex(0.5, 0.5), 1, "the floor along the wall should be occluded");
+        assert_eq!(floor_vertex(1.5, 0.5), 3, "the floor away from the wall should not be occluded");
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn occlude_the_vertices_with_neighbouring_chunks() {
+        let mut chunk = Chunk::new(1, 1, 1);
+        chunk.set_voxel(0, 0, 0, 1).unwrap();
+
+        // A solid voxel over the east side of the chunk.
+        let mesh_data = build_mesh_with_neighbours(&chunk, MeshingMode::Naive, &|position| {
+            position.x == 1 && position.y == 1 && position.z == 0
+        });
+        let top_face: Vec<u8> = mesh_data
+            .vertices
+            .iter()
+            .filter(|vertex| vertex.normal == Vector3::unit_y())
+            .map(|vertex| vertex.ambient_occlusion)
+            .collect();
+
+        assert_eq!(top_face, vec![3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn split_quads_along_the_most_occluded_diagonal() {
+        // A voxel whose top face is only occluded on its -x, +z corner, the second vertex.
+        let mut chunk = Chunk::new(2, 2, 2);
+        chunk.set_voxel(1, 0, 0, 1).unwrap();
+        chunk.set_voxel(0, 1, 1, 1).unwrap();
+
+        let mesh_data = build_mesh(&chunk);
+        let quad_index = mesh_data
+            .vertices
+            .chunks(4)
+            .position(|quad| quad[0].normal == Vector3::unit_y() && quad[0].position.y == 0.5)
+            .unwrap();
+        let quad = &mesh_data.vertices[quad_index * 4..quad_index * 4 + 4];
+        let triangles = &mesh_data.indices[quad_index * 6..quad_index * 6 + 6];
+        let occluded_vertex = quad_index as i32 * 4 + 1;
+
+        assert_eq!(quad[1].ambient_occlusion, 2);
+        assert!([0, 2, 3].iter().all(|&index| quad[index].ambient_occlusion == 3));
+        assert_eq!(
+            triangles.iter().filter(|&&index| index == occluded_vertex).count(),
+            2,
+            "the quad should be split along the diagonal of the occluded vertex"
+        );
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn greedy_mesh_of_empty_chunk_is_empty() {
+        let chunk = Chunk::new(3, 3, 3);
+        let greedy_mesh = build_greedy_mesh(&chunk);
+
+        assert!(greedy_mesh.vertices.is_empty());
+        assert!(greedy_mesh.indices.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use super::chunk::Chunk;
+use super::is_solid;
+use super::position::Position;
+use super::voxel_mesh_builder::{build_mesh_with_neighbours, MeshingMode};
+use mesh_data::MeshData;
+use std::collections::hash_map::Keys;
+use std::collections::HashMap;
+
+/// The number of voxels on each side of the chunks of a `VoxelWorld`.
+pub const CHUNK_SIZE: u8 = 32;
+
+/// A sparse and unbounded voxel space, split into chunks of `CHUNK_SIZE` voxels per side.
+/// Chunks are keyed by their chunk coordinates, the chunk `(1, 0, 0)` holding the voxels from
+/// `(CHUNK_SIZE, 0, 0)` to `(2 * CHUNK_SIZE - 1, CHUNK_SIZE - 1, CHUNK_SIZE - 1)`.
+#[derive(Debug, Default)]
+pub struct VoxelWorld {
+    chunks: HashMap<Position, Chunk>,
+}
+
+impl VoxelWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the coordinates of the chunk holding the voxel at `position`.
+    pub fn chunk_position(position: &Position) -> Position {
+        let size = i64::from(CHUNK_SIZE);
+
+        Position::new(
+            floor_div(position.x, size),
+            floor_div(position.y, size),
+            floor_div(position.z, size),
+        )
+    }
+
+    /// Return the position of the voxel relative to the chunk holding it.
+    pub fn local_position(position: &Position) -> Position {
+        let size = i64::from(CHUNK_SIZE);
+
+        Position::new(
+            floor_mod(position.x, size),
+            floor_mod(position.y, size),
+            floor_mod(position.z, size),
+        )
+    }
+
+    /// Return the world position of the first voxel of a chunk.
+    pub fn chunk_origin(chunk_position: &Position) -> Position {
+        let size = i64::from(CHUNK_SIZE);
+
+        Position::new(
+            chunk_position.x * size,
+            chunk_position.y * size,
+            chunk_position.z * size,
+        )
+    }
+
+    /// Voxels in chunks which have never been written are air.
+    pub fn get_voxel(&self, position: &Position) -> u8 {
+        let local_position = Self::local_position(position);
+
+        match self.chunks.get(&Self::chunk_position(position)) {
+            Some(chunk) => chunk
+                .get_voxel(local_position.x, local_position.y, local_position.z)
+                .expect("the local position should always be in the chunk"),
+            None => 0,
+        }
+    }
+
+    /// Set a voxel, creating its chunk if needed. The meshes of the neighbouring chunks depend on
+    /// the voxels of their borders, so the existing chunks touching the voxel are marked dirty as
+    /// well.
+    pub fn set_voxel(&mut self, position: &Position, voxel: u8) {
+        let chunk_position = Self::chunk_position(position);
+        let local_position = Self::local_position(position);
+
+        // Writing air in a missing chunk, or the current voxel, changes nothing.
+        if self.get_voxel(position) == voxel {
+            return;
+        }
+
+        self.chunks
+            .entry(chunk_position)
+            .or_insert_with(|| Chunk::new(CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE))
+            .set_voxel(local_position.x, local_position.y, local_position.z, voxel)
+            .expect("the local position should always be in the chunk");
+
+        for neighbour_position in neighbour_chunk_positions(&chunk_position, &local_position) {
+            if let Some(neighbour_chunk) = self.chunks.get_mut(&neighbour_position) {
+                neighbour_chunk.mark_dirty();
+            }
+        }
+    }
+
+    pub fn is_position_solid(&self, position: &Position) -> bool {
+        is_solid(self.get_voxel(position))
+    }
+
+    pub fn get_chunk(&self, chunk_position: &Position) -> Option<&Chunk> {
+        self.chunks.get(chunk_position)
+    }
+
+    pub fn get_chunk_mut(&mut self, chunk_position: &Position) -> Option<&mut Chunk> {
+        self.chunks.get_mut(chunk_position)
+    }
+
+    pub fn chunk_positions(&self) -> Keys<'_, Position, Chunk> {
+        self.chunks.keys()
+    }
+
+    /// Rebuild the mesh of a chunk if it is dirty and mark it clean. Return `None` if the chunk is
+    /// up to date or missing.
+    pub fn update_chunk_mesh(&mut self, chunk_position: &Position, mode: MeshingMode) -> Option<MeshData> {
+        if !self.chunks.get(chunk_position)?.is_dirty() {
+            return None;
+        }
+
+        let mesh_data = self.build_chunk_mesh(chunk_position, mode);
+        self.chunks.get_mut(chunk_position)?.mark_clean();
+
+        mesh_data
+    }
+
+    /// Build the mesh of a chunk, in coordinates relative to its origin. Faces touching a solid
+    /// voxel of a neighbouring chunk are culled.
+    pub fn build_chunk_mesh(&self, chunk_position: &Position, mode: MeshingMode) -> Option<MeshData> {
+        let chunk = self.chunks.get(chunk_position)?;
+        let origin = Self::chunk_origin(chunk_position);
+
+        Some(build_mesh_with_neighbours(chunk, mode, &|local_position| {
+            self.is_position_solid(&(origin + *local_position))
+        }))
+    }
+}
+
+/// The chunks sharing a face, an edge or a corner with the voxel at `local_position` of the chunk.
+fn neighbour_chunk_positions(chunk_position: &Position, local_position: &Position) -> Vec<Position> {
+    let offsets = |local: i64| match local {
+        0 => vec![-1, 0],
+        local if local == i64::from(CHUNK_SIZE) - 1 => vec![0, 1],
+        _ => vec![0],
+    };
+    let mut positions = Vec::new();
+
+    for x in offsets(local_position.x) {
+        for y in offsets(local_position.y) {
+            for z in offsets(local_position.z) {
+                if (x, y, z) != (0, 0, 0) {
+                    positions.push(*chunk_position + Position::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    positions
+}
+
+fn floor_div(value: i64, divisor: i64) -> i64 {
+    let quotient = value / divisor;
+
+    if value % divisor < 0 {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+fn floor_mod(value: i64, divisor: i64) -> i64 {
+    let remainder = value % divisor;
+
+    if remainder < 0 {
+        remainder + divisor
+    } else {
+        remainder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VoxelWorld, CHUNK_SIZE};
+    use voxel::position::Position;
+    use voxel::voxel_mesh_builder::MeshingMode;
+
+    #[test]
+    fn chunk_and_local_positions() {
+        let size = i64::from(CHUNK_SIZE);
+        let position = Position::new(-1, size, 2 * size + 3);
+
+        assert_eq!(VoxelWorld::chunk_position(&position), Position::new(-1, 1, 2));
+        assert_eq!(VoxelWorld::local_position(&position), Position::new(size - 1, 0, 3));
+    }
+
+    #[test]
+    fn set_voxel_creates_chunks() {
+        let mut world = VoxelWorld::new();
+
+        world.set_voxel(&Position::new(-5, 40, 1_000_000), 3);
+        world.set_voxel(&Position::new(100, 100, 100), 0);
+
+        assert_eq!(world.get_voxel(&Position::new(-5, 40, 1_000_000)), 3);
+        assert_eq!(world.get_voxel(&Position::new(-4, 40, 1_000_000)), 0);
+        assert_eq!(world.chunk_positions().count(), 1, "writing air should not create a chunk");
+    }
+
+    #[test]
+    fn mark_the_neighbour_chunks_of_border_voxels_dirty() {
+        let mut world = VoxelWorld::new();
+        let size = i64::from(CHUNK_SIZE);
+
+        world.set_voxel(&Position::new(size - 1, 0, 0), 1);
+        world.set_voxel(&Position::new(size, 0, 0), 1);
+        world.set_voxel(&Position::new(2 * size, 0, 0), 1);
+
+        let chunk_positions = [Position::new(0, 0, 0), Position::new(1, 0, 0), Position::new(2, 0, 0)];
+
+        for chunk_position in &chunk_positions {
+            world.get_chunk_mut(chunk_position).unwrap().mark_clean();
+        }
+
+        // On the west border of the chunk (1, 0, 0).
+        world.set_voxel(&Position::new(size, 5, 5), 1);
+
+        let is_dirty = |chunk_position: &Position| world.get_chunk(chunk_position).unwrap().is_dirty();
+
+        assert!(is_dirty(&chunk_positions[0]), "the west neighbour should be dirty");
+        assert!(is_dirty(&chunk_positions[1]));
+        assert!(!is_dirty(&chunk_positions[2]), "the east neighbour does not touch the voxel");
+    }
+
+    #[test]
+    fn mesh_culls_faces_at_chunk_borders() {
+        let mut world = VoxelWorld::new();
+        let size = i64::from(CHUNK_SIZE);
+
+        // Two voxels touching each other on both sides of a chunk border.
+        world.set_voxel(&Position::new(size - 1, 0, 0), 1);
+        world.set_voxel(&Position::new(size, 0, 0), 1);
+
+        for mode in &[MeshingMode::Naive, MeshingMode::Greedy] {
+            let left_mesh = world.build_chunk_mesh(&Position::new(0, 0, 0), *mode).unwrap();
+            let right_mesh = world.build_chunk_mesh(&Position::new(1, 0, 0), *mode).unwrap();
+
+            assert_eq!(left_mesh.vertices.len(), 5 * 4, "the east face should be culled");
+            assert_eq!(right_mesh.vertices.len(), 5 * 4, "the west face should be culled");
+        }
+
+        assert!(world.build_chunk_mesh(&Position::new(2, 0, 0), MeshingMode::Naive).is_none());
+    }
+
+    #[test]
+    fn update_the_mesh_of_dirty_chunks() {
+        let mut world = VoxelWorld::new();
+        let size = i64::from(CHUNK_SIZE);
+        let chunk_position = Position::new(0, 0, 0);
+
+        world.set_voxel(&Position::new(size - 1, 0, 0), 1);
+
+        let mesh_data = world.update_chunk_mesh(&chunk_position, MeshingMode::Greedy).unwrap();
+
+        assert_eq!(mesh_data.vertices.len(), 6 * 4);
+        assert!(
+            world.update_chunk_mesh(&chunk_position, MeshingMode::Greedy).is_none(),
+            "the chunk should be clean"
+        );
+
+        world.set_voxel(&Position::new(size, 0, 0), 1);
+
+        let mesh_data = world.update_chunk_mesh(&chunk_position, MeshingMode::Greedy).unwrap();
+
+        assert_eq!(mesh_data.vertices.len(), 5 * 4, "the face against the new voxel should be culled");
+        assert!(world.update_chunk_mesh(&Position::new(2, 0, 0), MeshingMode::Greedy).is_none());
+    }
+}